@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS idx_nodes_search;
+DROP TRIGGER IF EXISTS schemas_search_vector ON schemas;
+DROP TRIGGER IF EXISTS nodes_search_vector ON nodes;
+DROP FUNCTION IF EXISTS schemas_search_vector_trigger();
+DROP FUNCTION IF EXISTS nodes_search_vector_trigger();
+DROP FUNCTION IF EXISTS node_search_vector(TEXT, JSONB, JSONB);
+DROP FUNCTION IF EXISTS search_weight(JSONB, "char");
+ALTER TABLE nodes DROP COLUMN IF EXISTS search_vector;
@@ -0,0 +1,73 @@
+ALTER TABLE nodes ADD COLUMN search_vector TSVECTOR;
+
+-- Weight annotations look like `"x-search-weight": "A"` (A-D) on the schema
+-- root for the node name and on properties for string values in `data`.
+-- `false` excludes a property from the index.
+CREATE FUNCTION search_weight(annotation JSONB, fallback "char") RETURNS "char" AS $$
+    SELECT CASE
+        WHEN annotation #>> '{}' IN ('A', 'B', 'C', 'D') THEN (annotation #>> '{}')::"char"
+        ELSE fallback
+    END;
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE FUNCTION node_search_vector(node_name TEXT, node_data JSONB, schema_json JSONB)
+RETURNS TSVECTOR AS $$
+DECLARE
+    vector TSVECTOR;
+    property RECORD;
+    annotation JSONB;
+BEGIN
+    vector := setweight(
+        to_tsvector('english', node_name),
+        search_weight(schema_json -> 'x-search-weight', 'A')
+    );
+    FOR property IN
+        SELECT key, value FROM jsonb_each(node_data)
+        WHERE jsonb_typeof(value) = 'string' AND key <> 'name'
+    LOOP
+        annotation := schema_json -> 'properties' -> property.key -> 'x-search-weight';
+        CONTINUE WHEN annotation = 'false'::jsonb;
+        vector := vector || setweight(
+            to_tsvector('english', property.value #>> '{}'),
+            search_weight(annotation, 'C')
+        );
+    END LOOP;
+    RETURN vector;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+CREATE FUNCTION nodes_search_vector_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.search_vector := node_search_vector(
+        NEW.name,
+        NEW.data,
+        (SELECT schema_json FROM schemas WHERE title = NEW.schema_title)
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER nodes_search_vector
+    BEFORE INSERT OR UPDATE OF name, data, schema_title ON nodes
+    FOR EACH ROW EXECUTE FUNCTION nodes_search_vector_trigger();
+
+-- re-weight every node of a schema whose annotations changed
+CREATE FUNCTION schemas_search_vector_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE nodes
+    SET search_vector = node_search_vector(name, data, NEW.schema_json)
+    WHERE schema_title = NEW.title;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER schemas_search_vector
+    AFTER UPDATE OF schema_json ON schemas
+    FOR EACH ROW EXECUTE FUNCTION schemas_search_vector_trigger();
+
+UPDATE nodes n
+SET search_vector = node_search_vector(n.name, n.data, s.schema_json)
+FROM schemas s
+WHERE s.title = n.schema_title;
+
+CREATE INDEX idx_nodes_search ON nodes USING GIN (search_vector);
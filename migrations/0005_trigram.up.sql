@@ -0,0 +1,3 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_nodes_name_trgm ON nodes USING GIN (lower(name) gin_trgm_ops);
@@ -0,0 +1,5 @@
+DROP TRIGGER IF EXISTS edges_graph_version ON edges;
+DROP TRIGGER IF EXISTS nodes_graph_version ON nodes;
+DROP TRIGGER IF EXISTS schemas_graph_version ON schemas;
+DROP FUNCTION IF EXISTS bump_graph_version();
+DROP TABLE IF EXISTS graph_version;
@@ -0,0 +1,27 @@
+-- A counter bumped by every statement that writes schemas, nodes or edges.
+-- Caches of computed values compare it to decide whether they are stale.
+CREATE TABLE graph_version (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    version BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO graph_version DEFAULT VALUES;
+
+CREATE FUNCTION bump_graph_version() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE graph_version SET version = version + 1;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER schemas_graph_version
+    AFTER INSERT OR UPDATE OR DELETE ON schemas
+    FOR EACH STATEMENT EXECUTE FUNCTION bump_graph_version();
+
+CREATE TRIGGER nodes_graph_version
+    AFTER INSERT OR UPDATE OR DELETE ON nodes
+    FOR EACH STATEMENT EXECUTE FUNCTION bump_graph_version();
+
+CREATE TRIGGER edges_graph_version
+    AFTER INSERT OR UPDATE OR DELETE ON edges
+    FOR EACH STATEMENT EXECUTE FUNCTION bump_graph_version();
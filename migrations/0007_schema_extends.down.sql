@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_schemas_extends;
+ALTER TABLE schemas DROP COLUMN IF EXISTS extends;
@@ -0,0 +1,8 @@
+-- `x-extends` names the parent schema; a parent cannot be deleted while
+-- schemas extend it.
+ALTER TABLE schemas
+    ADD COLUMN extends TEXT
+    GENERATED ALWAYS AS (schema_json ->> 'x-extends') STORED
+    REFERENCES schemas (title);
+
+CREATE INDEX idx_schemas_extends ON schemas (extends);
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS idx_schemas_id_uri;
@@ -0,0 +1,3 @@
+-- `$ref`s are resolved by `$id`, so it has to identify a single schema.
+CREATE UNIQUE INDEX idx_schemas_id_uri ON schemas ((schema_json ->> '$id'))
+    WHERE schema_json ? '$id';
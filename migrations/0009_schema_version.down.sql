@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS schemas_schema_version ON schemas;
+DROP FUNCTION IF EXISTS bump_schema_version();
+ALTER TABLE graph_version DROP COLUMN IF EXISTS schema_version;
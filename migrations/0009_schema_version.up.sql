@@ -0,0 +1,14 @@
+-- Bumped only by writes to `schemas`, for things derived from the schema
+-- definitions alone such as the typed GraphQL API.
+ALTER TABLE graph_version ADD COLUMN schema_version BIGINT NOT NULL DEFAULT 0;
+
+CREATE FUNCTION bump_schema_version() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE graph_version SET schema_version = schema_version + 1;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER schemas_schema_version
+    AFTER INSERT OR UPDATE OR DELETE ON schemas
+    FOR EACH STATEMENT EXECUTE FUNCTION bump_schema_version();
@@ -0,0 +1,8 @@
+-- GraphQL operations that may be sent by their sha256 hash alone and that
+-- are the only ones executed when the server runs with strict operations.
+CREATE TABLE persisted_operations (
+    sha256 CHAR(64) PRIMARY KEY CHECK (sha256 ~ '^[0-9a-f]{64}$'),
+    name VARCHAR(255),
+    query TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- Accounts that access tokens are issued to. Passwords are stored as argon2
+-- PHC strings.
+CREATE TABLE users (
+    id SERIAL PRIMARY KEY,
+    username VARCHAR(255) NOT NULL UNIQUE CHECK (username <> ''),
+    password_hash TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
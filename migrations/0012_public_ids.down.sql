@@ -0,0 +1,4 @@
+ALTER TABLE edges DROP COLUMN IF EXISTS uid;
+ALTER TABLE nodes DROP COLUMN IF EXISTS uid;
+ALTER TABLE schemas DROP COLUMN IF EXISTS uid;
+DROP FUNCTION IF EXISTS uuid_generate_v7(TIMESTAMPTZ);
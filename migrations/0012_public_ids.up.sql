@@ -0,0 +1,30 @@
+-- Public ids are UUIDs of version 7 (RFC 9562): a millisecond timestamp
+-- followed by random bits, so they sort in creation order. Postgres 18 has
+-- uuidv7(); this one also takes the time, so that the rows stored before
+-- this migration get ids of when they were created.
+CREATE FUNCTION uuid_generate_v7(at TIMESTAMPTZ DEFAULT clock_timestamp()) RETURNS UUID AS $$
+    SELECT encode(
+        set_bit(
+            set_bit(
+                overlay(uuid_send(gen_random_uuid())
+                        PLACING substring(int8send(floor(extract(epoch FROM at) * 1000)::bigint) FROM 3)
+                        FROM 1 FOR 6),
+                52, 1),
+            53, 1),
+        'hex')::uuid;
+$$ LANGUAGE sql VOLATILE;
+
+ALTER TABLE schemas ADD COLUMN uid UUID;
+ALTER TABLE nodes ADD COLUMN uid UUID;
+ALTER TABLE edges ADD COLUMN uid UUID;
+
+UPDATE schemas SET uid = uuid_generate_v7(coalesce(created_at, clock_timestamp()));
+UPDATE nodes SET uid = uuid_generate_v7(coalesce(created_at, clock_timestamp()));
+UPDATE edges SET uid = uuid_generate_v7(coalesce(created_at, clock_timestamp()));
+
+ALTER TABLE schemas ALTER COLUMN uid SET DEFAULT uuid_generate_v7(),
+    ALTER COLUMN uid SET NOT NULL, ADD CONSTRAINT schemas_uid_key UNIQUE (uid);
+ALTER TABLE nodes ALTER COLUMN uid SET DEFAULT uuid_generate_v7(),
+    ALTER COLUMN uid SET NOT NULL, ADD CONSTRAINT nodes_uid_key UNIQUE (uid);
+ALTER TABLE edges ALTER COLUMN uid SET DEFAULT uuid_generate_v7(),
+    ALTER COLUMN uid SET NOT NULL, ADD CONSTRAINT edges_uid_key UNIQUE (uid);
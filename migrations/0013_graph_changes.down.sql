@@ -0,0 +1,19 @@
+UPDATE graph_version
+SET version = version + (SELECT count(*) FROM graph_changes WHERE NOT schemas),
+    schema_version = schema_version + (SELECT count(*) FROM graph_changes WHERE schemas);
+
+CREATE OR REPLACE FUNCTION bump_graph_version() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE graph_version SET version = version + 1;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION bump_schema_version() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE graph_version SET schema_version = schema_version + 1;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TABLE IF EXISTS graph_changes;
@@ -0,0 +1,26 @@
+-- Bumping the single `graph_version` row made every transaction that writes
+-- schemas, nodes or edges wait for the row lock of the one before it until
+-- that one committed. Instead each writing transaction now records itself
+-- here, which does not block other writers and becomes visible together with
+-- its changes. A version is the counter in `graph_version` plus the recorded
+-- transactions, which readers fold into the counter from time to time.
+CREATE TABLE graph_changes (
+    xid XID8 NOT NULL DEFAULT pg_current_xact_id(),
+    -- whether the transaction wrote `schemas` rather than nodes or edges
+    schemas BOOLEAN NOT NULL,
+    PRIMARY KEY (xid, schemas)
+);
+
+CREATE OR REPLACE FUNCTION bump_graph_version() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO graph_changes (schemas) VALUES (FALSE) ON CONFLICT DO NOTHING;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION bump_schema_version() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO graph_changes (schemas) VALUES (TRUE) ON CONFLICT DO NOTHING;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
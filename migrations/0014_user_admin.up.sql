@@ -0,0 +1,2 @@
+-- Administrators may back up and restore the inventory over the API.
+ALTER TABLE users ADD COLUMN admin BOOLEAN NOT NULL DEFAULT false;
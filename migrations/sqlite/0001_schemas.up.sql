@@ -0,0 +1,20 @@
+-- JSON is stored as text and read with the json1 functions. `extends` and the
+-- `$id` index cover what the Postgres migrations 0007 and 0008 add.
+CREATE TABLE schemas (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title TEXT NOT NULL UNIQUE,
+    schema_json TEXT NOT NULL CHECK (json_valid(schema_json)),
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    -- `x-extends` names the parent schema; a parent cannot be deleted while
+    -- schemas extend it.
+    extends TEXT
+        GENERATED ALWAYS AS (json_extract(schema_json, '$."x-extends"')) STORED
+        REFERENCES schemas (title)
+);
+
+CREATE INDEX idx_schemas_extends ON schemas (extends);
+
+-- `$ref`s are resolved by `$id`, so it has to identify a single schema.
+CREATE UNIQUE INDEX idx_schemas_id_uri ON schemas (json_extract(schema_json, '$."$id"'))
+    WHERE json_extract(schema_json, '$."$id"') IS NOT NULL;
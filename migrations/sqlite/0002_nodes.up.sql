@@ -0,0 +1,12 @@
+CREATE TABLE nodes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    schema_title TEXT NOT NULL,
+    name TEXT NOT NULL,
+    data TEXT NOT NULL CHECK (json_valid(data)),
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    FOREIGN KEY (schema_title) REFERENCES schemas(title) ON DELETE CASCADE,
+    UNIQUE(schema_title, name)
+);
+
+CREATE INDEX idx_nodes_schema ON nodes(schema_title);
@@ -0,0 +1,13 @@
+CREATE TABLE edges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_node_id INTEGER NOT NULL,
+    target_node_id INTEGER NOT NULL,
+    weight TEXT NOT NULL,
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    FOREIGN KEY (source_node_id) REFERENCES nodes(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_node_id) REFERENCES nodes(id) ON DELETE CASCADE,
+    UNIQUE(source_node_id, target_node_id, weight)
+);
+
+CREATE INDEX idx_edges_source ON edges(source_node_id);
+CREATE INDEX idx_edges_target ON edges(target_node_id);
@@ -0,0 +1,10 @@
+DROP TRIGGER IF EXISTS edges_delete_version;
+DROP TRIGGER IF EXISTS edges_update_version;
+DROP TRIGGER IF EXISTS edges_insert_version;
+DROP TRIGGER IF EXISTS nodes_delete_version;
+DROP TRIGGER IF EXISTS nodes_update_version;
+DROP TRIGGER IF EXISTS nodes_insert_version;
+DROP TRIGGER IF EXISTS schemas_delete_version;
+DROP TRIGGER IF EXISTS schemas_update_version;
+DROP TRIGGER IF EXISTS schemas_insert_version;
+DROP TABLE IF EXISTS graph_version;
@@ -0,0 +1,40 @@
+-- Counters bumped by every write to schemas, nodes or edges, and by writes to
+-- schemas only, like the Postgres migrations 0006 and 0009. SQLite triggers
+-- fire per row and per event.
+CREATE TABLE graph_version (
+    id INTEGER PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    version INTEGER NOT NULL DEFAULT 0,
+    schema_version INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO graph_version DEFAULT VALUES;
+
+CREATE TRIGGER schemas_insert_version AFTER INSERT ON schemas BEGIN
+    UPDATE graph_version SET version = version + 1, schema_version = schema_version + 1;
+END;
+CREATE TRIGGER schemas_update_version AFTER UPDATE ON schemas BEGIN
+    UPDATE graph_version SET version = version + 1, schema_version = schema_version + 1;
+END;
+CREATE TRIGGER schemas_delete_version AFTER DELETE ON schemas BEGIN
+    UPDATE graph_version SET version = version + 1, schema_version = schema_version + 1;
+END;
+
+CREATE TRIGGER nodes_insert_version AFTER INSERT ON nodes BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER nodes_update_version AFTER UPDATE ON nodes BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER nodes_delete_version AFTER DELETE ON nodes BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+
+CREATE TRIGGER edges_insert_version AFTER INSERT ON edges BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER edges_update_version AFTER UPDATE ON edges BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER edges_delete_version AFTER DELETE ON edges BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_edges_uid;
+DROP INDEX IF EXISTS idx_nodes_uid;
+DROP INDEX IF EXISTS idx_schemas_uid;
+ALTER TABLE edges DROP COLUMN uid;
+ALTER TABLE nodes DROP COLUMN uid;
+ALTER TABLE schemas DROP COLUMN uid;
@@ -0,0 +1,129 @@
+-- Public ids are version 7 UUIDs kept as text, like the Postgres migration
+-- 0012; the storage generates them on insert. The rows stored before this
+-- migration get ids of when they were created.
+--
+-- SQLite only adds NOT NULL columns with a constant default, so the tables
+-- are made again: their rows are copied aside, the tables dropped from the
+-- edges up so that no delete cascades, and the rows copied back with ids.
+
+-- the random bits are drawn once per row here, not once per use
+CREATE TEMP TABLE public_ids AS
+SELECT kind, id, lower(printf('%08x-%04x-7%s-%s%s-%s',
+        ms >> 16, ms & 65535,
+        substr(hex(randomblob(2)), 2, 3),
+        substr('89ab', 1 + abs(random() % 4), 1), substr(hex(randomblob(2)), 2, 3),
+        hex(randomblob(6)))) AS uid
+FROM (
+    SELECT kind, id,
+           CAST((julianday(coalesce(created_at, 'now')) - 2440587.5) * 86400000 AS INTEGER) AS ms
+    FROM (SELECT 'schemas' AS kind, id, created_at FROM schemas
+          UNION ALL SELECT 'nodes', id, created_at FROM nodes
+          UNION ALL SELECT 'edges', id, created_at FROM edges)
+);
+CREATE TEMP TABLE old_schemas AS SELECT id, title, schema_json, created_at, updated_at FROM schemas;
+CREATE TEMP TABLE old_nodes AS SELECT * FROM nodes;
+CREATE TEMP TABLE old_edges AS SELECT * FROM edges;
+CREATE TEMP TABLE sequences AS SELECT name, seq FROM sqlite_sequence;
+
+-- dropping a table drops its indexes and triggers, which are made again below
+DROP TABLE edges;
+DROP TABLE nodes;
+DROP TABLE schemas;
+
+CREATE TABLE schemas (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title TEXT NOT NULL UNIQUE,
+    schema_json TEXT NOT NULL CHECK (json_valid(schema_json)),
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    extends TEXT
+        GENERATED ALWAYS AS (json_extract(schema_json, '$."x-extends"')) STORED
+        REFERENCES schemas (title),
+    uid TEXT NOT NULL
+);
+CREATE TABLE nodes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    schema_title TEXT NOT NULL,
+    name TEXT NOT NULL,
+    data TEXT NOT NULL CHECK (json_valid(data)),
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    uid TEXT NOT NULL,
+    FOREIGN KEY (schema_title) REFERENCES schemas(title) ON DELETE CASCADE,
+    UNIQUE(schema_title, name)
+);
+CREATE TABLE edges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_node_id INTEGER NOT NULL,
+    target_node_id INTEGER NOT NULL,
+    weight TEXT NOT NULL,
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    uid TEXT NOT NULL,
+    FOREIGN KEY (source_node_id) REFERENCES nodes(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_node_id) REFERENCES nodes(id) ON DELETE CASCADE,
+    UNIQUE(source_node_id, target_node_id, weight)
+);
+
+INSERT INTO schemas (id, title, schema_json, created_at, updated_at, uid)
+SELECT s.id, s.title, s.schema_json, s.created_at, s.updated_at, p.uid
+FROM old_schemas s JOIN public_ids p ON p.kind = 'schemas' AND p.id = s.id;
+INSERT INTO nodes (id, schema_title, name, data, created_at, updated_at, uid)
+SELECT n.id, n.schema_title, n.name, n.data, n.created_at, n.updated_at, p.uid
+FROM old_nodes n JOIN public_ids p ON p.kind = 'nodes' AND p.id = n.id;
+INSERT INTO edges (id, source_node_id, target_node_id, weight, created_at, uid)
+SELECT e.id, e.source_node_id, e.target_node_id, e.weight, e.created_at, p.uid
+FROM old_edges e JOIN public_ids p ON p.kind = 'edges' AND p.id = e.id;
+
+-- ids of deleted rows are not handed out again
+UPDATE sqlite_sequence
+SET seq = max(seq, (SELECT s.seq FROM sequences s WHERE s.name = sqlite_sequence.name))
+WHERE name IN (SELECT name FROM sequences);
+INSERT INTO sqlite_sequence (name, seq)
+SELECT name, seq FROM sequences WHERE name NOT IN (SELECT name FROM sqlite_sequence);
+
+DROP TABLE public_ids;
+DROP TABLE old_schemas;
+DROP TABLE old_nodes;
+DROP TABLE old_edges;
+DROP TABLE sequences;
+
+CREATE INDEX idx_schemas_extends ON schemas (extends);
+CREATE UNIQUE INDEX idx_schemas_id_uri ON schemas (json_extract(schema_json, '$."$id"'))
+    WHERE json_extract(schema_json, '$."$id"') IS NOT NULL;
+CREATE INDEX idx_nodes_schema ON nodes(schema_title);
+CREATE INDEX idx_edges_source ON edges(source_node_id);
+CREATE INDEX idx_edges_target ON edges(target_node_id);
+
+CREATE UNIQUE INDEX idx_schemas_uid ON schemas(uid);
+CREATE UNIQUE INDEX idx_nodes_uid ON nodes(uid);
+CREATE UNIQUE INDEX idx_edges_uid ON edges(uid);
+
+CREATE TRIGGER schemas_insert_version AFTER INSERT ON schemas BEGIN
+    UPDATE graph_version SET version = version + 1, schema_version = schema_version + 1;
+END;
+CREATE TRIGGER schemas_update_version AFTER UPDATE ON schemas BEGIN
+    UPDATE graph_version SET version = version + 1, schema_version = schema_version + 1;
+END;
+CREATE TRIGGER schemas_delete_version AFTER DELETE ON schemas BEGIN
+    UPDATE graph_version SET version = version + 1, schema_version = schema_version + 1;
+END;
+
+CREATE TRIGGER nodes_insert_version AFTER INSERT ON nodes BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER nodes_update_version AFTER UPDATE ON nodes BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER nodes_delete_version AFTER DELETE ON nodes BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+
+CREATE TRIGGER edges_insert_version AFTER INSERT ON edges BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER edges_update_version AFTER UPDATE ON edges BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
+CREATE TRIGGER edges_delete_version AFTER DELETE ON edges BEGIN
+    UPDATE graph_version SET version = version + 1;
+END;
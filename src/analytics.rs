@@ -0,0 +1,474 @@
+//! Graph analytics over the whole inventory: degrees, PageRank, betweenness
+//! centrality, connected components and nodes without edges.
+//!
+//! The stored nodes and edges are loaded into a [`DiGraph`] once per
+//! `graph_version`, and every result computed from it is kept until the next
+//! write. An edge label (the `weight` of an edge) restricts the edges an
+//! analysis follows, a schema title the nodes it reports; edges are followed
+//! in their direction, components ignore it.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use petgraph::{
+    graph::{DiGraph, EdgeReference, NodeIndex},
+    unionfind::UnionFind,
+    visit::{EdgeFiltered, EdgeRef, IntoEdgeReferences},
+};
+
+use crate::{
+    error::Error,
+    model::DbNode,
+    repository::{Direction, NodeFilter, Page},
+    storage::Storage,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// At most this many nodes are returned by a ranking.
+pub const MAX_RANKED_NODES: i64 = 500;
+/// The probability of following an edge rather than jumping to any node.
+pub const DEFAULT_DAMPING_FACTOR: f64 = 0.85;
+const PAGE_RANK_ITERATIONS: usize = 100;
+/// Ranks have converged once they move less than this in total.
+const PAGE_RANK_TOLERANCE: f64 = 1e-10;
+/// Betweenness follows the shortest paths from at most this many nodes.
+pub const BETWEENNESS_SOURCES: usize = 1_000;
+/// How often the graph is read while writes keep changing it.
+const LOAD_ATTEMPTS: usize = 3;
+
+/// How many edges of one label enter and leave a node.
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+pub struct NodeDegree {
+    pub node: DbNode,
+    pub in_degree: usize,
+    pub out_degree: usize,
+    /// The degree in the direction asked for.
+    pub degree: usize,
+}
+
+/// The degree distribution of the nodes of one schema.
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+pub struct DegreeStats {
+    pub nodes: usize,
+    /// The edges followed in the whole graph.
+    pub edges: usize,
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// The nodes with the highest degree, most connected first.
+    pub top: Vec<NodeDegree>,
+}
+
+/// A node with its centrality.
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+pub struct NodeScore {
+    pub node: DbNode,
+    pub score: f64,
+}
+
+/// Nodes connected to each other, whatever the direction of their edges.
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+#[graphql(complex)]
+pub struct Component {
+    pub size: usize,
+    /// Ordered by id.
+    #[graphql(skip)]
+    pub nodes: Vec<DbNode>,
+}
+
+/// The analytics of the current graph, replaced after a write.
+#[derive(Default)]
+pub struct AnalyticsCache {
+    current: Mutex<Option<Arc<Analytics>>>,
+}
+
+impl AnalyticsCache {
+    /// The analytics of the stored graph, loaded again when it has changed.
+    pub async fn load(&self, storage: &dyn Storage) -> Result<Arc<Analytics>, Error> {
+        let version = storage.version().await?.graph;
+        if let Some(current) = self
+            .current
+            .lock()
+            .expect("analytics cache poisoned")
+            .as_ref()
+            .filter(|current| current.version == version)
+        {
+            return Ok(current.clone());
+        }
+
+        // the nodes and edges belong together if the version has not moved
+        // while they were read; under a stream of writes the last read is
+        // kept under the older version, so that it is loaded again next time
+        let mut version = version;
+        let mut attempts = 1;
+        let (nodes, edges) = loop {
+            let nodes = storage
+                .list_nodes(&NodeFilter::default(), Page::default())
+                .await?;
+            let edges = storage.list_edges(Page::default()).await?;
+            let after = storage.version().await?.graph;
+            if after == version || attempts == LOAD_ATTEMPTS {
+                break (nodes, edges);
+            }
+            version = after;
+            attempts += 1;
+        };
+        let mut graph = DiGraph::with_capacity(nodes.len(), edges.len());
+        let mut indices = HashMap::with_capacity(nodes.len());
+        for node in &nodes {
+            indices.insert(node.id, graph.add_node(()));
+        }
+        for edge in edges {
+            if let (Some(source), Some(target)) = (
+                indices.get(&edge.source_node_id),
+                indices.get(&edge.target_node_id),
+            ) {
+                graph.add_edge(*source, *target, edge.weight);
+            }
+        }
+
+        let analytics = Arc::new(Analytics {
+            version,
+            nodes,
+            graph,
+            results: Mutex::default(),
+        });
+        *self.current.lock().expect("analytics cache poisoned") = Some(analytics.clone());
+        Ok(analytics)
+    }
+}
+
+/// One version of the graph; node `i` of `graph` is `nodes[i]`.
+pub struct Analytics {
+    version: i64,
+    nodes: Vec<DbNode>,
+    graph: DiGraph<(), String>,
+    results: Mutex<Results>,
+}
+
+/// Computed per edge label, `None` for all edges.
+#[derive(Default)]
+struct Results {
+    degrees: HashMap<Option<String>, Arc<Vec<(usize, usize)>>>,
+    /// Keyed by the damping factor in hundredths.
+    page_rank: HashMap<(Option<String>, u8), Arc<Vec<f64>>>,
+    betweenness: HashMap<Option<String>, Arc<Vec<f64>>>,
+    components: HashMap<Option<String>, Arc<Vec<Vec<usize>>>>,
+}
+
+type Labelled<'a> =
+    EdgeFiltered<&'a DiGraph<(), String>, Box<dyn Fn(EdgeReference<String>) -> bool + 'a>>;
+
+impl Analytics {
+    /// The degree distribution of the nodes of `schema_title`, counting edges
+    /// labelled `edge_label` in `direction`, with the `limit` highest.
+    pub fn degree_stats(
+        &self,
+        schema_title: Option<&str>,
+        edge_label: Option<&str>,
+        direction: Direction,
+        limit: usize,
+    ) -> DegreeStats {
+        let degrees = self.cached(
+            |r| &mut r.degrees,
+            edge_label.map(str::to_string),
+            || {
+                let mut degrees = vec![(0, 0); self.nodes.len()];
+                for edge in self.labelled(edge_label).edge_references() {
+                    degrees[edge.target().index()].0 += 1;
+                    degrees[edge.source().index()].1 += 1;
+                }
+                degrees
+            },
+        );
+
+        let mut selected: Vec<NodeDegree> = self
+            .selected(schema_title)
+            .map(|i| {
+                let (in_degree, out_degree) = degrees[i];
+                NodeDegree {
+                    node: self.nodes[i].clone(),
+                    in_degree,
+                    out_degree,
+                    degree: match direction {
+                        Direction::Incoming => in_degree,
+                        Direction::Outgoing => out_degree,
+                        Direction::Both => in_degree + out_degree,
+                    },
+                }
+            })
+            .collect();
+        selected.sort_by(|a, b| b.degree.cmp(&a.degree).then(a.node.id.cmp(&b.node.id)));
+
+        let count = selected.len();
+        let median = match count {
+            0 => 0.0,
+            _ if count % 2 == 1 => selected[count / 2].degree as f64,
+            _ => (selected[count / 2 - 1].degree + selected[count / 2].degree) as f64 / 2.0,
+        };
+        let total: usize = selected.iter().map(|node| node.degree).sum();
+        let edges = match edge_label {
+            Some(label) => self.graph.edge_weights().filter(|w| *w == label).count(),
+            None => self.graph.edge_count(),
+        };
+        DegreeStats {
+            nodes: count,
+            edges,
+            min: selected.last().map_or(0, |node| node.degree),
+            max: selected.first().map_or(0, |node| node.degree),
+            mean: if count == 0 {
+                0.0
+            } else {
+                total as f64 / count as f64
+            },
+            median,
+            top: selected.into_iter().take(limit).collect(),
+        }
+    }
+
+    /// The nodes of `schema_title` by PageRank over the edges labelled
+    /// `edge_label`, highest first. Ranks sum to 1 over the whole graph.
+    /// `damping_factor`, between 0 and 1, is rounded to two decimals, so that
+    /// at most 101 rankings are kept per label.
+    pub fn page_rank(
+        &self,
+        schema_title: Option<&str>,
+        edge_label: Option<&str>,
+        damping_factor: f64,
+        limit: usize,
+    ) -> Vec<NodeScore> {
+        let hundredths = (damping_factor.clamp(0.0, 1.0) * 100.0).round();
+        let key = (edge_label.map(str::to_string), hundredths as u8);
+        let ranks = self.cached(
+            |r| &mut r.page_rank,
+            key,
+            || self.compute_page_rank(edge_label, hundredths / 100.0),
+        );
+        self.ranking(schema_title, &ranks, limit)
+    }
+
+    /// The nodes of `schema_title` by betweenness centrality over the edges
+    /// labelled `edge_label`, highest first: the share of shortest paths
+    /// between other nodes that pass through them. Exact up to
+    /// [`BETWEENNESS_SOURCES`] nodes and estimated from the paths of that
+    /// many evenly spread ones beyond, so that it costs at most that many
+    /// breadth-first searches over the graph.
+    pub fn betweenness(
+        &self,
+        schema_title: Option<&str>,
+        edge_label: Option<&str>,
+        limit: usize,
+    ) -> Vec<NodeScore> {
+        let scores = self.cached(
+            |r| &mut r.betweenness,
+            edge_label.map(str::to_string),
+            || self.compute_betweenness(edge_label),
+        );
+        self.ranking(schema_title, &scores, limit)
+    }
+
+    /// The weakly connected components over the edges labelled `edge_label`
+    /// with at most `max_size` nodes, largest first.
+    pub fn connected_components(
+        &self,
+        edge_label: Option<&str>,
+        max_size: Option<usize>,
+    ) -> Vec<Component> {
+        let components = self.cached(
+            |r| &mut r.components,
+            edge_label.map(str::to_string),
+            || {
+                let mut sets = UnionFind::new(self.nodes.len());
+                for edge in self.labelled(edge_label).edge_references() {
+                    sets.union(edge.source().index(), edge.target().index());
+                }
+                let mut components: HashMap<usize, Vec<usize>> = HashMap::new();
+                for (i, set) in sets.into_labeling().into_iter().enumerate() {
+                    components.entry(set).or_default().push(i);
+                }
+                let mut components: Vec<Vec<usize>> = components.into_values().collect();
+                for component in &mut components {
+                    component.sort_by_key(|i| self.nodes[*i].id);
+                }
+                components.sort_by(|a, b| {
+                    b.len()
+                        .cmp(&a.len())
+                        .then(self.nodes[a[0]].id.cmp(&self.nodes[b[0]].id))
+                });
+                components
+            },
+        );
+
+        components
+            .iter()
+            .filter(|component| max_size.is_none_or(|max_size| component.len() <= max_size))
+            .map(|component| Component {
+                size: component.len(),
+                nodes: component.iter().map(|i| self.nodes[*i].clone()).collect(),
+            })
+            .collect()
+    }
+
+    /// The nodes of `schema_title` without any edge, newest first.
+    pub fn orphan_nodes(&self, schema_title: Option<&str>) -> Vec<DbNode> {
+        self.selected(schema_title)
+            .filter(|i| {
+                self.graph
+                    .neighbors_undirected(NodeIndex::new(*i))
+                    .next()
+                    .is_none()
+            })
+            .map(|i| self.nodes[i].clone())
+            .collect()
+    }
+
+    /// The graph with only the edges labelled `label`, or all of them.
+    fn labelled<'a>(&'a self, label: Option<&'a str>) -> Labelled<'a> {
+        EdgeFiltered(
+            &self.graph,
+            Box::new(move |edge: EdgeReference<String>| {
+                label.is_none_or(|label| edge.weight() == label)
+            }),
+        )
+    }
+
+    /// The positions of the nodes of `schema_title`, or of all nodes.
+    fn selected<'a>(&'a self, schema_title: Option<&'a str>) -> impl Iterator<Item = usize> + 'a {
+        (0..self.nodes.len())
+            .filter(move |i| schema_title.is_none_or(|title| self.nodes[*i].schema_title == title))
+    }
+
+    /// The value `select` keeps under `key`, computed the first time.
+    fn cached<K: Eq + Hash, V>(
+        &self,
+        select: fn(&mut Results) -> &mut HashMap<K, Arc<V>>,
+        key: K,
+        compute: impl FnOnce() -> V,
+    ) -> Arc<V> {
+        if let Some(value) =
+            select(&mut self.results.lock().expect("analytics cache poisoned")).get(&key)
+        {
+            return value.clone();
+        }
+        // computed without the lock, which other analyses of this graph need
+        let value = Arc::new(compute());
+        select(&mut self.results.lock().expect("analytics cache poisoned"))
+            .entry(key)
+            .or_insert(value)
+            .clone()
+    }
+
+    fn ranking(&self, schema_title: Option<&str>, scores: &[f64], limit: usize) -> Vec<NodeScore> {
+        let mut ranked: Vec<NodeScore> = self
+            .selected(schema_title)
+            .map(|i| NodeScore {
+                node: self.nodes[i].clone(),
+                score: scores[i],
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.node.id.cmp(&b.node.id)));
+        ranked.truncate(limit);
+        ranked
+    }
+
+    /// Power iteration over the edges. petgraph's `page_rank` looks at every
+    /// pair of nodes in every iteration, which is too slow for an inventory.
+    /// Nodes without outgoing edges spread their rank over all nodes.
+    fn compute_page_rank(&self, edge_label: Option<&str>, damping_factor: f64) -> Vec<f64> {
+        let count = self.nodes.len();
+        if count == 0 {
+            return Vec::new();
+        }
+        let graph = self.labelled(edge_label);
+        let mut out_degrees = vec![0usize; count];
+        for edge in graph.edge_references() {
+            out_degrees[edge.source().index()] += 1;
+        }
+
+        let mut ranks = vec![1.0 / count as f64; count];
+        for _ in 0..PAGE_RANK_ITERATIONS {
+            let dangling: f64 = (0..count)
+                .filter(|i| out_degrees[*i] == 0)
+                .map(|i| ranks[i])
+                .sum();
+            let mut next =
+                vec![(1.0 - damping_factor + damping_factor * dangling) / count as f64; count];
+            for edge in graph.edge_references() {
+                let source = edge.source().index();
+                next[edge.target().index()] +=
+                    damping_factor * ranks[source] / out_degrees[source] as f64;
+            }
+            let change: f64 = next.iter().zip(&ranks).map(|(a, b)| (a - b).abs()).sum();
+            ranks = next;
+            if change < PAGE_RANK_TOLERANCE {
+                break;
+            }
+        }
+        ranks
+    }
+
+    /// Brandes' algorithm with a breadth-first search from every node, or
+    /// from every `step`th one on larger graphs with the sums scaled to all,
+    /// normalized by the `(n - 1)(n - 2)` ordered pairs of other nodes.
+    /// petgraph has no betweenness centrality.
+    fn compute_betweenness(&self, edge_label: Option<&str>) -> Vec<f64> {
+        let count = self.nodes.len();
+        let graph = self.labelled(edge_label);
+        let mut successors = vec![Vec::new(); count];
+        for edge in graph.edge_references() {
+            successors[edge.source().index()].push(edge.target().index());
+        }
+
+        let step = count.div_ceil(BETWEENNESS_SOURCES).max(1);
+        let mut centrality = vec![0.0; count];
+        for source in (0..count).step_by(step) {
+            let mut order = Vec::new();
+            let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); count];
+            let mut paths = vec![0.0; count];
+            let mut distances = vec![usize::MAX; count];
+            paths[source] = 1.0;
+            distances[source] = 0;
+
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+                for &next in &successors[node] {
+                    if distances[next] == usize::MAX {
+                        distances[next] = distances[node] + 1;
+                        queue.push_back(next);
+                    }
+                    if distances[next] == distances[node] + 1 {
+                        paths[next] += paths[node];
+                        predecessors[next].push(node);
+                    }
+                }
+            }
+
+            let mut dependencies = vec![0.0; count];
+            while let Some(node) = order.pop() {
+                for &previous in &predecessors[node] {
+                    dependencies[previous] +=
+                        paths[previous] / paths[node] * (1.0 + dependencies[node]);
+                }
+                if node != source {
+                    centrality[node] += dependencies[node];
+                }
+            }
+        }
+
+        if count > 2 {
+            let pairs = ((count - 1) * (count - 2)) as f64;
+            let sampled = count as f64 / count.div_ceil(step) as f64;
+            for score in &mut centrality {
+                *score *= sampled / pairs;
+            }
+        }
+        centrality
+    }
+}
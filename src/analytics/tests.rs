@@ -0,0 +1,136 @@
+use std::sync::Mutex;
+
+use petgraph::graph::{DiGraph, NodeIndex};
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{Analytics, BETWEENNESS_SOURCES, DEFAULT_DAMPING_FACTOR};
+use crate::model::DbNode;
+
+/// `count` nodes named by their position, with `edges` between positions.
+fn analytics(count: usize, edges: &[(usize, usize)]) -> Analytics {
+    let nodes = (0..count)
+        .map(|i| DbNode {
+            id: i as i32 + 1,
+            uid: Uuid::nil(),
+            schema_title: "Node".to_string(),
+            name: i.to_string(),
+            data: json!({}),
+            created_at: None,
+            updated_at: None,
+        })
+        .collect();
+    let mut graph = DiGraph::with_capacity(count, edges.len());
+    for _ in 0..count {
+        graph.add_node(());
+    }
+    for &(source, target) in edges {
+        graph.add_edge(
+            NodeIndex::new(source),
+            NodeIndex::new(target),
+            "links".to_string(),
+        );
+    }
+    Analytics {
+        version: 0,
+        nodes,
+        graph,
+        results: Mutex::default(),
+    }
+}
+
+fn assert_scores(scores: &[f64], expected: &[f64]) {
+    assert_eq!(scores.len(), expected.len());
+    for (score, expected) in scores.iter().zip(expected) {
+        assert!(
+            (score - expected).abs() < 1e-6,
+            "{scores:?} is not {expected:?}"
+        );
+    }
+}
+
+#[test]
+fn page_rank_of_a_cycle_is_even() {
+    let analytics = analytics(3, &[(0, 1), (1, 2), (2, 0)]);
+    let ranks = analytics.compute_page_rank(None, DEFAULT_DAMPING_FACTOR);
+    assert_scores(&ranks, &[1.0 / 3.0; 3]);
+}
+
+#[test]
+fn page_rank_of_a_star_gathers_in_its_centre() {
+    // every leaf links to the centre, which links nowhere: its rank is spread
+    // over all nodes, so that r_c = 0.15/5 + 0.85 (4 r_l + r_c/5) and
+    // r_l = 0.15/5 + 0.85 r_c/5, summing to 1
+    let analytics = analytics(5, &[(1, 0), (2, 0), (3, 0), (4, 0)]);
+    let ranks = analytics.compute_page_rank(None, 0.85);
+    let leaf = (0.03 + 0.17 * 0.03 / 0.83) / (1.0 - 4.0 * 0.17 * 0.85 / 0.83);
+    let centre = 1.0 - 4.0 * leaf;
+    assert_scores(&ranks, &[centre, leaf, leaf, leaf, leaf]);
+    assert!((ranks.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn page_rank_spreads_dangling_nodes_over_all() {
+    // 0 -> 1 and 2 links nowhere, neither does 1
+    let analytics = analytics(3, &[(0, 1)]);
+    let ranks = analytics.compute_page_rank(None, 0.5);
+    // r0 = r2 = (0.5 + 0.5 (r1 + r2)) / 3 and r1 = r0 + 0.5 r0, so r0 = 2/7
+    assert_scores(&ranks, &[2.0 / 7.0, 3.0 / 7.0, 2.0 / 7.0]);
+    assert!((ranks.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn page_rank_follows_the_edge_label() {
+    let mut analytics = analytics(2, &[(0, 1)]);
+    analytics
+        .graph
+        .add_edge(NodeIndex::new(1), NodeIndex::new(0), "other".to_string());
+    assert_ne!(
+        analytics.compute_page_rank(Some("links"), 0.85),
+        analytics.compute_page_rank(None, 0.85)
+    );
+    assert_scores(&analytics.compute_page_rank(None, 0.85), &[0.5, 0.5]);
+}
+
+#[test]
+fn betweenness_of_a_path_peaks_in_the_middle() {
+    // of the 6 ordered pairs of other nodes, 1 lies on 0->2 and 0->3, 2 on
+    // 0->3 and 1->3
+    let analytics = analytics(4, &[(0, 1), (1, 2), (2, 3)]);
+    let scores = analytics.compute_betweenness(None);
+    assert_scores(&scores, &[0.0, 2.0 / 6.0, 2.0 / 6.0, 0.0]);
+}
+
+#[test]
+fn betweenness_of_a_star_is_its_centre() {
+    // every path between two leaves passes the centre
+    let edges: Vec<(usize, usize)> = (1..5).flat_map(|leaf| [(0, leaf), (leaf, 0)]).collect();
+    let analytics = analytics(5, &edges);
+    let scores = analytics.compute_betweenness(None);
+    assert_scores(&scores, &[1.0, 0.0, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn betweenness_splits_between_equal_paths() {
+    // 0 reaches 3 through 1 or 2
+    let analytics = analytics(4, &[(0, 1), (0, 2), (1, 3), (2, 3)]);
+    let scores = analytics.compute_betweenness(None);
+    assert_scores(&scores, &[0.0, 0.5 / 6.0, 0.5 / 6.0, 0.0]);
+}
+
+#[test]
+fn betweenness_is_estimated_on_large_graphs() {
+    // a path long enough to be sampled: the middle lies on the paths from
+    // the half before it to the half after it, a quarter of all pairs
+    let count = 2 * BETWEENNESS_SOURCES + 1;
+    let edges: Vec<(usize, usize)> = (1..count).map(|i| (i - 1, i)).collect();
+    let scores = analytics(count, &edges).compute_betweenness(None);
+    assert_eq!(scores[0], 0.0);
+    assert_eq!(scores[count - 1], 0.0);
+    let middle = count / 2;
+    assert!(
+        (scores[middle] - 0.25).abs() < 0.01,
+        "{} is not about 0.25",
+        scores[middle]
+    );
+}
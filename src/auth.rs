@@ -0,0 +1,105 @@
+//! Passwords and access tokens.
+//!
+//! Passwords are hashed with argon2. Access tokens are JWTs signed with
+//! HS256 by `auth.token_secret`, naming the user in `sub` and whether they
+//! are an administrator in `admin`.
+
+use std::time::Duration;
+
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::AuthConfig, error::Error, model::DbUser};
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// The token of an `Authorization: Bearer` header, not yet verified.
+#[derive(Debug, Clone)]
+pub struct BearerToken(pub String);
+
+impl BearerToken {
+    pub fn from_headers(headers: &http::HeaderMap) -> Option<Self> {
+        let value = headers.get(http::header::AUTHORIZATION)?.to_str().ok()?;
+        let token = value.strip_prefix("Bearer ")?.trim();
+        (!token.is_empty()).then(|| BearerToken(token.to_string()))
+    }
+}
+
+/// The claims of an access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// The username.
+    pub sub: String,
+    pub uid: i32,
+    /// Tokens issued before users had roles carry no `admin` claim.
+    #[serde(default)]
+    pub admin: bool,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Error::ValidationFailed(format!(
+            "passwords must be at least {MIN_PASSWORD_LENGTH} characters long"
+        )));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| Error::internal(e.to_string()))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+fn secret(auth: &AuthConfig) -> Result<&[u8], Error> {
+    auth.token_secret
+        .as_deref()
+        .map(str::as_bytes)
+        .ok_or_else(|| Error::ValidationFailed("auth.token_secret is not set".to_string()))
+}
+
+/// A token for `user`, valid for `ttl` or else `auth.token_ttl_secs`.
+pub fn issue_token(
+    auth: &AuthConfig,
+    user: &DbUser,
+    ttl: Option<Duration>,
+) -> Result<String, Error> {
+    let ttl = ttl.unwrap_or(Duration::from_secs(auth.token_ttl_secs));
+    let now = chrono::Utc::now().timestamp();
+    let claims = Claims {
+        sub: user.username.clone(),
+        uid: user.id,
+        admin: user.admin,
+        iat: now,
+        exp: now.saturating_add(i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX)),
+    };
+    jsonwebtoken::encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret(auth)?),
+    )
+    .map_err(Error::internal)
+}
+
+/// The claims of a token signed by `auth.token_secret` that has not expired.
+pub fn verify_token(auth: &AuthConfig, token: &str) -> Result<Claims, Error> {
+    jsonwebtoken::decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret(auth)?),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|e| Error::Unauthorized(format!("invalid token: {e}")))
+}
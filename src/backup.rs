@@ -0,0 +1,158 @@
+//! Snapshots of the whole inventory.
+//!
+//! A [`Backup`] holds every schema, node and edge with its ids and timestamps,
+//! read in one [snapshot](Storage::snapshot) so that it is consistent. Its
+//! archive is the gzip-compressed JSON of the backup, which names its
+//! [`FORMAT`] and [`VERSION`] so that older archives can still be read when
+//! the layout changes. Users and persisted operations are not part of it.
+
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+};
+
+use chrono::{DateTime, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use uuid::{NoContext, Timestamp, Uuid};
+
+use crate::{
+    error::Error,
+    model::{DbEdge, DbNode, DbSchema},
+    storage::{Snapshot, Storage},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// The `format` of every archive.
+pub const FORMAT: &str = "lixiv-backup";
+/// The archive layout written by this version. Version 1 archives have no
+/// public ids; their rows get new ones when they are restored.
+pub const VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    pub format: String,
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Ordered by id, so parents come before the schemas extending them.
+    pub schemas: Vec<DbSchema>,
+    pub nodes: Vec<DbNode>,
+    pub edges: Vec<DbEdge>,
+}
+
+/// How [`restore`] treats what is already stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, async_graphql::Enum, clap::ValueEnum)]
+pub enum RestoreMode {
+    /// Only restore into a database without schemas, nodes and edges, keeping
+    /// the ids and public ids of the backup.
+    #[default]
+    Empty,
+    /// Add to what is stored: schemas and nodes that exist by title and by
+    /// `(schema, name)` are kept as they are, the others get new ids that the
+    /// edges are remapped to. Public ids are kept unless they are taken.
+    Merge,
+}
+
+/// Number of rows written by a restore, and of those in the backup that were
+/// already stored.
+#[derive(Debug, Default, Serialize, async_graphql::SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub schemas: usize,
+    pub nodes: usize,
+    pub edges: usize,
+    pub existing_schemas: usize,
+    pub existing_nodes: usize,
+    pub existing_edges: usize,
+}
+
+/// Read everything in one snapshot.
+pub async fn backup(storage: &dyn Storage) -> Result<Backup, Error> {
+    let Snapshot {
+        schemas,
+        nodes,
+        edges,
+    } = storage.snapshot().await?;
+    Ok(Backup {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created_at: Utc::now(),
+        schemas,
+        nodes,
+        edges,
+    })
+}
+
+impl Backup {
+    pub fn to_archive(&self) -> Result<Vec<u8>, Error> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, self).map_err(Error::internal)?;
+        encoder.flush().map_err(Error::internal)?;
+        encoder.finish().map_err(Error::internal)
+    }
+
+    pub fn from_archive(archive: &[u8]) -> Result<Self, Error> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            Error::ValidationFailed(format!("not a lixiv backup archive: {e}"))
+        };
+        let mut text = Vec::new();
+        GzDecoder::new(archive)
+            .read_to_end(&mut text)
+            .map_err(|e| invalid(&e))?;
+
+        // check the version before the layout it determines
+        #[derive(Deserialize)]
+        struct Header {
+            format: String,
+            version: u32,
+        }
+        let header: Header = serde_json::from_slice(&text).map_err(|e| invalid(&e))?;
+        if header.format != FORMAT {
+            return Err(invalid(&format!("unknown format '{}'", header.format)));
+        }
+        if !(1..=VERSION).contains(&header.version) {
+            return Err(Error::ValidationFailed(format!(
+                "backup archives of version {} are not supported, expected {VERSION}",
+                header.version
+            )));
+        }
+        serde_json::from_slice(&text).map_err(|e| invalid(&e))
+    }
+}
+
+/// `None` for the nil ids of version 1 archives, which get new public ids.
+pub(crate) fn public_id(uid: Uuid) -> Option<Uuid> {
+    (!uid.is_nil()).then_some(uid)
+}
+
+/// A new public id for a restored row whose own is missing or taken, of when
+/// the row was created.
+pub(crate) fn new_public_id(created_at: Option<DateTime<Utc>>) -> Uuid {
+    let created_at = created_at.unwrap_or_else(Utc::now);
+    Uuid::new_v7(Timestamp::from_unix(
+        NoContext,
+        created_at.timestamp() as u64,
+        created_at.timestamp_subsec_nanos(),
+    ))
+}
+
+/// Write `backup` in a single transaction, as `mode` says.
+pub async fn restore(
+    storage: &dyn Storage,
+    backup: &Backup,
+    mode: RestoreMode,
+) -> Result<RestoreReport, Error> {
+    let node_ids: HashSet<i32> = backup.nodes.iter().map(|node| node.id).collect();
+    if let Some(edge) = backup.edges.iter().find(|edge| {
+        !node_ids.contains(&edge.source_node_id) || !node_ids.contains(&edge.target_node_id)
+    }) {
+        return Err(Error::ValidationFailed(format!(
+            "edge {} refers to a node that is not in the backup",
+            edge.id
+        )));
+    }
+    storage.restore(backup, mode).await
+}
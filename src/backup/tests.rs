@@ -0,0 +1,135 @@
+use serde_json::{Value, json};
+
+use super::{Backup, RestoreMode, backup, restore};
+use crate::{
+    error::Error,
+    import::import_graph,
+    storage::{MemoryStorage, Storage},
+};
+
+async fn storage(document: Value) -> MemoryStorage {
+    let storage = MemoryStorage::default();
+    import_graph(&storage, &document.to_string())
+        .await
+        .expect("the document is imported");
+    storage
+}
+
+fn inventory() -> Value {
+    json!({
+        "schemas": [
+            { "title": "Food", "type": "object" },
+            { "title": "Ingredient", "type": "object" }
+        ],
+        "nodes": [
+            { "schema": "Food", "name": "Lasagne" },
+            { "schema": "Ingredient", "name": "Tomatoes" },
+            { "schema": "Ingredient", "name": "Basil" }
+        ],
+        "edges": [
+            { "source": "Food/Lasagne", "target": "Ingredient/Tomatoes", "weight": "has-ingredient" },
+            { "source": "Food/Lasagne", "target": "Ingredient/Basil", "weight": "has-ingredient" }
+        ]
+    })
+}
+
+/// The backup of `storage` after a trip through its archive.
+async fn archived(storage: &dyn Storage) -> Backup {
+    let archive = backup(storage)
+        .await
+        .unwrap()
+        .to_archive()
+        .expect("the backup is archived");
+    Backup::from_archive(&archive).expect("the archive is read")
+}
+
+fn contents(backup: &Backup) -> Value {
+    json!([backup.schemas, backup.nodes, backup.edges])
+}
+
+#[tokio::test]
+async fn empty_restores_keep_every_id() {
+    let original = archived(&storage(inventory()).await).await;
+
+    let restored = MemoryStorage::default();
+    let report = restore(&restored, &original, RestoreMode::Empty)
+        .await
+        .expect("the backup is restored");
+    assert_eq!((report.schemas, report.nodes, report.edges), (2, 3, 2));
+    assert_eq!(contents(&archived(&restored).await), contents(&original));
+
+    let error = restore(&restored, &original, RestoreMode::Empty)
+        .await
+        .expect_err("the storage is not empty");
+    assert!(matches!(error, Error::ValidationFailed(_)), "{error:?}");
+}
+
+#[tokio::test]
+async fn merges_keep_what_is_stored() {
+    let original = archived(&storage(inventory()).await).await;
+    let stored = storage(json!({
+        "schemas": [{ "title": "Ingredient", "type": "object" }],
+        "nodes": [
+            { "schema": "Ingredient", "name": "Basil" },
+            { "schema": "Ingredient", "name": "Oregano" }
+        ]
+    }))
+    .await;
+
+    let report = restore(&stored, &original, RestoreMode::Merge)
+        .await
+        .expect("the backup is merged");
+    assert_eq!(
+        (
+            report.schemas,
+            report.nodes,
+            report.edges,
+            report.existing_schemas,
+            report.existing_nodes
+        ),
+        (1, 2, 2, 1, 1)
+    );
+
+    let merged = archived(&stored).await;
+    let name = |id: i32| {
+        let node = merged.nodes.iter().find(|node| node.id == id).unwrap();
+        format!("{}/{}", node.schema_title, node.name)
+    };
+    let mut edges: Vec<(String, String)> = merged
+        .edges
+        .iter()
+        .map(|edge| (name(edge.source_node_id), name(edge.target_node_id)))
+        .collect();
+    edges.sort();
+    assert_eq!(
+        edges,
+        [
+            ("Food/Lasagne".to_string(), "Ingredient/Basil".to_string()),
+            (
+                "Food/Lasagne".to_string(),
+                "Ingredient/Tomatoes".to_string()
+            )
+        ]
+    );
+    let uids = |backup: &Backup| {
+        backup
+            .nodes
+            .iter()
+            .find(|node| node.name == "Lasagne")
+            .map(|node| node.uid)
+    };
+    assert_eq!(uids(&merged), uids(&original));
+
+    let report = restore(&stored, &original, RestoreMode::Merge)
+        .await
+        .expect("merging twice changes nothing");
+    assert_eq!(
+        (
+            report.nodes,
+            report.edges,
+            report.existing_nodes,
+            report.existing_edges
+        ),
+        (0, 0, 3, 2)
+    );
+}
@@ -0,0 +1,426 @@
+//! `lixiv`, the administrative command line tool. It works on the database
+//! directly, through the same library as the server, and prints what it
+//! creates or reads as JSON.
+
+use std::{
+    collections::HashSet,
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use lixiv_backend::{
+    auth,
+    backup::{self, Backup, RestoreMode},
+    config::{Config, ConfigArgs, StorageKind},
+    database::{self, MIGRATOR},
+    error::Error,
+    export::{ExportFormat, Selection, export_graph},
+    import::{CsvMapping, import_csv, import_graph},
+    repository::{NewEdge, NewNode, NewSchema, NewUser, Page, Repositories, UserRepository},
+    storage::PgStorage,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgPool, migrate::Migrate};
+
+type Result<T, E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+#[derive(Parser)]
+#[command(version, about = "Administer a lixiv inventory")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply, revert or list database migrations
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Add and list schemas
+    #[command(subcommand)]
+    Schema(SchemaCommand),
+    /// Read, create and delete nodes
+    #[command(subcommand)]
+    Node(NodeCommand),
+    /// Create edges between nodes
+    #[command(subcommand)]
+    Edge(EdgeCommand),
+    /// Import a JSON graph document, or a CSV file with --mapping, in one
+    /// transaction
+    Import {
+        file: PathBuf,
+        /// JSON file describing the target schema and column mapping of a CSV file
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+    },
+    /// Export the graph, or the part selected by schema or root node
+    Export {
+        #[arg(long, default_value = "json")]
+        format: ExportFormat,
+        /// Only export nodes of this schema, may be repeated
+        #[arg(long = "schema")]
+        schemas: Vec<String>,
+        /// Only export nodes reachable from this node id
+        #[arg(long)]
+        root: Option<i32>,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Write an archive of every schema, node and edge, read in one snapshot
+    Backup {
+        /// The archive to write
+        output: PathBuf,
+    },
+    /// Restore an archive written by `backup`
+    Restore {
+        archive: PathBuf,
+        /// Restore into an empty database keeping ids, or merge into what is
+        /// stored with new ids
+        #[arg(long, value_enum, default_value_t)]
+        mode: RestoreMode,
+    },
+    /// Manage the users access tokens are issued to
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Issue access tokens
+    #[command(subcommand)]
+    Token(TokenCommand),
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Apply all pending migrations
+    Up,
+    /// Revert the latest applied migrations
+    Down {
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// List applied and pending migrations
+    Status,
+}
+
+#[derive(Subcommand)]
+enum SchemaCommand {
+    /// Store the JSON schema in a file
+    Add {
+        file: PathBuf,
+        /// Title to store the schema under, defaults to its `title`
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Print every schema
+    List,
+}
+
+#[derive(Subcommand)]
+enum NodeCommand {
+    /// Print a node
+    Get { id: i32 },
+    /// Create a node of a schema
+    Create {
+        #[arg(long)]
+        schema: String,
+        #[arg(long)]
+        name: String,
+        /// The node's data as a JSON object
+        #[arg(long, default_value = "{}")]
+        data: String,
+        /// Also print the nodes of the schema whose names are at least this similar
+        #[arg(long)]
+        duplicate_threshold: Option<f32>,
+    },
+    /// Delete a node and its edges
+    Delete { id: i32 },
+}
+
+#[derive(Subcommand)]
+enum EdgeCommand {
+    /// Create an edge from the source to the target node
+    Create {
+        source: i32,
+        target: i32,
+        weight: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Create a user, prompting for the password unless it is piped to stdin
+    Create {
+        username: String,
+        /// Let the user back up and restore the inventory over the API
+        #[arg(long)]
+        admin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokenCommand {
+    /// Print an access token for a user, signed with auth.token_secret
+    Issue {
+        username: String,
+        /// Seconds until the token expires, defaults to auth.token_ttl_secs
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    // variables from .env count as set in the environment
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+
+    if let Err(e) = run(cli).await {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let config = Config::load(&cli.config)?;
+    if config.storage != StorageKind::Database || config.database.is_sqlite() {
+        return Err(
+            "lixiv works on a Postgres database, SQLite and the in-memory storage are not \
+             supported"
+                .into(),
+        );
+    }
+    let pool = database::connect(&config.database).await?;
+
+    if let Command::Migrate(command) = cli.command {
+        return migrate(&pool, command).await;
+    }
+    let pending = pending_migrations(&pool).await?;
+    if pending > 0 {
+        return Err(format!(
+            "the database has {pending} pending migration(s), run `lixiv migrate up` first"
+        )
+        .into());
+    }
+    let storage = PgStorage::new(pool.clone());
+    let repositories = Repositories::new(Arc::new(storage.clone()));
+    let users = UserRepository::new(pool.clone());
+
+    match cli.command {
+        Command::Migrate(_) => unreachable!("handled above"),
+        Command::Schema(SchemaCommand::Add { file, title }) => {
+            let definition: Value = serde_json::from_str(&read(&file)?)
+                .map_err(|e| format!("{}: {e}", file.display()))?;
+            let title = match title {
+                Some(title) => title,
+                None => definition
+                    .get("title")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .ok_or_else(|| {
+                        format!("{}: the schema has no title, pass --title", file.display())
+                    })?,
+            };
+            let schema = NewSchema {
+                title,
+                schema_json: definition,
+            };
+            print(&repositories.schemas.create(schema).await?)
+        }
+        Command::Schema(SchemaCommand::List) => {
+            print(&repositories.schemas.list(Page::default()).await?)
+        }
+        Command::Node(NodeCommand::Get { id }) => {
+            let node = repositories
+                .nodes
+                .get(id)
+                .await?
+                .ok_or_else(|| Error::NotFound(format!("node {id} does not exist")))?;
+            print(&node)
+        }
+        Command::Node(NodeCommand::Create {
+            schema,
+            name,
+            data,
+            duplicate_threshold,
+        }) => {
+            let data: Value = serde_json::from_str(&data).map_err(|e| format!("--data: {e}"))?;
+            let node = NewNode {
+                schema_title: schema,
+                name,
+                data,
+            };
+            print(&repositories.nodes.create(node, duplicate_threshold).await?)
+        }
+        Command::Node(NodeCommand::Delete { id }) => {
+            if !repositories.nodes.delete(id).await? {
+                return Err(Error::NotFound(format!("node {id} does not exist")).into());
+            }
+            Ok(())
+        }
+        Command::Edge(EdgeCommand::Create {
+            source,
+            target,
+            weight,
+        }) => {
+            let edge = NewEdge {
+                source_node_id: source,
+                target_node_id: target,
+                weight,
+            };
+            print(&repositories.edges.create(edge).await?)
+        }
+        Command::Import { file, mapping } => {
+            let text = read(&file)?;
+            let report = match mapping {
+                None => import_graph(&storage, &text).await,
+                Some(mapping_file) => {
+                    let mapping: CsvMapping = serde_json::from_str(&read(&mapping_file)?)
+                        .map_err(|e| format!("{}: {e}", mapping_file.display()))?;
+                    import_csv(&storage, &text, &mapping).await
+                }
+            }
+            .map_err(|e| format!("{}: {e}", file.display()))?;
+            eprintln!(
+                "imported {} schema(s), {} node(s), {} edge(s)",
+                report.schemas, report.nodes, report.edges
+            );
+            Ok(())
+        }
+        Command::Export {
+            format,
+            schemas,
+            root,
+            output,
+        } => {
+            let selection = Selection { schemas, root };
+            let rendered = export_graph(&storage, &selection).await?.render(format);
+            match output {
+                Some(path) => std::fs::write(&path, rendered)
+                    .map_err(|e| format!("{}: {e}", path.display()))?,
+                None => print!("{rendered}"),
+            }
+            Ok(())
+        }
+        Command::Backup { output } => {
+            let backup = backup::backup(&storage).await?;
+            std::fs::write(&output, backup.to_archive()?)
+                .map_err(|e| format!("{}: {e}", output.display()))?;
+            eprintln!(
+                "backed up {} schema(s), {} node(s), {} edge(s)",
+                backup.schemas.len(),
+                backup.nodes.len(),
+                backup.edges.len()
+            );
+            Ok(())
+        }
+        Command::Restore { archive, mode } => {
+            let bytes =
+                std::fs::read(&archive).map_err(|e| format!("{}: {e}", archive.display()))?;
+            let backup =
+                Backup::from_archive(&bytes).map_err(|e| format!("{}: {e}", archive.display()))?;
+            print(&backup::restore(&storage, &backup, mode).await?)
+        }
+        Command::User(UserCommand::Create { username, admin }) => {
+            let password = read_password()?;
+            let user = NewUser {
+                username,
+                password,
+                admin,
+            };
+            print(&users.create(user).await?)
+        }
+        Command::Token(TokenCommand::Issue { username, ttl }) => {
+            let user = users
+                .get(&username)
+                .await?
+                .ok_or_else(|| Error::NotFound(format!("user '{username}' does not exist")))?;
+            let token = auth::issue_token(&config.auth, &user, ttl.map(Duration::from_secs))?;
+            println!("{token}");
+            Ok(())
+        }
+    }
+}
+
+async fn migrate(pool: &PgPool, command: MigrateCommand) -> Result<()> {
+    match command {
+        MigrateCommand::Up => {
+            let pending = pending_migrations(pool).await?;
+            MIGRATOR.run(pool).await?;
+            eprintln!("applied {pending} migration(s)");
+        }
+        MigrateCommand::Down { steps } => {
+            let mut applied = applied_migrations(pool).await?;
+            applied.sort_unstable();
+            let kept = applied.len().saturating_sub(steps);
+            let target = if kept == 0 { 0 } else { applied[kept - 1] };
+            MIGRATOR.undo(pool, target).await?;
+            eprintln!("reverted {} migration(s)", applied.len() - kept);
+        }
+        MigrateCommand::Status => {
+            let applied: HashSet<i64> = applied_migrations(pool).await?.into_iter().collect();
+            for migration in MIGRATOR
+                .iter()
+                .filter(|migration| !migration.migration_type.is_down_migration())
+            {
+                let state = if applied.contains(&migration.version) {
+                    "applied"
+                } else {
+                    "pending"
+                };
+                println!(
+                    "{:04} {state:7} {}",
+                    migration.version, migration.description
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn applied_migrations(pool: &PgPool) -> Result<Vec<i64>> {
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+    let applied = connection.list_applied_migrations().await?;
+    Ok(applied
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect())
+}
+
+async fn pending_migrations(pool: &PgPool) -> Result<usize> {
+    let applied: HashSet<i64> = applied_migrations(pool).await?.into_iter().collect();
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| {
+            !migration.migration_type.is_down_migration() && !applied.contains(&migration.version)
+        })
+        .count())
+}
+
+fn read(path: &PathBuf) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()).into())
+}
+
+fn read_password() -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("password: ")?;
+        if rpassword::prompt_password("repeat password: ")? != password {
+            return Err("the passwords do not match".into());
+        }
+        Ok(password)
+    } else {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+fn print(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
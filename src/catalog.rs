@@ -0,0 +1,244 @@
+//! Every stored schema, for building validators that can refer to each other.
+//!
+//! A schema extends another one by naming it in `x-extends`:
+//!
+//! ```json
+//! { "title": "Dessert", "x-extends": "Food", "properties": { "sweetness": { "type": "integer" } } }
+//! ```
+//!
+//! Nodes of an extending schema must also be valid against all of its
+//! ancestors. Schemas can also `$ref` each other, either by their `$id` or as
+//! `lixiv:schemas/<title>`. Every stored schema is registered under both, and
+//! references are only ever resolved against that registry: nothing is fetched
+//! from the network or the file system, and a `$ref` to anything else is an
+//! error.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{Value, json};
+
+pub const EXTENDS_KEYWORD: &str = "x-extends";
+/// Prefix of the URI a schema can be referenced by through its title.
+pub const TITLE_URI_PREFIX: &str = "lixiv:schemas/";
+
+pub fn title_uri(title: &str) -> String {
+    format!("{TITLE_URI_PREFIX}{title}")
+}
+
+/// The title named by `x-extends`, if any.
+pub fn parent_title(schema_json: &Value) -> Option<&str> {
+    schema_json.get(EXTENDS_KEYWORD).and_then(Value::as_str)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SchemaCatalog {
+    schemas: HashMap<String, Value>,
+}
+
+impl SchemaCatalog {
+    pub async fn load(executor: impl sqlx::PgExecutor<'_>) -> Result<Self, sqlx::Error> {
+        let schemas = sqlx::query!(
+            r#"
+            SELECT title, schema_json as "schema_json: Value"
+            FROM schemas
+            "#
+        )
+        .fetch_all(executor)
+        .await?
+        .into_iter()
+        .map(|row| (row.title, row.schema_json))
+        .collect();
+        Ok(SchemaCatalog { schemas })
+    }
+
+    /// Add or replace a schema, e.g. one that is about to be stored.
+    pub fn insert(&mut self, title: &str, schema_json: Value) {
+        self.schemas.insert(title.to_string(), schema_json);
+    }
+
+    pub fn get(&self, title: &str) -> Option<&Value> {
+        self.schemas.get(title)
+    }
+
+    pub fn titles(&self) -> impl Iterator<Item = &str> {
+        self.schemas.keys().map(String::as_str)
+    }
+
+    /// `title` followed by the schemas it extends, nearest first.
+    pub fn lineage(&self, title: &str) -> Result<Vec<&str>, String> {
+        let mut lineage: Vec<&str> = Vec::new();
+        let mut current = Some(title);
+        while let Some(title) = current {
+            let (title, schema) = self
+                .schemas
+                .get_key_value(title)
+                .ok_or_else(|| format!("unknown schema '{title}'"))?;
+            if lineage.contains(&title.as_str()) {
+                return Err(format!("schema '{}' extends itself", lineage[0]));
+            }
+            lineage.push(title);
+            current = parent_title(schema);
+        }
+        Ok(lineage)
+    }
+
+    /// A validator for the schema stored as `title`, including its ancestors.
+    pub fn validator(&self, title: &str) -> Result<jsonschema::Validator, String> {
+        let schema = self
+            .schemas
+            .get(title)
+            .ok_or_else(|| format!("unknown schema '{title}'"))?;
+        if let Some(id) = schema_id(schema)
+            && let Some(other) = self
+                .schemas
+                .iter()
+                .find(|(other, json)| *other != title && schema_id(json) == Some(id))
+                .map(|(other, _)| other)
+        {
+            return Err(format!("$id '{id}' is already used by schema '{other}'"));
+        }
+        self.lineage(title)?;
+        self.validator_for(schema)
+    }
+
+    /// Titles of the schemas that extend or refer to `title`, directly or
+    /// through other schemas. References are looked up by title URI and `$id`
+    /// without compiling any schema.
+    pub fn dependents(&self, title: &str) -> Vec<String> {
+        let mut uris: HashMap<String, &str> = HashMap::with_capacity(self.schemas.len() * 2);
+        for (title, schema_json) in &self.schemas {
+            if let Some(id) = schema_id(schema_json) {
+                uris.insert(id.to_string(), title);
+            }
+            uris.insert(title_uri(title), title);
+        }
+
+        let mut referrers: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for (referrer, schema_json) in &self.schemas {
+            let mut references = Vec::new();
+            collect_references(schema_json, &mut references);
+            let referenced = references
+                .into_iter()
+                .filter_map(|reference| resolve_reference(schema_id(schema_json), reference))
+                .filter_map(|uri| uris.get(&uri).copied())
+                .chain(parent_title(schema_json));
+            for referenced in referenced {
+                referrers.entry(referenced).or_default().insert(referrer);
+            }
+        }
+
+        let mut dependents = HashSet::new();
+        let mut pending = vec![title];
+        while let Some(current) = pending.pop() {
+            for referrer in referrers.get(current).into_iter().flatten() {
+                if *referrer != title && dependents.insert(*referrer) {
+                    pending.push(referrer);
+                }
+            }
+        }
+        let mut dependents: Vec<String> = dependents.into_iter().map(str::to_string).collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// A validator for `schema_json`, resolving `x-extends` and references to
+    /// other schemas against the catalog.
+    pub fn validator_for(&self, schema_json: &Value) -> Result<jsonschema::Validator, String> {
+        jsonschema::options()
+            .with_retriever(CatalogRetriever {
+                resources: self.resources(),
+            })
+            .build(&effective_schema(schema_json))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Every schema under its title URI and its `$id`.
+    fn resources(&self) -> HashMap<String, Value> {
+        let mut resources = HashMap::with_capacity(self.schemas.len() * 2);
+        for (title, schema_json) in &self.schemas {
+            let effective = effective_schema(schema_json);
+            if let Some(id) = schema_id(schema_json) {
+                resources.insert(id.to_string(), effective.clone());
+            }
+            resources.insert(title_uri(title), effective);
+        }
+        resources
+    }
+}
+
+/// The `$id` a schema is registered under, without an empty fragment.
+fn schema_id(schema_json: &Value) -> Option<&str> {
+    schema_json
+        .get("$id")
+        .and_then(Value::as_str)
+        .map(|id| id.trim_end_matches('#'))
+}
+
+/// Every `$ref` in `schema_json`.
+fn collect_references<'a>(schema_json: &'a Value, references: &mut Vec<&'a str>) {
+    match schema_json {
+        Value::Object(object) => {
+            for (key, value) in object {
+                match (key.as_str(), value) {
+                    ("$ref", Value::String(reference)) => references.push(reference),
+                    _ => collect_references(value, references),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_references(value, references);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The URI of the schema `reference` points into, relative to the `$id` of
+/// the schema it is written in; `None` for a reference within that schema.
+fn resolve_reference(base: Option<&str>, reference: &str) -> Option<String> {
+    let uri = reference.split('#').next().unwrap_or_default();
+    if uri.is_empty() {
+        None
+    } else if uri.contains(':') {
+        Some(uri.to_string())
+    } else {
+        let base = base?;
+        let directory = base.rfind('/').map_or("", |end| &base[..=end]);
+        Some(format!("{directory}{uri}"))
+    }
+}
+
+/// `schema_json` with its parent added to `allOf`.
+fn effective_schema(schema_json: &Value) -> Value {
+    let Some(parent) = parent_title(schema_json) else {
+        return schema_json.clone();
+    };
+    let mut schema = schema_json.clone();
+    if let Some(object) = schema.as_object_mut() {
+        let reference = json!({ "$ref": title_uri(parent) });
+        match object.get_mut("allOf").and_then(Value::as_array_mut) {
+            Some(all_of) => all_of.push(reference),
+            None => {
+                object.insert("allOf".to_string(), json!([reference]));
+            }
+        }
+    }
+    schema
+}
+
+struct CatalogRetriever {
+    resources: HashMap<String, Value>,
+}
+
+impl jsonschema::Retrieve for CatalogRetriever {
+    fn retrieve(
+        &self,
+        uri: &jsonschema::Uri<String>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.resources
+            .get(uri.as_str())
+            .cloned()
+            .ok_or_else(|| format!("'{}' does not refer to a stored schema", uri.as_str()).into())
+    }
+}
@@ -0,0 +1,528 @@
+//! The settings of the server and the command line tools.
+//!
+//! Settings are layered: the defaults are overridden by a TOML file (given by
+//! `--config` or `LIXIV_CONFIG`, else `lixiv.toml` if it exists), which is
+//! overridden by environment variables, which are overridden by command line
+//! flags. Variables from a `.env` file count as environment variables.
+//! [`Config::load`] validates the result and reports every problem at once.
+
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::graphql::QueryLimits;
+
+/// The file read when no other is given, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "lixiv.toml";
+
+/// Secrets used to sign tokens must be at least this long.
+pub const MIN_SECRET_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The address the HTTP server listens on.
+    pub bind: SocketAddr,
+    /// Where the server keeps schemas, nodes and edges.
+    pub storage: StorageKind,
+    pub database: DatabaseConfig,
+    pub http: HttpConfig,
+    pub log: LogConfig,
+    pub auth: AuthConfig,
+    pub graphql: GraphqlConfig,
+    pub features: Features,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// The Postgres or SQLite database at `database.url`
+    #[default]
+    Database,
+    /// In memory, empty on every start; pattern queries are not available
+    Memory,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long to wait for a free connection before failing a request.
+    pub acquire_timeout_secs: u64,
+    /// Close connections idle for longer than this; never if unset.
+    pub idle_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Requests taking longer than this are answered with `408`.
+    pub request_timeout_secs: u64,
+    /// Origins allowed to call the API from a browser, or `*` for any.
+    pub cors_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// A `tracing` filter, overridden by `RUST_LOG`.
+    pub filter: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable, one event per line
+    Pretty,
+    /// Like pretty, without the span context
+    Compact,
+    /// One JSON object per event
+    Json,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// The secret signing access tokens.
+    pub token_secret: Option<String>,
+    /// How long issued tokens are valid.
+    pub token_ttl_secs: u64,
+}
+
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field(
+                "token_secret",
+                &self.token_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("token_ttl_secs", &self.token_ttl_secs)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphqlConfig {
+    /// Apollo persisted query manifest whose operations are registered at
+    /// startup.
+    pub operations: Option<PathBuf>,
+    /// Only execute persisted operations.
+    pub strict_operations: bool,
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    pub max_aliases: usize,
+}
+
+impl GraphqlConfig {
+    pub fn limits(&self) -> QueryLimits {
+        QueryLimits {
+            max_depth: self.max_depth,
+            max_complexity: self.max_complexity,
+            max_aliases: self.max_aliases,
+        }
+    }
+}
+
+/// Parts of the HTTP API that can be switched off.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// The GraphQL playground at `/debug/playground`, on by default in debug
+    /// builds only.
+    pub playground: bool,
+    /// `/graphql/typed`.
+    pub typed_graphql: bool,
+    /// The REST resources and `/openapi.json`.
+    pub rest: bool,
+    /// `/import` and `/import/csv`.
+    pub import: bool,
+    /// `/export` and `/export/rdf`.
+    pub export: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Feature {
+    Playground,
+    TypedGraphql,
+    Rest,
+    Import,
+    Export,
+}
+
+impl Features {
+    fn set(&mut self, feature: Feature, enabled: bool) {
+        let toggle = match feature {
+            Feature::Playground => &mut self.playground,
+            Feature::TypedGraphql => &mut self.typed_graphql,
+            Feature::Rest => &mut self.rest,
+            Feature::Import => &mut self.import,
+            Feature::Export => &mut self.export,
+        };
+        *toggle = enabled;
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            storage: StorageKind::default(),
+            database: DatabaseConfig::default(),
+            http: HttpConfig::default(),
+            log: LogConfig::default(),
+            auth: AuthConfig::default(),
+            graphql: GraphqlConfig::default(),
+            features: Features::default(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: None,
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 3,
+            idle_timeout_secs: Some(600),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            request_timeout_secs: 30,
+            cors_origins: vec!["*".to_string()],
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            format: LogFormat::Pretty,
+            filter: format!(
+                "{}=debug,tower_http=debug,axum::rejection=trace",
+                env!("CARGO_CRATE_NAME")
+            ),
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            token_secret: None,
+            token_ttl_secs: 60 * 60,
+        }
+    }
+}
+
+impl Default for GraphqlConfig {
+    fn default() -> Self {
+        let limits = QueryLimits::default();
+        GraphqlConfig {
+            operations: None,
+            strict_operations: false,
+            max_depth: limits.max_depth,
+            max_complexity: limits.max_complexity,
+            max_aliases: limits.max_aliases,
+        }
+    }
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            playground: cfg!(debug_assertions),
+            typed_graphql: true,
+            rest: true,
+            import: true,
+            export: true,
+        }
+    }
+}
+
+/// Command line flags overriding the configuration file, each of which may
+/// also be given as an environment variable.
+#[derive(Debug, Default, clap::Args)]
+#[command(next_help_heading = "Configuration")]
+pub struct ConfigArgs {
+    /// TOML configuration file
+    #[arg(long = "config", env = "LIXIV_CONFIG", global = true)]
+    pub config_file: Option<PathBuf>,
+    /// Address the HTTP server listens on
+    #[arg(long, env = "LIXIV_BIND", global = true)]
+    pub bind: Option<SocketAddr>,
+    /// Where the server keeps schemas, nodes and edges
+    #[arg(long, env = "LIXIV_STORAGE", global = true)]
+    pub storage: Option<StorageKind>,
+    #[arg(long, env = "DATABASE_URL", global = true, hide_env_values = true)]
+    pub database_url: Option<String>,
+    /// Largest number of pooled database connections
+    #[arg(long, env = "LIXIV_MAX_CONNECTIONS", global = true)]
+    pub max_connections: Option<u32>,
+    /// Seconds to wait for a free database connection
+    #[arg(long, env = "LIXIV_ACQUIRE_TIMEOUT", global = true)]
+    pub acquire_timeout: Option<u64>,
+    /// Seconds after which requests are answered with 408
+    #[arg(long, env = "LIXIV_REQUEST_TIMEOUT", global = true)]
+    pub request_timeout: Option<u64>,
+    /// Origin allowed by CORS, or `*` for any; may be repeated
+    #[arg(
+        long = "cors-origin",
+        env = "LIXIV_CORS_ORIGINS",
+        value_delimiter = ',',
+        global = true
+    )]
+    pub cors_origins: Vec<String>,
+    #[arg(long, env = "LIXIV_LOG_FORMAT", global = true)]
+    pub log_format: Option<LogFormat>,
+    /// Secret signing access tokens
+    #[arg(
+        long,
+        env = "LIXIV_TOKEN_SECRET",
+        global = true,
+        hide_env_values = true
+    )]
+    pub token_secret: Option<String>,
+    /// Apollo persisted query manifest whose operations are registered at startup
+    #[arg(long, env = "LIXIV_OPERATIONS", global = true)]
+    pub operations: Option<PathBuf>,
+    /// Only execute persisted operations, from the manifest or the
+    /// `persisted_operations` table
+    #[arg(
+        long,
+        env = "LIXIV_STRICT_OPERATIONS",
+        num_args = 0..=1,
+        default_missing_value = "true",
+        global = true
+    )]
+    pub strict_operations: Option<bool>,
+    /// Reject operations nested deeper than this
+    #[arg(long, env = "LIXIV_MAX_DEPTH", global = true)]
+    pub max_depth: Option<usize>,
+    /// Reject operations costing more than this, list fields count once per
+    /// requested item
+    #[arg(long, env = "LIXIV_MAX_COMPLEXITY", global = true)]
+    pub max_complexity: Option<usize>,
+    /// Reject operations with more aliased fields than this
+    #[arg(long, env = "LIXIV_MAX_ALIASES", global = true)]
+    pub max_aliases: Option<usize>,
+    /// Switch on parts of the HTTP API; may be repeated
+    #[arg(long, env = "LIXIV_ENABLE", value_delimiter = ',', global = true)]
+    pub enable: Vec<Feature>,
+    /// Switch off parts of the HTTP API; may be repeated
+    #[arg(long, env = "LIXIV_DISABLE", value_delimiter = ',', global = true)]
+    pub disable: Vec<Feature>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("cannot read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("invalid configuration:\n  {}", .0.join("\n  "))]
+    Invalid(Vec<String>),
+}
+
+impl Config {
+    /// The configuration file, overridden by `args`, validated.
+    pub fn load(args: &ConfigArgs) -> Result<Config, ConfigError> {
+        let mut config = match &args.config_file {
+            Some(path) => Config::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Config::default(),
+        };
+        config.apply(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn apply(&mut self, args: &ConfigArgs) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut self.bind, &args.bind);
+        set(&mut self.storage, &args.storage);
+        if args.database_url.is_some() {
+            self.database.url.clone_from(&args.database_url);
+        }
+        set(&mut self.database.max_connections, &args.max_connections);
+        set(
+            &mut self.database.acquire_timeout_secs,
+            &args.acquire_timeout,
+        );
+        set(&mut self.http.request_timeout_secs, &args.request_timeout);
+        if !args.cors_origins.is_empty() {
+            self.http.cors_origins.clone_from(&args.cors_origins);
+        }
+        set(&mut self.log.format, &args.log_format);
+        if args.token_secret.is_some() {
+            self.auth.token_secret.clone_from(&args.token_secret);
+        }
+        if args.operations.is_some() {
+            self.graphql.operations.clone_from(&args.operations);
+        }
+        set(&mut self.graphql.strict_operations, &args.strict_operations);
+        set(&mut self.graphql.max_depth, &args.max_depth);
+        set(&mut self.graphql.max_complexity, &args.max_complexity);
+        set(&mut self.graphql.max_aliases, &args.max_aliases);
+        for feature in &args.enable {
+            self.features.set(*feature, true);
+        }
+        for feature in &args.disable {
+            self.features.set(*feature, false);
+        }
+    }
+
+    /// Every problem of the configuration, not just the first.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        let database = &self.database;
+        match database.url.as_deref() {
+            _ if self.storage == StorageKind::Memory => {}
+            None | Some("") => problems.push(
+                "database.url is not set; set DATABASE_URL, pass --database-url or add it to the \
+                 configuration file"
+                    .to_string(),
+            ),
+            Some(url)
+                if !url.starts_with("postgres://")
+                    && !url.starts_with("postgresql://")
+                    && !database.is_sqlite() =>
+            {
+                problems.push(
+                    "database.url must start with postgres://, postgresql:// or sqlite:".into(),
+                )
+            }
+            Some(_) => {}
+        }
+        if database.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_string());
+        }
+        if database.min_connections > database.max_connections {
+            problems.push(format!(
+                "database.min_connections ({}) exceeds database.max_connections ({})",
+                database.min_connections, database.max_connections
+            ));
+        }
+        if database.acquire_timeout_secs == 0 {
+            problems.push("database.acquire_timeout_secs must be at least 1".to_string());
+        }
+
+        if self.http.request_timeout_secs == 0 {
+            problems.push("http.request_timeout_secs must be at least 1".to_string());
+        }
+        let origins = &self.http.cors_origins;
+        if origins.iter().any(|origin| origin == "*") {
+            if origins.len() > 1 {
+                problems.push("http.cors_origins cannot combine * with other origins".to_string());
+            }
+        } else {
+            for origin in origins {
+                let valid = (origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin.ends_with('/')
+                    && http::HeaderValue::from_str(origin).is_ok();
+                if !valid {
+                    problems.push(format!(
+                        "http.cors_origins: '{origin}' is not an origin such as https://example.com"
+                    ));
+                }
+            }
+        }
+
+        if let Some(secret) = &self.auth.token_secret
+            && secret.len() < MIN_SECRET_LENGTH
+        {
+            problems.push(format!(
+                "auth.token_secret must be at least {MIN_SECRET_LENGTH} bytes long"
+            ));
+        }
+        if self.auth.token_ttl_secs == 0 {
+            problems.push("auth.token_ttl_secs must be at least 1".to_string());
+        }
+
+        let graphql = &self.graphql;
+        if let Some(path) = &graphql.operations
+            && !path.is_file()
+        {
+            problems.push(format!(
+                "graphql.operations: {} does not exist",
+                path.display()
+            ));
+        }
+        for (key, value) in [
+            ("max_depth", graphql.max_depth),
+            ("max_complexity", graphql.max_complexity),
+            ("max_aliases", graphql.max_aliases),
+        ] {
+            if value == 0 {
+                problems.push(format!("graphql.{key} must be at least 1"));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// Whether `url` names a SQLite database, e.g. `sqlite://lixiv.db`.
+    pub fn is_sqlite(&self) -> bool {
+        self.url
+            .as_deref()
+            .is_some_and(|url| url.starts_with("sqlite:"))
+    }
+
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout_secs.map(Duration::from_secs)
+    }
+}
+
+impl HttpConfig {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+}
@@ -1,18 +1,70 @@
-use std::{env, time::Duration};
-
-pub async fn set_up_database() -> sqlx::PgPool {
-    let database_url =
-        env::var("DATABASE_URL").expect("there is no DATABASE_URL environment variable present");
-    let database_pool = sqlx::postgres::PgPoolOptions::new()
-        .acquire_timeout(Duration::from_secs(3))
-        .connect(&database_url)
+use std::str::FromStr;
+
+use sqlx::migrate::Migrator;
+
+use crate::config::DatabaseConfig;
+
+/// The migrations in `migrations/`, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// The migrations in `migrations/sqlite/`, for SQLite databases.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+#[derive(Debug, thiserror::Error)]
+pub enum DatabaseError {
+    #[error("database.url is not set")]
+    MissingUrl,
+    #[error("cannot connect to the database: {0}")]
+    Connect(#[source] sqlx::Error),
+    #[error("cannot run migrations: {0}")]
+    Migrate(#[source] sqlx::migrate::MigrateError),
+}
+
+/// A connection pool, after applying any pending migrations.
+pub async fn set_up_database(config: &DatabaseConfig) -> Result<sqlx::PgPool, DatabaseError> {
+    let database_pool = connect(config).await?;
+
+    MIGRATOR
+        .run(&database_pool)
+        .await
+        .map_err(DatabaseError::Migrate)?;
+
+    Ok(database_pool)
+}
+
+/// A connection pool, leaving the schema of the database as it is.
+pub async fn connect(config: &DatabaseConfig) -> Result<sqlx::PgPool, DatabaseError> {
+    let database_url = config.url.as_deref().ok_or(DatabaseError::MissingUrl)?;
+    sqlx::postgres::PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout())
+        .idle_timeout(config.idle_timeout())
+        .connect(database_url)
+        .await
+        .map_err(DatabaseError::Connect)
+}
+
+/// A connection pool to the SQLite database at `database.url`, created if it
+/// does not exist, after applying any pending migrations.
+pub async fn set_up_sqlite(config: &DatabaseConfig) -> Result<sqlx::SqlitePool, DatabaseError> {
+    let database_url = config.url.as_deref().ok_or(DatabaseError::MissingUrl)?;
+    let options = sqlx::sqlite::SqliteConnectOptions::from_str(database_url)
+        .map_err(DatabaseError::Connect)?
+        .create_if_missing(true);
+    let database_pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout())
+        .idle_timeout(config.idle_timeout())
+        .connect_with(options)
         .await
-        .expect("can't connect to database");
+        .map_err(DatabaseError::Connect)?;
 
-    sqlx::migrate!("./migrations")
+    SQLITE_MIGRATOR
         .run(&database_pool)
         .await
-        .expect("cannot run migrations");
+        .map_err(DatabaseError::Migrate)?;
 
-    database_pool
+    Ok(database_pool)
 }
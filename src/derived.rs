@@ -0,0 +1,181 @@
+//! Node properties computed from the graph instead of stored in `data`.
+//!
+//! A schema declares them under `x-derived` on its root:
+//!
+//! ```json
+//! "x-derived": {
+//!   "totalKcal": {
+//!     "path": ["has-ingredient", "has-nutrition"],
+//!     "property": "kcal",
+//!     "aggregate": "sum"
+//!   }
+//! }
+//! ```
+//!
+//! `path` lists the edge labels followed outgoing from the node, `property` is
+//! read from the `data` of every distinct node at the end of the path and
+//! `aggregate` is one of `sum`, `min`, `max` or `count`. `count` counts the
+//! nodes reached, or those that have `property` when it is given.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use serde_json::{Map, Value};
+
+use crate::{error::Error, model::DbNode, storage::Storage};
+
+pub const DERIVED_KEYWORD: &str = "x-derived";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+    Count,
+}
+
+#[derive(Debug, Clone)]
+pub struct DerivedField {
+    pub name: String,
+    pub path: Vec<String>,
+    pub property: Option<String>,
+    pub aggregate: Aggregate,
+}
+
+/// The derived fields declared by `schema_json`, or a message describing the
+/// first malformed declaration.
+pub fn definitions(schema_json: &Value) -> Result<Vec<DerivedField>, String> {
+    let Some(declared) = schema_json.get(DERIVED_KEYWORD) else {
+        return Ok(Vec::new());
+    };
+    let declared = declared
+        .as_object()
+        .ok_or_else(|| format!("{DERIVED_KEYWORD} must be an object"))?;
+
+    let mut fields = Vec::with_capacity(declared.len());
+    for (name, definition) in declared {
+        let invalid = |message: &str| format!("{DERIVED_KEYWORD}.{name}: {message}");
+        let path = definition
+            .get("path")
+            .and_then(Value::as_array)
+            .filter(|path| !path.is_empty())
+            .ok_or_else(|| invalid("path must be a non-empty array of edge labels"))?
+            .iter()
+            .map(|label| label.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("path must only contain strings"))?;
+        let property = match definition.get("property") {
+            None => None,
+            Some(Value::String(property)) => Some(property.clone()),
+            Some(_) => return Err(invalid("property must be a string")),
+        };
+        let aggregate = match definition.get("aggregate").and_then(Value::as_str) {
+            Some("sum") => Aggregate::Sum,
+            Some("min") => Aggregate::Min,
+            Some("max") => Aggregate::Max,
+            Some("count") => Aggregate::Count,
+            _ => return Err(invalid("aggregate must be one of sum, min, max, count")),
+        };
+        if property.is_none() && aggregate != Aggregate::Count {
+            return Err(invalid("property is required unless aggregate is count"));
+        }
+        fields.push(DerivedField {
+            name: name.clone(),
+            path,
+            property,
+            aggregate,
+        });
+    }
+    Ok(fields)
+}
+
+/// Evaluated fields per node, valid for one `graph_version`.
+#[derive(Debug, Default)]
+pub struct DerivedCache {
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    version: i64,
+    values: HashMap<i32, Map<String, Value>>,
+}
+
+impl DerivedCache {
+    fn get(&self, version: i64, node_id: i32) -> Option<Map<String, Value>> {
+        let mut entries = self.entries.lock().expect("derived cache poisoned");
+        if entries.version != version {
+            entries.version = version;
+            entries.values.clear();
+        }
+        entries.values.get(&node_id).cloned()
+    }
+
+    fn insert(&self, version: i64, node_id: i32, values: Map<String, Value>) {
+        let mut entries = self.entries.lock().expect("derived cache poisoned");
+        if entries.version == version {
+            entries.values.insert(node_id, values);
+        }
+    }
+}
+
+/// The derived fields of `node`, served from `cache` while the graph is
+/// unchanged.
+pub async fn evaluate(
+    storage: &dyn Storage,
+    cache: &DerivedCache,
+    node: &DbNode,
+) -> Result<Map<String, Value>, Error> {
+    let version = storage.version().await?.graph;
+    if let Some(values) = cache.get(version, node.id) {
+        return Ok(values);
+    }
+
+    let schemas = storage
+        .get_schemas(std::slice::from_ref(&node.schema_title))
+        .await?;
+    // declarations are checked when the schema is created, but a restored or
+    // migrated schema may still hold a malformed one
+    let fields = match schemas.first() {
+        Some(schema) => definitions(&schema.schema_json).map_err(|message| {
+            Error::ValidationFailed(format!("schema '{}': {message}", schema.title))
+        })?,
+        None => Vec::new(),
+    };
+
+    let mut values = Map::new();
+    for field in fields {
+        let reached = storage.walk(node.id, &field.path).await?;
+        let value = aggregate(&field, &reached);
+        values.insert(field.name, value);
+    }
+
+    cache.insert(version, node.id, values.clone());
+    Ok(values)
+}
+
+/// `field` over the `data` of the nodes at the end of its path.
+fn aggregate(field: &DerivedField, reached: &[DbNode]) -> Value {
+    let property = field.property.as_deref();
+    if field.aggregate == Aggregate::Count {
+        let count = reached
+            .iter()
+            .filter(|node| property.is_none_or(|property| node.data.get(property).is_some()))
+            .count();
+        return Value::from(count as i64);
+    }
+
+    let amounts = reached.iter().filter_map(|node| {
+        property
+            .and_then(|property| node.data.get(property))
+            .and_then(Value::as_f64)
+    });
+    let amount = match field.aggregate {
+        Aggregate::Sum => amounts.reduce(|sum, amount| sum + amount),
+        Aggregate::Min => amounts.reduce(f64::min),
+        Aggregate::Max => amounts.reduce(f64::max),
+        Aggregate::Count => unreachable!("counted above"),
+    };
+    amount
+        .and_then(serde_json::Number::from_f64)
+        .map_or(Value::Null, Value::Number)
+}
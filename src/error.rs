@@ -0,0 +1,155 @@
+//! The errors reported to API clients.
+//!
+//! Every variant has a stable [`code`](Error::code), returned as
+//! `extensions.code` by the GraphQL endpoints and as `code` by the REST
+//! resources. Postgres errors are translated by their SQLSTATE, SQLite errors
+//! by their kind; anything unexpected becomes [`Error::Internal`], whose details are logged but never
+//! sent to the client.
+
+use std::sync::Arc;
+
+use sqlx::{error::ErrorKind, postgres::PgDatabaseError};
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    NotFound(String),
+    /// A row with the same `key`, e.g. `(schema_title, name)=(Food, Apple)`,
+    /// already exists.
+    #[error("{key} already exists")]
+    UniqueViolation { key: String },
+    /// A reference to a schema or node that does not exist, or a row that
+    /// cannot be deleted because others still refer to it.
+    #[error("{0}")]
+    ForeignKeyViolation(String),
+    /// The input is malformed or does not satisfy its schema.
+    #[error("{0}")]
+    ValidationFailed(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    /// `feature` is missing from the configured storage and from the other
+    /// `storages` named.
+    #[error("{feature} are not supported by the {} storages", .storages.join(" and "))]
+    Unsupported {
+        feature: &'static str,
+        storages: &'static [&'static str],
+    },
+    #[error("internal error")]
+    Internal(#[source] Arc<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "NOT_FOUND",
+            Error::UniqueViolation { .. } => "UNIQUE_VIOLATION",
+            Error::ForeignKeyViolation(_) => "FOREIGN_KEY_VIOLATION",
+            Error::ValidationFailed(_) => "VALIDATION_FAILED",
+            Error::Unauthorized(_) => "UNAUTHORIZED",
+            Error::Unsupported { .. } => "UNSUPPORTED",
+            Error::Internal(_) => "INTERNAL",
+        }
+    }
+
+    pub fn internal(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::Internal(Arc::from(error.into()))
+    }
+
+    /// Log the details of an internal error before they are dropped from
+    /// the response.
+    fn log(&self) {
+        if let Error::Internal(source) = self {
+            tracing::error!(error = %source, "internal error");
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        let database = match &error {
+            sqlx::Error::RowNotFound => return Error::NotFound("not found".to_string()),
+            sqlx::Error::Database(database) => database,
+            _ => return Error::internal(error),
+        };
+        let Some(postgres) = database.try_downcast_ref::<PgDatabaseError>() else {
+            // SQLite names the constraint but not the offending values
+            return match database.kind() {
+                ErrorKind::UniqueViolation => Error::UniqueViolation {
+                    key: "the key".to_string(),
+                },
+                ErrorKind::ForeignKeyViolation => {
+                    Error::ForeignKeyViolation("a referenced row does not exist".to_string())
+                }
+                ErrorKind::NotNullViolation | ErrorKind::CheckViolation => {
+                    Error::ValidationFailed(database.message().to_string())
+                }
+                _ => Error::internal(error),
+            };
+        };
+        match postgres.code() {
+            "23505" => Error::UniqueViolation {
+                key: key(postgres).unwrap_or("the key").to_string(),
+            },
+            "23503" => Error::ForeignKeyViolation(foreign_key_message(postgres)),
+            // check_violation, not_null_violation, invalid_text_representation,
+            // invalid_row_count_in_limit_clause
+            "23514" | "23502" | "22P02" | "2201W" => {
+                Error::ValidationFailed(postgres.message().to_string())
+            }
+            _ => Error::internal(error),
+        }
+    }
+}
+
+/// `(schema_title, name)=(Food, Apple)` from a detail such as
+/// `Key (schema_title, name)=(Food, Apple) already exists.`
+fn key(error: &PgDatabaseError) -> Option<&str> {
+    let detail = error.detail()?.strip_prefix("Key ")?;
+    let end = detail.rfind(") ")?;
+    Some(&detail[..=end])
+}
+
+fn foreign_key_message(error: &PgDatabaseError) -> String {
+    let detail = error.detail().unwrap_or_default();
+    match (key(error), detail.contains("is still referenced")) {
+        (Some(key), true) => format!("{key} is still referenced"),
+        (Some(key), false) => format!("{key} does not exist"),
+        (None, _) => "a referenced row does not exist".to_string(),
+    }
+}
+
+impl From<Error> for async_graphql::Error {
+    fn from(error: Error) -> Self {
+        error.log();
+        let code = error.code();
+        let mut graphql = async_graphql::Error::new(error.to_string());
+        let extensions = graphql.extensions.get_or_insert_with(Default::default);
+        extensions.set("code", code);
+        match &error {
+            Error::UniqueViolation { key } => extensions.set("key", key.as_str()),
+            Error::Unsupported { storages, .. } => extensions.set("storages", storages.to_vec()),
+            _ => {}
+        }
+        graphql
+    }
+}
+
+impl axum::response::IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        use axum::http::StatusCode;
+
+        self.log();
+        let status = match &self {
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::UniqueViolation { .. } | Error::ForeignKeyViolation(_) => StatusCode::CONFLICT,
+            Error::ValidationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Unsupported { .. } => StatusCode::NOT_IMPLEMENTED,
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = serde_json::json!({
+            "errors": [{ "message": self.to_string(), "code": self.code() }]
+        });
+        (status, axum::Json(body)).into_response()
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    str::FromStr,
+};
+
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use petgraph::{dot::Dot, prelude::StableDiGraph};
+use serde::Deserialize;
+
+pub(crate) mod rdf;
+#[cfg(test)]
+mod tests;
+
+pub use rdf::{RdfFormat, Term, Triple, rdf_handler};
+
+use crate::{
+    error::Error,
+    import::{EdgeEntry, GraphDocument, NodeEntry},
+    model::{DbEdge, DbNode, DbSchema},
+    storage::{SharedStorage, Snapshot, Storage},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// The native format read by `import`.
+    #[default]
+    Json,
+    /// GraphML for yEd and Gephi.
+    Graphml,
+    /// Graphviz DOT.
+    Dot,
+    /// JSON-LD with schemas as classes.
+    Jsonld,
+    /// N-Triples with schemas as classes.
+    Ntriples,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Graphml => "application/graphml+xml",
+            ExportFormat::Dot => "text/vnd.graphviz",
+            ExportFormat::Jsonld => RdfFormat::JsonLd.content_type(),
+            ExportFormat::Ntriples => RdfFormat::NTriples.content_type(),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "graphml" => Ok(ExportFormat::Graphml),
+            "dot" => Ok(ExportFormat::Dot),
+            "jsonld" => Ok(ExportFormat::Jsonld),
+            "ntriples" => Ok(ExportFormat::Ntriples),
+            _ => Err(format!(
+                "unknown export format '{s}', expected json, graphml, dot, jsonld or ntriples"
+            )),
+        }
+    }
+}
+
+/// Which part of the graph to export. The default selects everything.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// Only nodes of these schemas, all schemas when empty.
+    pub schemas: Vec<String>,
+    /// Only nodes reachable from this node id, following edge direction.
+    pub root: Option<i32>,
+}
+
+/// The selected part of a consistent snapshot of the graph.
+#[derive(Debug, Default)]
+pub struct ExportGraph {
+    pub schemas: Vec<DbSchema>,
+    pub nodes: Vec<DbNode>,
+    pub edges: Vec<DbEdge>,
+}
+
+/// Load the selected nodes, the edges between them and the schemas they use.
+pub async fn export_graph(
+    storage: &dyn Storage,
+    selection: &Selection,
+) -> Result<ExportGraph, Error> {
+    Ok(ExportGraph::select(storage.snapshot().await?, selection))
+}
+
+impl ExportGraph {
+    /// The part of `snapshot` that `selection` selects.
+    fn select(snapshot: Snapshot, selection: &Selection) -> Self {
+        let reachable: Option<HashSet<i32>> = selection.root.map(|root| {
+            let mut targets: HashMap<i32, Vec<i32>> = HashMap::new();
+            for edge in &snapshot.edges {
+                targets
+                    .entry(edge.source_node_id)
+                    .or_default()
+                    .push(edge.target_node_id);
+            }
+            let mut reached = HashSet::from([root]);
+            let mut pending = vec![root];
+            while let Some(id) = pending.pop() {
+                for target in targets.get(&id).into_iter().flatten() {
+                    if reached.insert(*target) {
+                        pending.push(*target);
+                    }
+                }
+            }
+            reached
+        });
+
+        let nodes: Vec<DbNode> = snapshot
+            .nodes
+            .into_iter()
+            .filter(|node| {
+                (selection.schemas.is_empty() || selection.schemas.contains(&node.schema_title))
+                    && reachable
+                        .as_ref()
+                        .is_none_or(|reachable| reachable.contains(&node.id))
+            })
+            .collect();
+        let ids: HashSet<i32> = nodes.iter().map(|node| node.id).collect();
+        let edges = snapshot
+            .edges
+            .into_iter()
+            .filter(|edge| ids.contains(&edge.source_node_id) && ids.contains(&edge.target_node_id))
+            .collect();
+
+        let everything = selection.schemas.is_empty() && selection.root.is_none();
+        let titles: HashSet<&str> = nodes.iter().map(|node| node.schema_title.as_str()).collect();
+        let schemas = snapshot
+            .schemas
+            .into_iter()
+            .filter(|schema| {
+                everything
+                    || titles.contains(schema.title.as_str())
+                    || selection.schemas.contains(&schema.title)
+            })
+            .collect();
+        ExportGraph {
+            schemas,
+            nodes,
+            edges,
+        }
+    }
+
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Json => serde_json::to_string_pretty(&self.to_document())
+                .expect("graph documents always serialize"),
+            ExportFormat::Graphml => self.to_graphml(),
+            ExportFormat::Dot => self.to_dot(),
+            ExportFormat::Jsonld => serde_json::to_string_pretty(&self.to_json_ld())
+                .expect("JSON-LD documents always serialize"),
+            ExportFormat::Ntriples => self.to_ntriples(),
+        }
+    }
+
+    /// The native format, with nodes referenced as `schema/name`.
+    pub fn to_document(&self) -> GraphDocument {
+        let references: HashMap<i32, String> = self
+            .nodes
+            .iter()
+            .map(|node| (node.id, format!("{}/{}", node.schema_title, node.name)))
+            .collect();
+
+        GraphDocument {
+            schemas: self
+                .schemas
+                .iter()
+                .map(|schema| schema.schema_json.clone())
+                .collect(),
+            nodes: self
+                .nodes
+                .iter()
+                .map(|node| NodeEntry {
+                    schema: node.schema_title.clone(),
+                    name: node.name.clone(),
+                    data: node.data.clone(),
+                })
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .map(|edge| EdgeEntry {
+                    source: references[&edge.source_node_id].clone(),
+                    target: references[&edge.target_node_id].clone(),
+                    weight: edge.weight.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Node labels are `name (schema_title)`, edge labels their weight.
+    pub fn to_petgraph(&self) -> StableDiGraph<String, String> {
+        let mut graph = StableDiGraph::new();
+        let mut indices = HashMap::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let index = graph.add_node(label(node));
+            indices.insert(node.id, index);
+        }
+        for edge in &self.edges {
+            graph.add_edge(
+                indices[&edge.source_node_id],
+                indices[&edge.target_node_id],
+                edge.weight.clone(),
+            );
+        }
+        graph
+    }
+
+    pub fn to_dot(&self) -> String {
+        format!("{}", Dot::new(&self.to_petgraph()))
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        out.push('\n');
+        out.push_str(r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#);
+        out.push('\n');
+        for (id, domain, name) in [
+            ("label", "node", "label"),
+            ("schema", "node", "schema"),
+            ("name", "node", "name"),
+            ("data", "node", "data"),
+            ("weight", "edge", "weight"),
+        ] {
+            let _ = writeln!(
+                out,
+                r#"  <key id="{id}" for="{domain}" attr.name="{name}" attr.type="string"/>"#
+            );
+        }
+        out.push_str(r#"  <graph id="lixiv" edgedefault="directed">"#);
+        out.push('\n');
+        for node in &self.nodes {
+            let _ = writeln!(out, r#"    <node id="n{}">"#, node.id);
+            for (key, value) in [
+                ("label", label(node)),
+                ("schema", node.schema_title.clone()),
+                ("name", node.name.clone()),
+                ("data", node.data.to_string()),
+            ] {
+                let _ = writeln!(
+                    out,
+                    r#"      <data key="{key}">{}</data>"#,
+                    escape_xml(&value)
+                );
+            }
+            out.push_str("    </node>\n");
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                r#"    <edge id="e{}" source="n{}" target="n{}">"#,
+                edge.id, edge.source_node_id, edge.target_node_id
+            );
+            let _ = writeln!(
+                out,
+                r#"      <data key="weight">{}</data>"#,
+                escape_xml(&edge.weight)
+            );
+            out.push_str("    </edge>\n");
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+fn label(node: &DbNode) -> String {
+    format!("{} ({})", node.name, node.schema_title)
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    /// Ignored by `/export/rdf`, which negotiates through `Accept`.
+    #[serde(default)]
+    format: ExportFormat,
+    /// Comma separated schema titles.
+    schemas: Option<String>,
+    /// Only nodes reachable from this node id.
+    root: Option<i32>,
+}
+
+impl ExportParams {
+    fn selection(&self) -> Selection {
+        Selection {
+            schemas: self
+                .schemas
+                .iter()
+                .flat_map(|schemas| schemas.split(','))
+                .map(str::trim)
+                .filter(|title| !title.is_empty())
+                .map(str::to_string)
+                .collect(),
+            root: self.root,
+        }
+    }
+}
+
+/// `GET /export?format=dot&schemas=Food,Ingredient&root=1`
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "export",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "The selected graph in the requested format", content(
+            (String = "application/json"),
+            (String = "application/graphml+xml"),
+            (String = "text/vnd.graphviz"),
+            (String = "application/ld+json"),
+            (String = "application/n-triples"),
+        )),
+    )
+)]
+pub async fn export_handler(
+    State(storage): State<SharedStorage>,
+    Query(params): Query<ExportParams>,
+) -> Response {
+    match export_graph(storage.as_ref(), &params.selection()).await {
+        Ok(graph) => (
+            [(header::CONTENT_TYPE, params.format.content_type())],
+            graph.render(params.format),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
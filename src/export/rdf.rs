@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use super::{ExportGraph, ExportParams, export_graph};
+use crate::{
+    model::{DbSchema, declared_type, property_schema},
+    storage::SharedStorage,
+};
+
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+const RDF_JSON: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON";
+const RDFS_CLASS: &str = "http://www.w3.org/2000/01/rdf-schema#Class";
+const RDFS_LABEL: &str = "http://www.w3.org/2000/01/rdf-schema#label";
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Iri(String),
+    Literal { value: String, datatype: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Triple {
+    pub subject: String,
+    pub predicate: String,
+    pub object: Term,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RdfFormat {
+    JsonLd,
+    NTriples,
+}
+
+impl RdfFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            RdfFormat::JsonLd => "application/ld+json",
+            RdfFormat::NTriples => "application/n-triples",
+        }
+    }
+
+    /// Pick a format from an `Accept` header, JSON-LD unless N-Triples
+    /// is preferred. `None` when neither is acceptable.
+    pub fn negotiate(accept: Option<&str>) -> Option<Self> {
+        let Some(accept) = accept else {
+            return Some(RdfFormat::JsonLd);
+        };
+        let mut best: Option<(f32, RdfFormat)> = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';').map(str::trim);
+            let media = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let quality = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let format = match media.as_str() {
+                "application/n-triples" | "text/plain" => RdfFormat::NTriples,
+                "application/ld+json" | "application/json" | "application/*" | "*/*" => {
+                    RdfFormat::JsonLd
+                }
+                _ => continue,
+            };
+            if quality > 0.0 && best.is_none_or(|(q, _)| quality > q) {
+                best = Some((quality, format));
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+}
+
+/// IRIs of one schema, derived from its `$id`.
+struct Vocabulary {
+    class: String,
+}
+
+impl Vocabulary {
+    fn new(schema: &DbSchema) -> Self {
+        let class = schema
+            .schema_json
+            .get("$id")
+            .and_then(Value::as_str)
+            .map(|id| id.split('#').next().unwrap_or(id).to_string())
+            .unwrap_or_else(|| format!("urn:lixiv:schema:{}", encode(&schema.title)));
+        Vocabulary { class }
+    }
+
+    fn property(&self, name: &str) -> String {
+        format!("{}#{}", self.class, encode(name))
+    }
+
+    fn node(&self, name: &str) -> String {
+        format!("{}/{}", self.class, encode(name))
+    }
+}
+
+impl ExportGraph {
+    /// Schemas become classes, node data literals typed after the schema and
+    /// edges object properties named after their weight.
+    pub fn to_triples(&self) -> Vec<Triple> {
+        let schemas: HashMap<&str, (&DbSchema, Vocabulary)> = self
+            .schemas
+            .iter()
+            .map(|schema| (schema.title.as_str(), (schema, Vocabulary::new(schema))))
+            .collect();
+        let mut triples = Vec::new();
+
+        for schema in &self.schemas {
+            let vocabulary = &schemas[schema.title.as_str()].1;
+            triples.push(Triple {
+                subject: vocabulary.class.clone(),
+                predicate: RDF_TYPE.to_string(),
+                object: Term::Iri(RDFS_CLASS.to_string()),
+            });
+            triples.push(Triple {
+                subject: vocabulary.class.clone(),
+                predicate: RDFS_LABEL.to_string(),
+                object: literal(&schema.title, "string"),
+            });
+        }
+
+        let mut subjects = HashMap::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let Some((schema, vocabulary)) = schemas.get(node.schema_title.as_str()) else {
+                continue;
+            };
+            let subject = vocabulary.node(&node.name);
+            triples.push(Triple {
+                subject: subject.clone(),
+                predicate: RDF_TYPE.to_string(),
+                object: Term::Iri(vocabulary.class.clone()),
+            });
+            triples.push(Triple {
+                subject: subject.clone(),
+                predicate: RDFS_LABEL.to_string(),
+                object: literal(&node.name, "string"),
+            });
+            for (key, value) in node.data.as_object().into_iter().flatten() {
+                let property = property_schema(
+                    &schema.schema_json,
+                    &format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+                );
+                let values = match value {
+                    Value::Array(items) => items.iter().collect(),
+                    value => vec![value],
+                };
+                let item_schema = match value {
+                    Value::Array(_) => property.and_then(|p| p.get("items")),
+                    _ => property,
+                };
+                for value in values {
+                    if let Some(object) = typed_literal(value, item_schema) {
+                        triples.push(Triple {
+                            subject: subject.clone(),
+                            predicate: vocabulary.property(key),
+                            object,
+                        });
+                    }
+                }
+            }
+            subjects.insert(node.id, (subject, vocabulary));
+        }
+
+        for edge in &self.edges {
+            let (Some((source, vocabulary)), Some((target, _))) = (
+                subjects.get(&edge.source_node_id),
+                subjects.get(&edge.target_node_id),
+            ) else {
+                continue;
+            };
+            triples.push(Triple {
+                subject: source.clone(),
+                predicate: vocabulary.property(&edge.weight),
+                object: Term::Iri(target.clone()),
+            });
+        }
+
+        triples
+    }
+
+    pub fn to_ntriples(&self) -> String {
+        let mut out = String::new();
+        for triple in self.to_triples() {
+            out.push_str(&format!("<{}> <{}> ", triple.subject, triple.predicate));
+            match &triple.object {
+                Term::Iri(iri) => out.push_str(&format!("<{iri}>")),
+                Term::Literal { value, datatype } => {
+                    out.push_str(&format!("\"{}\"^^<{datatype}>", escape_ntriples(value)))
+                }
+            }
+            out.push_str(" .\n");
+        }
+        out
+    }
+
+    /// Expanded JSON-LD, one object per subject.
+    pub fn to_json_ld(&self) -> Value {
+        let mut subjects: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+        for triple in self.to_triples() {
+            let entry = subjects.entry(triple.subject.clone()).or_insert_with(|| {
+                let mut object = Map::new();
+                object.insert("@id".to_string(), Value::String(triple.subject.clone()));
+                object
+            });
+            let (key, value) = match (triple.predicate.as_str(), triple.object) {
+                (RDF_TYPE, Term::Iri(iri)) => ("@type".to_string(), Value::String(iri)),
+                (_, Term::Iri(iri)) => (triple.predicate, json!({ "@id": iri })),
+                (_, Term::Literal { value, datatype }) if datatype == RDF_JSON => (
+                    triple.predicate,
+                    json!({
+                        "@value": serde_json::from_str::<Value>(&value).unwrap_or(Value::Null),
+                        "@type": "@json",
+                    }),
+                ),
+                (_, Term::Literal { value, datatype }) => (
+                    triple.predicate,
+                    json!({ "@value": value, "@type": datatype }),
+                ),
+            };
+            match entry.entry(key).or_insert_with(|| Value::Array(Vec::new())) {
+                Value::Array(values) => values.push(value),
+                _ => unreachable!("JSON-LD properties are always arrays"),
+            }
+        }
+
+        json!({
+            "@graph": subjects.into_values().map(Value::Object).collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn literal(value: &str, xsd_type: &str) -> Term {
+    Term::Literal {
+        value: value.to_string(),
+        datatype: format!("{XSD}{xsd_type}"),
+    }
+}
+
+/// Type a JSON value after the JSON Schema type of its property, falling
+/// back to the JSON value itself when the schema does not say.
+fn typed_literal(value: &Value, property: Option<&Value>) -> Option<Term> {
+    let format = property
+        .and_then(|p| p.get("format"))
+        .and_then(Value::as_str);
+    let declared = property.map(|p| declared_type(Some(p)));
+    Some(match (value, declared, format) {
+        (Value::Null, _, _) => return None,
+        (Value::Bool(b), _, _) => literal(&b.to_string(), "boolean"),
+        (Value::Number(n), Some("integer"), _) => literal(&n.to_string(), "integer"),
+        (Value::Number(n), _, _) if n.is_i64() || n.is_u64() => match declared {
+            Some("number") => literal(&n.to_string(), "double"),
+            _ => literal(&n.to_string(), "integer"),
+        },
+        (Value::Number(n), _, _) => literal(&n.to_string(), "double"),
+        (Value::String(s), _, Some("date-time")) => literal(s, "dateTime"),
+        (Value::String(s), _, Some("date")) => literal(s, "date"),
+        (Value::String(s), _, Some("uri" | "iri")) => literal(s, "anyURI"),
+        (Value::String(s), _, _) => literal(s, "string"),
+        (value, _, _) => Term::Literal {
+            value: value.to_string(),
+            datatype: RDF_JSON.to_string(),
+        },
+    })
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters.
+fn encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn escape_ntriples(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `GET /export/rdf`, JSON-LD or N-Triples depending on `Accept`.
+#[utoipa::path(
+    get,
+    path = "/export/rdf",
+    tag = "export",
+    params(super::ExportParams),
+    responses(
+        (status = 200, description = "The selected graph as RDF", content(
+            (String = "application/ld+json"),
+            (String = "application/n-triples"),
+        )),
+    )
+)]
+pub async fn rdf_handler(
+    State(storage): State<SharedStorage>,
+    Query(params): Query<ExportParams>,
+    headers: HeaderMap,
+) -> Response {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    let Some(format) = RdfFormat::negotiate(accept) else {
+        return (
+            StatusCode::NOT_ACCEPTABLE,
+            Json(json!({
+                "errors": [{ "message": "supported types are application/ld+json and application/n-triples" }]
+            })),
+        )
+            .into_response();
+    };
+
+    match export_graph(storage.as_ref(), &params.selection()).await {
+        Ok(graph) => {
+            let body = match format {
+                RdfFormat::JsonLd => graph.to_json_ld().to_string(),
+                RdfFormat::NTriples => graph.to_ntriples(),
+            };
+            (
+                [
+                    (header::CONTENT_TYPE, format.content_type()),
+                    (header::VARY, "Accept"),
+                ],
+                body,
+            )
+                .into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
@@ -0,0 +1,86 @@
+use serde_json::json;
+
+use super::{ExportGraph, Selection, export_graph};
+use crate::{import::import_graph, storage::MemoryStorage};
+
+async fn storage() -> MemoryStorage {
+    let storage = MemoryStorage::default();
+    let document = json!({
+        "schemas": [
+            { "title": "Food", "type": "object" },
+            { "title": "Ingredient", "type": "object" },
+            { "title": "Shop", "type": "object" }
+        ],
+        "nodes": [
+            { "schema": "Food", "name": "Lasagne" },
+            { "schema": "Food", "name": "Pizza" },
+            { "schema": "Ingredient", "name": "Tomatoes" },
+            { "schema": "Ingredient", "name": "Basil" }
+        ],
+        "edges": [
+            { "source": "Food/Lasagne", "target": "Ingredient/Tomatoes", "weight": "has-ingredient" },
+            { "source": "Food/Pizza", "target": "Ingredient/Tomatoes", "weight": "has-ingredient" },
+            { "source": "Food/Pizza", "target": "Ingredient/Basil", "weight": "has-ingredient" }
+        ]
+    });
+    import_graph(&storage, &document.to_string())
+        .await
+        .expect("the document is imported");
+    storage
+}
+
+fn names(graph: &ExportGraph) -> (Vec<&str>, Vec<&str>, usize) {
+    (
+        graph.schemas.iter().map(|s| s.title.as_str()).collect(),
+        graph.nodes.iter().map(|n| n.name.as_str()).collect(),
+        graph.edges.len(),
+    )
+}
+
+#[tokio::test]
+async fn the_default_selects_everything() {
+    let graph = export_graph(&storage().await, &Selection::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        names(&graph),
+        (
+            vec!["Food", "Ingredient", "Shop"],
+            vec!["Lasagne", "Pizza", "Tomatoes", "Basil"],
+            3
+        )
+    );
+}
+
+#[tokio::test]
+async fn schemas_select_their_nodes() {
+    let selection = Selection {
+        schemas: vec!["Ingredient".to_string(), "Shop".to_string()],
+        root: None,
+    };
+    let graph = export_graph(&storage().await, &selection).await.unwrap();
+    assert_eq!(
+        names(&graph),
+        (vec!["Ingredient", "Shop"], vec!["Tomatoes", "Basil"], 0)
+    );
+}
+
+#[tokio::test]
+async fn a_root_selects_what_it_reaches() {
+    let storage = storage().await;
+    let everything = export_graph(&storage, &Selection::default()).await.unwrap();
+    let pizza = everything.nodes.iter().find(|n| n.name == "Pizza").unwrap();
+    let selection = Selection {
+        schemas: Vec::new(),
+        root: Some(pizza.id),
+    };
+    let graph = export_graph(&storage, &selection).await.unwrap();
+    assert_eq!(
+        names(&graph),
+        (
+            vec!["Food", "Ingredient"],
+            vec!["Pizza", "Tomatoes", "Basil"],
+            2
+        )
+    );
+}
@@ -1,33 +1,117 @@
 use async_graphql::{EmptySubscription, MergedObject, Schema, extensions::Logger};
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::Extension;
+use http::HeaderMap;
 
+use crate::{
+    analytics::AnalyticsCache,
+    auth::BearerToken,
+    config::AuthConfig,
+    derived::DerivedCache,
+    error::Error,
+    repository::{EdgeRepository, NodeRepository, Page, SchemaRepository, UserRepository},
+    storage::SharedStorage,
+};
+
+mod analytics;
+mod backup;
 mod edge;
+mod limits;
+mod loaders;
 mod node;
+mod persisted;
+mod query;
+mod relay;
 mod schema;
+pub(crate) mod typed;
+
+pub use limits::{QueryLimits, list_cost};
+pub use loaders::{EdgesOf, GraphDataLoader, GraphLoader};
+pub use persisted::{PersistedOperations, PersistedQueries};
+pub use relay::GlobalId;
+pub use typed::{TypedSchema, typed_graphql_handler};
 
 #[derive(Default, MergedObject)]
-pub struct Query(schema::Schema, node::Node, edge::Edge);
+pub struct Query(
+    relay::RelayQuery,
+    schema::Schema,
+    node::NodeQuery,
+    edge::Edge,
+    query::GraphQuery,
+    analytics::AnalyticsQuery,
+    backup::BackupQuery,
+);
 
 #[derive(Default, MergedObject)]
 pub struct Mutation(
     schema::SchemaMutation,
     node::NodeMutation,
     edge::EdgeMutation,
+    backup::BackupMutation,
 );
 
 pub type SchemaType = Schema<Query, Mutation, EmptySubscription>;
 
-pub fn create_schema(database_pool: sqlx::PgPool) -> SchemaType {
-    Schema::build(Query::default(), Mutation::default(), EmptySubscription)
+/// The schema of `/graphql`; `backup` and `restore` need an administrator's
+/// access token signed as `auth` says, whose user is also an administrator
+/// in `users` when the server has them.
+pub fn create_schema(
+    storage: SharedStorage,
+    users: Option<UserRepository>,
+    auth: AuthConfig,
+    persisted: PersistedQueries,
+    limits: QueryLimits,
+) -> SchemaType {
+    let schema = Schema::build(Query::default(), Mutation::default(), EmptySubscription)
         .extension(Logger)
-        .data(database_pool)
-        .finish()
+        .extension(persisted)
+        .extension(limits)
+        .data(loaders::data_loader(storage.clone()))
+        .data(SchemaRepository::new(storage.clone()))
+        .data(NodeRepository::new(storage.clone()))
+        .data(EdgeRepository::new(storage.clone()))
+        .data(storage)
+        .data(DerivedCache::default())
+        .data(AnalyticsCache::default())
+        .data(auth);
+    match users {
+        Some(users) => schema.data(users).finish(),
+        None => schema.finish(),
+    }
 }
 
+/// `POST /graphql`
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    tag = "graphql",
+    request_body(content = Object, description = "A GraphQL request with `query`, `variables` and `operationName`"),
+    responses((status = 200, description = "A GraphQL response with `data` and `errors`", body = Object))
+)]
 pub async fn graphql_handler(
     schema: Extension<SchemaType>,
+    headers: HeaderMap,
     request: GraphQLRequest,
 ) -> GraphQLResponse {
-    schema.execute(request.into_inner()).await.into()
+    let mut request = request.into_inner();
+    if let Some(token) = BearerToken::from_headers(&headers) {
+        request = request.data(token);
+    }
+    schema.execute(request).await.into()
+}
+
+/// The page requested by the `limit` and `offset` arguments of a list field,
+/// [`limits::DEFAULT_LIST_SIZE`] items without a `limit`.
+pub(crate) fn page(limit: Option<i64>, offset: i64) -> Result<Page, Error> {
+    let limit = limit.unwrap_or(limits::DEFAULT_LIST_SIZE);
+    if !(0..=limits::MAX_LIST_SIZE).contains(&limit) || offset < 0 {
+        return Err(Error::ValidationFailed(format!(
+            "limit must be within 0..={} and offset must not be negative",
+            limits::MAX_LIST_SIZE
+        )));
+    }
+    Ok(Page {
+        limit: Some(limit),
+        offset,
+    })
 }
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use super::limits::list_cost;
+use crate::{
+    analytics::{
+        Analytics, AnalyticsCache, Component, DEFAULT_DAMPING_FACTOR, DegreeStats,
+        MAX_RANKED_NODES, NodeScore,
+    },
+    error::Error,
+    model::DbNode,
+    repository::Direction,
+    storage::SharedStorage,
+};
+
+#[derive(Default)]
+pub struct AnalyticsQuery;
+
+#[async_graphql::Object]
+impl AnalyticsQuery {
+    /// How many edges labelled `edgeLabel`, or any edges, the nodes of
+    /// `schemaTitle` have in `direction`, with the `limit` most connected.
+    #[graphql(complexity = "list_cost(Some(limit.clamp(0, MAX_RANKED_NODES)), child_complexity)")]
+    async fn degree_stats(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        schema_title: Option<String>,
+        edge_label: Option<String>,
+        #[graphql(default)] direction: Direction,
+        #[graphql(default = 20)] limit: i64,
+    ) -> Result<DegreeStats, async_graphql::Error> {
+        Ok(load(ctx).await?.degree_stats(
+            schema_title.as_deref(),
+            edge_label.as_deref(),
+            direction,
+            ranked(limit)?,
+        ))
+    }
+
+    /// The nodes of `schemaTitle` with the highest PageRank over the edges
+    /// labelled `edgeLabel`: those many well-ranked nodes point to.
+    /// `dampingFactor` is rounded to two decimals.
+    #[graphql(complexity = "list_cost(Some(limit.clamp(0, MAX_RANKED_NODES)), child_complexity)")]
+    async fn page_rank(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        schema_title: Option<String>,
+        edge_label: Option<String>,
+        #[graphql(default_with = "DEFAULT_DAMPING_FACTOR")] damping_factor: f64,
+        #[graphql(default = 20)] limit: i64,
+    ) -> Result<Vec<NodeScore>, async_graphql::Error> {
+        if !(0.0..=1.0).contains(&damping_factor) {
+            return Err(Error::ValidationFailed(
+                "dampingFactor must be between 0 and 1".to_string(),
+            )
+            .into());
+        }
+        let limit = ranked(limit)?;
+        blocking(ctx, move |analytics| {
+            analytics.page_rank(
+                schema_title.as_deref(),
+                edge_label.as_deref(),
+                damping_factor,
+                limit,
+            )
+        })
+        .await
+    }
+
+    /// The nodes of `schemaTitle` on the most shortest paths between other
+    /// nodes along the edges labelled `edgeLabel`. Each node costs a
+    /// breadth-first search over the graph; beyond 1000 nodes the scores are
+    /// estimated from the paths of 1000 of them.
+    #[graphql(complexity = "list_cost(Some(limit.clamp(0, MAX_RANKED_NODES)), child_complexity)")]
+    async fn betweenness(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        schema_title: Option<String>,
+        edge_label: Option<String>,
+        #[graphql(default = 20)] limit: i64,
+    ) -> Result<Vec<NodeScore>, async_graphql::Error> {
+        let limit = ranked(limit)?;
+        blocking(ctx, move |analytics| {
+            analytics.betweenness(schema_title.as_deref(), edge_label.as_deref(), limit)
+        })
+        .await
+    }
+
+    /// The groups of nodes linked by edges labelled `edgeLabel`, or by any
+    /// edges, in either direction, largest first; `maxSize` finds the small
+    /// ones cut off from the rest.
+    #[graphql(complexity = "list_cost(limit, child_complexity)")]
+    async fn connected_components(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        edge_label: Option<String>,
+        max_size: Option<i64>,
+        limit: Option<i64>,
+        #[graphql(default = 0)] offset: i64,
+    ) -> Result<Vec<Component>, async_graphql::Error> {
+        let page = super::page(limit, offset)?;
+        let max_size = max_size.map(|max_size| max_size.max(0) as usize);
+        Ok(load(ctx)
+            .await?
+            .connected_components(edge_label.as_deref(), max_size)
+            .into_iter()
+            .skip(page.offset as usize)
+            .take(page.limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect())
+    }
+
+    /// The nodes of `schemaTitle` without any edge, newest first.
+    #[graphql(complexity = "list_cost(limit, child_complexity)")]
+    async fn orphan_nodes(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        schema_title: Option<String>,
+        limit: Option<i64>,
+        #[graphql(default = 0)] offset: i64,
+    ) -> Result<Vec<DbNode>, async_graphql::Error> {
+        let page = super::page(limit, offset)?;
+        Ok(load(ctx)
+            .await?
+            .orphan_nodes(schema_title.as_deref())
+            .into_iter()
+            .skip(page.offset as usize)
+            .take(page.limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect())
+    }
+}
+
+#[async_graphql::ComplexObject]
+impl Component {
+    /// The nodes of the component, in the order they were created.
+    #[graphql(complexity = "list_cost(limit, child_complexity)")]
+    async fn nodes(
+        &self,
+        limit: Option<i64>,
+        #[graphql(default = 0)] offset: i64,
+    ) -> Result<Vec<DbNode>, async_graphql::Error> {
+        let page = super::page(limit, offset)?;
+        Ok(self
+            .nodes
+            .iter()
+            .skip(page.offset as usize)
+            .take(page.limit.map_or(usize::MAX, |limit| limit as usize))
+            .cloned()
+            .collect())
+    }
+}
+
+async fn load(ctx: &async_graphql::Context<'_>) -> Result<Arc<Analytics>, async_graphql::Error> {
+    let storage = ctx.data::<SharedStorage>()?;
+    Ok(ctx.data::<AnalyticsCache>()?.load(storage.as_ref()).await?)
+}
+
+/// Run `analysis` of the current graph on a thread that may block, as the
+/// centralities take long on a large graph.
+async fn blocking<T: Send + 'static>(
+    ctx: &async_graphql::Context<'_>,
+    analysis: impl FnOnce(&Analytics) -> T + Send + 'static,
+) -> Result<T, async_graphql::Error> {
+    let analytics = load(ctx).await?;
+    Ok(tokio::task::spawn_blocking(move || analysis(&analytics))
+        .await
+        .map_err(Error::internal)?)
+}
+
+/// The number of ranked nodes `limit` asks for.
+fn ranked(limit: i64) -> Result<usize, Error> {
+    if limit < 0 {
+        return Err(Error::ValidationFailed(
+            "limit must not be negative".to_string(),
+        ));
+    }
+    Ok(limit.min(MAX_RANKED_NODES) as usize)
+}
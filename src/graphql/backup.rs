@@ -0,0 +1,85 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+use crate::{
+    auth::{self, BearerToken, Claims},
+    backup::{self, Backup, RestoreMode, RestoreReport},
+    config::AuthConfig,
+    error::Error,
+    repository::UserRepository,
+    storage::SharedStorage,
+};
+
+/// The claims of the caller's access token, which must have been issued to
+/// an administrator who still is one when the users are in the database.
+async fn administrator(ctx: &async_graphql::Context<'_>) -> Result<Claims, async_graphql::Error> {
+    let token = ctx.data_opt::<BearerToken>().ok_or_else(|| {
+        Error::Unauthorized("send an access token in the Authorization header".to_string())
+    })?;
+    let claims = auth::verify_token(ctx.data::<AuthConfig>()?, &token.0)?;
+    if !claims.admin {
+        return Err(
+            Error::Unauthorized(format!("user '{}' is not an administrator", claims.sub)).into(),
+        );
+    }
+    if let Some(users) = ctx.data_opt::<UserRepository>() {
+        match users.get(&claims.sub).await? {
+            Some(user) if user.id == claims.uid && user.admin => {}
+            Some(user) if user.id == claims.uid => {
+                return Err(Error::Unauthorized(format!(
+                    "user '{}' is no longer an administrator",
+                    claims.sub
+                ))
+                .into());
+            }
+            _ => {
+                return Err(
+                    Error::Unauthorized(format!("user '{}' no longer exists", claims.sub)).into(),
+                );
+            }
+        }
+    }
+    Ok(claims)
+}
+
+#[derive(Default)]
+pub struct BackupQuery;
+
+#[async_graphql::Object]
+impl BackupQuery {
+    /// A base64-encoded archive of every schema, node and edge, for `restore`
+    /// or `lixiv restore`.
+    async fn backup(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<String, async_graphql::Error> {
+        let claims = administrator(ctx).await?;
+        let storage = ctx.data::<SharedStorage>()?;
+        let backup = backup::backup(storage.as_ref()).await?;
+        tracing::info!(user = %claims.sub, nodes = backup.nodes.len(), "backed up the inventory");
+        Ok(BASE64_STANDARD.encode(backup.to_archive()?))
+    }
+}
+
+#[derive(Default)]
+pub struct BackupMutation;
+
+#[async_graphql::Object]
+impl BackupMutation {
+    /// Restore a base64-encoded archive made by `backup` or `lixiv backup`.
+    async fn restore(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        archive: String,
+        #[graphql(default)] mode: RestoreMode,
+    ) -> Result<RestoreReport, async_graphql::Error> {
+        let claims = administrator(ctx).await?;
+        let archive = BASE64_STANDARD
+            .decode(archive.trim())
+            .map_err(|e| Error::ValidationFailed(format!("archive is not base64: {e}")))?;
+        let backup = Backup::from_archive(&archive)?;
+        let storage = ctx.data::<SharedStorage>()?;
+        let report = backup::restore(storage.as_ref(), &backup, mode).await?;
+        tracing::info!(user = %claims.sub, ?mode, "restored a backup");
+        Ok(report)
+    }
+}
@@ -1,28 +1,31 @@
-use crate::model::DbEdge;
+use async_graphql::ID;
+
+use super::{
+    limits::list_cost,
+    relay::{self, GlobalId},
+};
+use crate::{
+    model::DbEdge,
+    repository::{EdgeRepository, NewEdge},
+};
 
 #[derive(Default)]
 pub struct Edge;
 
 #[async_graphql::Object]
 impl Edge {
+    /// Edges, newest first.
+    #[graphql(complexity = "list_cost(limit, child_complexity)")]
     async fn edges(
         &self,
         ctx: &async_graphql::Context<'_>,
+        limit: Option<i64>,
+        #[graphql(default = 0)] offset: i64,
     ) -> Result<Vec<DbEdge>, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let edges = sqlx::query_as!(
-            DbEdge,
-            r#"
-            SELECT id, source_node_id, target_node_id, weight, created_at
-            FROM edges
-            ORDER BY created_at DESC
-            "#
-        )
-        .fetch_all(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
-
-        Ok(edges)
+        Ok(ctx
+            .data::<EdgeRepository>()?
+            .list(super::page(limit, offset)?)
+            .await?)
     }
 }
 
@@ -34,46 +37,28 @@ impl EdgeMutation {
     async fn create_edge(
         &self,
         ctx: &async_graphql::Context<'_>,
-        source_node_id: i32,
-        target_node_id: i32,
+        source_node_id: ID,
+        target_node_id: ID,
         weight: String,
     ) -> Result<DbEdge, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let edge = sqlx::query_as!(
-            DbEdge,
-            r#"
-            INSERT INTO edges (source_node_id, target_node_id, weight)
-            VALUES ($1, $2, $3)
-            RETURNING id, source_node_id, target_node_id, weight, created_at
-            "#,
-            source_node_id,
-            target_node_id,
-            weight
-        )
-        .fetch_one(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
-
-        Ok(edge)
+        let ids = relay::node_ids(ctx, &[source_node_id, target_node_id]).await?;
+        let edge = NewEdge {
+            source_node_id: ids[0],
+            target_node_id: ids[1],
+            weight,
+        };
+        Ok(ctx.data::<EdgeRepository>()?.create(edge).await?)
     }
 
     async fn delete_edge(
         &self,
         ctx: &async_graphql::Context<'_>,
-        id: i32,
+        id: ID,
     ) -> Result<bool, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let result = sqlx::query!(
-            r#"
-            DELETE FROM edges
-            WHERE id = $1
-            "#,
-            id
-        )
-        .execute(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
-
-        Ok(result.rows_affected() > 0)
+        let edges = ctx.data::<EdgeRepository>()?;
+        match edges.get_by_uid(GlobalId::edge(&id)?).await? {
+            Some(edge) => Ok(edges.delete(edge.id).await?),
+            None => Ok(false),
+        }
     }
 }
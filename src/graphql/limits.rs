@@ -0,0 +1,363 @@
+//! Limits on the depth, cost and number of aliases of GraphQL operations.
+//!
+//! Every field costs 1 plus the cost of its selection. List fields multiply
+//! the cost of their selection by the number of items they may return, which
+//! is the requested `limit` or the default page size. Operations over a limit are
+//! rejected before anything is resolved, with an error whose extensions name
+//! the limit; the cost of executed operations is reported in the `cost`
+//! response extension.
+//!
+//! Fields of a dynamic schema cannot declare their cost, so async-graphql
+//! counts its list fields like any other field; [`QueryLimits::with_list_fields`]
+//! names them to add the difference.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use async_graphql::{
+    Name, Positioned, Response, ServerError, ServerResult, ValidationResult, Value, Variables,
+    extensions::{
+        Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextValidation,
+    },
+    parser::types::{
+        ExecutableDocument, Field, OperationType, Selection, SelectionSet, VariableDefinition,
+    },
+    value,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// The number of items list fields return without a `limit`.
+pub const DEFAULT_LIST_SIZE: i64 = 100;
+/// The largest `limit` list fields accept.
+pub const MAX_LIST_SIZE: i64 = 1000;
+
+/// The highest cost of a field, low enough that async-graphql can add up the
+/// costs of its siblings without overflowing.
+const MAX_FIELD_COST: usize = u32::MAX as usize;
+
+/// The cost of a list field returning at most `limit` items, or
+/// [`DEFAULT_LIST_SIZE`] without one.
+pub fn list_cost(limit: Option<i64>, child_complexity: usize) -> usize {
+    let size = limit.unwrap_or(DEFAULT_LIST_SIZE).clamp(0, MAX_LIST_SIZE) as usize;
+    size.saturating_mul(child_complexity)
+        .saturating_add(1)
+        .min(MAX_FIELD_COST)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueryLimits {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    pub max_aliases: usize,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            max_depth: 12,
+            max_complexity: 10_000,
+            max_aliases: 30,
+        }
+    }
+}
+
+impl QueryLimits {
+    /// The limits for a dynamic schema whose `Query` fields `list_fields`
+    /// cost [`list_cost`] of their `limit` argument.
+    pub fn with_list_fields(self, list_fields: HashSet<String>) -> DynamicLimits {
+        DynamicLimits {
+            limits: self,
+            list_fields: Arc::new(list_fields),
+        }
+    }
+}
+
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(LimitsExtension {
+            limits: *self,
+            list_fields: Arc::default(),
+            surcharge: Mutex::default(),
+            cost: Mutex::default(),
+        })
+    }
+}
+
+/// [`QueryLimits`] for a dynamic schema.
+#[derive(Debug, Clone)]
+pub struct DynamicLimits {
+    limits: QueryLimits,
+    list_fields: Arc<HashSet<String>>,
+}
+
+impl ExtensionFactory for DynamicLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(LimitsExtension {
+            limits: self.limits,
+            list_fields: self.list_fields.clone(),
+            surcharge: Mutex::default(),
+            cost: Mutex::default(),
+        })
+    }
+}
+
+struct LimitsExtension {
+    limits: QueryLimits,
+    list_fields: Arc<HashSet<String>>,
+    /// The cost of `list_fields` that async-graphql does not count.
+    surcharge: Mutex<usize>,
+    /// Complexity and depth of the validated operation.
+    cost: Mutex<Option<(usize, usize)>>,
+}
+
+/// An error with the measured value under `key` and the limit under
+/// `max_key` in its extensions.
+fn limit_exceeded(
+    code: &str,
+    message: String,
+    (key, actual): (&str, usize),
+    (max_key, max): (&str, usize),
+) -> ServerError {
+    let mut error = ServerError::new(message, None);
+    let extensions = error.extensions.get_or_insert_with(Default::default);
+    extensions.set("code", code);
+    extensions.set(key, actual as u64);
+    extensions.set(max_key, max as u64);
+    error
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for LimitsExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let mut fields = FieldSum::new(&document);
+        let aliases = document
+            .operations
+            .iter()
+            .map(|(_, operation)| fields.sum(&operation.node.selection_set.node, &mut aliases))
+            .fold(0, usize::saturating_add);
+        if aliases > self.limits.max_aliases {
+            return Err(limit_exceeded(
+                "TOO_MANY_ALIASES",
+                format!(
+                    "the operation uses {aliases} aliases, at most {} are allowed",
+                    self.limits.max_aliases
+                ),
+                ("aliases", aliases),
+                ("maxAliases", self.limits.max_aliases),
+            ));
+        }
+        if !self.list_fields.is_empty() {
+            *self.surcharge.lock().expect("surcharge lock poisoned") =
+                list_surcharge(&document, variables, &self.list_fields);
+        }
+        Ok(document)
+    }
+
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let mut result = next.run(ctx).await?;
+        result.complexity = result
+            .complexity
+            .saturating_add(*self.surcharge.lock().expect("surcharge lock poisoned"));
+        let QueryLimits {
+            max_depth,
+            max_complexity,
+            ..
+        } = self.limits;
+        if result.depth > max_depth {
+            return Err(vec![limit_exceeded(
+                "QUERY_TOO_DEEP",
+                format!(
+                    "the operation is nested {} levels deep, at most {max_depth} are allowed",
+                    result.depth
+                ),
+                ("depth", result.depth),
+                ("maxDepth", max_depth),
+            )]);
+        }
+        if result.complexity > max_complexity {
+            return Err(vec![limit_exceeded(
+                "QUERY_TOO_COMPLEX",
+                format!(
+                    "the operation costs {}, at most {max_complexity} is allowed; request fewer items or fields",
+                    result.complexity
+                ),
+                ("complexity", result.complexity),
+                ("maxComplexity", max_complexity),
+            )]);
+        }
+        *self.cost.lock().expect("cost lock poisoned") = Some((result.complexity, result.depth));
+        Ok(result)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let response = next.run(ctx, operation_name).await;
+        let cost = *self.cost.lock().expect("cost lock poisoned");
+        match cost {
+            Some((complexity, depth)) => response.extension(
+                "cost",
+                value!({
+                    "complexity": complexity,
+                    "maxComplexity": self.limits.max_complexity,
+                    "depth": depth,
+                    "maxDepth": self.limits.max_depth,
+                }),
+            ),
+            None => response,
+        }
+    }
+}
+
+/// The aliases of `field` and its selection.
+fn aliases<'a>(fields: &mut FieldSum<'a>, field: &'a Field) -> usize {
+    fields
+        .sum(&field.selection_set.node, &mut aliases)
+        .saturating_add(usize::from(field.alias.is_some()))
+}
+
+/// Every field costs 1 plus the cost of its selection.
+fn cost<'a>(fields: &mut FieldSum<'a>, field: &'a Field) -> usize {
+    fields
+        .sum(&field.selection_set.node, &mut cost)
+        .saturating_add(1)
+}
+
+/// How much more the `list_fields` selected by the queries of `document` cost
+/// than the 1 plus the cost of their selection async-graphql counts.
+fn list_surcharge(
+    document: &ExecutableDocument,
+    variables: &Variables,
+    list_fields: &HashSet<String>,
+) -> usize {
+    let mut costs = FieldSum::new(document);
+    document
+        .operations
+        .iter()
+        .filter(|(_, operation)| operation.node.ty == OperationType::Query)
+        .map(|(_, operation)| {
+            let definitions = &operation.node.variable_definitions;
+            FieldSum::new(document).sum(&operation.node.selection_set.node, &mut |_, field| {
+                if !list_fields.contains(field.name.node.as_str()) {
+                    return 0;
+                }
+                let children = costs.sum(&field.selection_set.node, &mut cost);
+                let limit = limit(field, variables, definitions);
+                list_cost(limit, children).saturating_sub(children.saturating_add(1))
+            })
+        })
+        .fold(0, usize::saturating_add)
+}
+
+/// Adds up a count over the fields of selection sets, with fragments spread
+/// into them. A fragment is counted once and its count reused wherever it is
+/// spread, so fragments spreading each other many times cannot make this
+/// exponential.
+struct FieldSum<'a> {
+    document: &'a ExecutableDocument,
+    fragments: HashMap<&'a Name, usize>,
+    spreading: Vec<&'a Name>,
+}
+
+impl<'a> FieldSum<'a> {
+    fn new(document: &'a ExecutableDocument) -> Self {
+        FieldSum {
+            document,
+            fragments: HashMap::new(),
+            spreading: Vec::new(),
+        }
+    }
+
+    /// The sum of `count` over the fields of `selection_set`. A `FieldSum`
+    /// must always be used with the same `count`.
+    fn sum(
+        &mut self,
+        selection_set: &'a SelectionSet,
+        count: &mut dyn FnMut(&mut Self, &'a Field) -> usize,
+    ) -> usize {
+        let mut sum = 0usize;
+        for selection in &selection_set.items {
+            let counted = match &selection.node {
+                Selection::Field(field) => count(self, &field.node),
+                Selection::InlineFragment(fragment) => {
+                    self.sum(&fragment.node.selection_set.node, count)
+                }
+                Selection::FragmentSpread(spread) => {
+                    self.spread(&spread.node.fragment_name.node, count)
+                }
+            };
+            sum = sum.saturating_add(counted);
+        }
+        sum
+    }
+
+    fn spread(
+        &mut self,
+        name: &'a Name,
+        count: &mut dyn FnMut(&mut Self, &'a Field) -> usize,
+    ) -> usize {
+        if let Some(&counted) = self.fragments.get(name) {
+            return counted;
+        }
+        // unknown fragments and cycles are rejected by validation, which runs later
+        let Some(fragment) = self.document.fragments.get(name) else {
+            return 0;
+        };
+        if self.spreading.contains(&name) {
+            return 0;
+        }
+        self.spreading.push(name);
+        let counted = self.sum(&fragment.node.selection_set.node, count);
+        self.spreading.pop();
+        self.fragments.insert(name, counted);
+        counted
+    }
+}
+/// The `limit` argument of `field`, from the variables or their defaults if
+/// it is one.
+fn limit(
+    field: &Field,
+    variables: &Variables,
+    definitions: &[Positioned<VariableDefinition>],
+) -> Option<i64> {
+    let value = field
+        .get_argument("limit")?
+        .node
+        .clone()
+        .into_const_with(|name| {
+            variables
+                .get(&name)
+                .cloned()
+                .or_else(|| {
+                    definitions
+                        .iter()
+                        .find(|definition| definition.node.name.node == name)
+                        .and_then(|definition| definition.node.default_value())
+                        .cloned()
+                })
+                .ok_or(())
+        })
+        .ok()?;
+    match value {
+        Value::Number(limit) => limit.as_i64(),
+        _ => None,
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+
+use async_graphql::{Variables, parser::parse_query, value};
+
+use super::{FieldSum, aliases, list_surcharge};
+
+fn alias_count(query: &str) -> usize {
+    let document = parse_query(query).expect("query parses");
+    let mut fields = FieldSum::new(&document);
+    document
+        .operations
+        .iter()
+        .map(|(_, operation)| fields.sum(&operation.node.selection_set.node, &mut aliases))
+        .sum()
+}
+
+#[test]
+fn aliases_count_where_fragments_are_spread() {
+    assert_eq!(alias_count("{ a: node { b: name } c: node { name } }"), 3);
+    assert_eq!(
+        alias_count(
+            "{ ...F ...F node { ... on Node { ...F } } }
+             fragment F on Query { a: node { b: name } }"
+        ),
+        6
+    );
+    assert_eq!(
+        alias_count("fragment F on Query { a: node { name } } { name }"),
+        0
+    );
+}
+
+#[test]
+fn nested_fragments_are_counted_once() {
+    let mut query = "{ ...F0 } fragment F40 on Query { a: name }".to_string();
+    for i in 0..40 {
+        let next = i + 1;
+        query.push_str(&format!(
+            " fragment F{i} on Query {{ ...F{next} ...F{next} }}"
+        ));
+    }
+    assert_eq!(alias_count(&query), 1 << 40);
+    assert_eq!(
+        alias_count("{ ...A } fragment A on Query { x: name ...B } fragment B on Query { ...A }"),
+        1
+    );
+}
+
+#[test]
+fn list_fields_cost_their_limit() {
+    let list_fields = HashSet::from(["foods".to_string()]);
+    let surcharge = |query: &str, variables: Variables| {
+        let document = parse_query(query).expect("query parses");
+        list_surcharge(&document, &variables, &list_fields)
+    };
+    // async-graphql counts 1 plus the selection, a list `limit` times the selection plus 1
+    assert_eq!(
+        surcharge("{ foods(limit: 10) { name id } }", Variables::default()),
+        18
+    );
+    assert_eq!(surcharge("{ foods { name } }", Variables::default()), 99);
+    assert_eq!(
+        surcharge(
+            "query($n: Int = 5) { ...F } fragment F on Query { foods(limit: $n) { name } }",
+            Variables::default()
+        ),
+        4
+    );
+    assert_eq!(
+        surcharge(
+            "query($n: Int = 5) { foods(limit: $n) { name } }",
+            Variables::from_value(value!({ "n": 20 }))
+        ),
+        19
+    );
+    assert_eq!(
+        surcharge(
+            "{ typedSchemas node { foods { name } } }",
+            Variables::default()
+        ),
+        0
+    );
+    assert_eq!(
+        surcharge(
+            "mutation { foods(limit: 10) { name } }",
+            Variables::default()
+        ),
+        0
+    );
+}
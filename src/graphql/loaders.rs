@@ -0,0 +1,95 @@
+//! Batched lookups for resolvers that follow references from one row to
+//! another, so that a list of edges with their endpoints takes one query for
+//! the edges and one for all endpoints instead of one per edge.
+//!
+//! The loader does not cache between requests: it only merges the lookups
+//! that are made while one selection set is resolved.
+
+use std::collections::HashMap;
+
+use async_graphql::dataloader::Loader;
+
+use crate::{
+    error::Error,
+    model::{DbEdge, DbNode, DbSchema},
+    repository::{Direction, EdgeRepository, NodeRepository, Page, SchemaRepository},
+    storage::SharedStorage,
+};
+
+pub type GraphDataLoader = async_graphql::dataloader::DataLoader<GraphLoader>;
+
+pub fn data_loader(storage: SharedStorage) -> GraphDataLoader {
+    let loader = GraphLoader {
+        schemas: SchemaRepository::new(storage.clone()),
+        nodes: NodeRepository::new(storage.clone()),
+        edges: EdgeRepository::new(storage),
+    };
+    GraphDataLoader::new(loader, tokio::spawn)
+}
+
+/// Loads nodes by id, schemas by title and edges by one of their endpoints.
+pub struct GraphLoader {
+    schemas: SchemaRepository,
+    nodes: NodeRepository,
+    edges: EdgeRepository,
+}
+
+/// A page of the edges leaving and/or entering a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EdgesOf {
+    pub node_id: i32,
+    pub direction: Direction,
+    pub page: Page,
+}
+
+impl Loader<i32> for GraphLoader {
+    type Value = DbNode;
+    type Error = Error;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, DbNode>, Self::Error> {
+        let nodes = self.nodes.get_many(ids).await?;
+        Ok(nodes.into_iter().map(|node| (node.id, node)).collect())
+    }
+}
+
+impl Loader<String> for GraphLoader {
+    type Value = DbSchema;
+    type Error = Error;
+
+    async fn load(&self, titles: &[String]) -> Result<HashMap<String, DbSchema>, Self::Error> {
+        let schemas = self.schemas.get_many(titles).await?;
+        Ok(schemas
+            .into_iter()
+            .map(|schema| (schema.title.clone(), schema))
+            .collect())
+    }
+}
+
+impl Loader<EdgesOf> for GraphLoader {
+    type Value = Vec<DbEdge>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[EdgesOf]) -> Result<HashMap<EdgesOf, Vec<DbEdge>>, Self::Error> {
+        let mut batches: HashMap<(Direction, Page), Vec<i32>> = HashMap::new();
+        for key in keys {
+            batches
+                .entry((key.direction, key.page))
+                .or_default()
+                .push(key.node_id);
+        }
+
+        let mut edges = HashMap::with_capacity(keys.len());
+        for ((direction, page), node_ids) in batches {
+            let mut found = self.edges.of_each_node(&node_ids, direction, page).await?;
+            for node_id in node_ids {
+                let key = EdgesOf {
+                    node_id,
+                    direction,
+                    page,
+                };
+                edges.insert(key, found.remove(&node_id).unwrap_or_default());
+            }
+        }
+        Ok(edges)
+    }
+}
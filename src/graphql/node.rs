@@ -1,50 +1,89 @@
-use crate::model::DbNode;
+use async_graphql::ID;
+
+use super::{
+    limits::list_cost,
+    relay::{self, GlobalId},
+};
+use crate::{
+    model::{DbCreatedNode, DbDuplicateCandidate, DbNode, DbSearchHit, DbSimilarNode},
+    repository::{
+        DUPLICATE_CANDIDATES_LIMIT, MAX_SEARCH_HITS, MergeNodes, MergeStrategy, NewNode,
+        NodeFilter, NodeOrder, NodeRepository, SIMILAR_NODES_LIMIT,
+    },
+};
+
+/// Similarity above which two names count as near-duplicates by default.
+const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.4;
 
 #[derive(Default)]
-pub struct Node;
+pub struct NodeQuery;
 
 #[async_graphql::Object]
-impl Node {
+impl NodeQuery {
+    /// All nodes, or those of `schemaTitle` and, with `includeSubtypes`, of
+    /// the schemas extending it, newest first.
+    #[graphql(complexity = "list_cost(limit, child_complexity)")]
     async fn nodes(
         &self,
         ctx: &async_graphql::Context<'_>,
+        schema_title: Option<String>,
+        #[graphql(default = false)] include_subtypes: bool,
+        limit: Option<i64>,
+        #[graphql(default = 0)] offset: i64,
     ) -> Result<Vec<DbNode>, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let nodes = sqlx::query_as!(
-            DbNode,
-            r#"
-            SELECT id, schema_title, name, data as "data: serde_json::Value", created_at, updated_at
-            FROM nodes
-            ORDER BY created_at DESC
-            "#
-        )
-        .fetch_all(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+        let filter = NodeFilter {
+            schema_title,
+            include_subtypes,
+            order: NodeOrder::Newest,
+        };
+        Ok(ctx
+            .data::<NodeRepository>()?
+            .list(&filter, super::page(limit, offset)?)
+            .await?)
+    }
 
-        Ok(nodes)
+    /// Ranked full-text search over node names and string properties.
+    #[graphql(complexity = "list_cost(Some(limit.clamp(1, MAX_SEARCH_HITS)), child_complexity)")]
+    async fn search(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        text: String,
+        schema_titles: Option<Vec<String>>,
+        #[graphql(default = 20)] limit: i64,
+    ) -> Result<Vec<DbSearchHit>, async_graphql::Error> {
+        Ok(ctx
+            .data::<NodeRepository>()?
+            .search(&text, &schema_titles.unwrap_or_default(), limit)
+            .await?)
     }
 
-    async fn node(
+    /// Nodes whose names are spelled similarly, e.g. "tomatos" and "Tomatoes".
+    #[graphql(complexity = "list_cost(Some(SIMILAR_NODES_LIMIT), child_complexity)")]
+    async fn similar_nodes(
         &self,
         ctx: &async_graphql::Context<'_>,
-        id: i32,
-    ) -> Result<Option<DbNode>, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let node = sqlx::query_as!(
-            DbNode,
-            r#"
-            SELECT id, schema_title, name, data as "data: serde_json::Value", created_at, updated_at
-            FROM nodes
-            WHERE id = $1
-            "#,
-            id
-        )
-        .fetch_optional(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+        name: String,
+        schema_title: Option<String>,
+        #[graphql(default_with = "DEFAULT_SIMILARITY_THRESHOLD")] threshold: f32,
+    ) -> Result<Vec<DbSimilarNode>, async_graphql::Error> {
+        Ok(ctx
+            .data::<NodeRepository>()?
+            .similar(&name, schema_title.as_deref(), threshold)
+            .await?)
+    }
 
-        Ok(node)
+    /// Pairs of nodes in a schema whose names are spelled alike.
+    #[graphql(complexity = "list_cost(Some(DUPLICATE_CANDIDATES_LIMIT), child_complexity)")]
+    async fn duplicate_candidates(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        schema_title: String,
+        #[graphql(default_with = "DEFAULT_SIMILARITY_THRESHOLD")] threshold: f32,
+    ) -> Result<Vec<DbDuplicateCandidate>, async_graphql::Error> {
+        Ok(ctx
+            .data::<NodeRepository>()?
+            .duplicate_candidates(&schema_title, threshold)
+            .await?)
     }
 }
 
@@ -53,49 +92,54 @@ pub struct NodeMutation;
 
 #[async_graphql::Object]
 impl NodeMutation {
+    /// Create a node; with `duplicateThreshold` the nodes of the schema whose
+    /// names are at least that similar are returned as `nearDuplicates`.
     async fn create_node(
         &self,
         ctx: &async_graphql::Context<'_>,
         schema_title: String,
         name: String,
         data: serde_json::Value,
-    ) -> Result<DbNode, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let node = sqlx::query_as!(
-            DbNode,
-            r#"
-            INSERT INTO nodes (schema_title, name, data)
-            VALUES ($1, $2, $3)
-            RETURNING id, schema_title, name, data as "data: serde_json::Value", created_at, updated_at
-            "#,
+        duplicate_threshold: Option<f32>,
+    ) -> Result<DbCreatedNode, async_graphql::Error> {
+        let node = NewNode {
             schema_title,
             name,
-            data
-        )
-        .fetch_one(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+            data,
+        };
+        Ok(ctx
+            .data::<NodeRepository>()?
+            .create(node, duplicate_threshold)
+            .await?)
+    }
 
-        Ok(node)
+    /// Fold `merge_ids` into `keep_id`: edges are re-pointed to the survivor,
+    /// `data` is combined and re-validated and the merged nodes are deleted.
+    async fn merge_nodes(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        keep_id: ID,
+        merge_ids: Vec<ID>,
+        #[graphql(default_with = "MergeStrategy::PreferKeep")] data_strategy: MergeStrategy,
+    ) -> Result<DbNode, async_graphql::Error> {
+        let mut ids = relay::node_ids(ctx, &[keep_id]).await?;
+        let merge = MergeNodes {
+            keep_id: ids.remove(0),
+            merge_ids: relay::node_ids(ctx, &merge_ids).await?,
+            strategy: data_strategy,
+        };
+        Ok(ctx.data::<NodeRepository>()?.merge(merge).await?)
     }
 
     async fn delete_node(
         &self,
         ctx: &async_graphql::Context<'_>,
-        id: i32,
+        id: ID,
     ) -> Result<bool, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let result = sqlx::query!(
-            r#"
-            DELETE FROM nodes
-            WHERE id = $1
-            "#,
-            id
-        )
-        .execute(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
-
-        Ok(result.rows_affected() > 0)
+        let nodes = ctx.data::<NodeRepository>()?;
+        match nodes.get_by_uid(GlobalId::node(&id)?).await? {
+            Some(node) => Ok(nodes.delete(node.id).await?),
+            None => Ok(false),
+        }
     }
 }
@@ -0,0 +1,239 @@
+//! Automatic persisted queries and the operation allow-list.
+//!
+//! A client may send `extensions.persistedQuery.sha256Hash` instead of the
+//! query text. Unknown hashes answer `PersistedQueryNotFound`, after which the
+//! client sends the text along with the hash and the server remembers it.
+//!
+//! Operations registered in the `persisted_operations` table or in an Apollo
+//! persisted query manifest are always known by their hash. In strict mode
+//! they are the only operations executed at all, and nothing else is
+//! remembered.
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use async_graphql::{
+    Request, ServerError, ServerResult, Value,
+    extensions::{
+        Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest,
+        apollo_persisted_queries::{CacheStorage, LruCacheStorage},
+    },
+    parser::{parse_query, types::ExecutableDocument},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// How many queries registered by clients are remembered.
+pub const CACHE_SIZE: usize = 1024;
+
+/// Operations registered ahead of time, by the sha256 hash of their text.
+#[derive(Default)]
+pub struct PersistedOperations {
+    documents: HashMap<String, ExecutableDocument>,
+    strict: bool,
+}
+
+/// An Apollo persisted query manifest.
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    operations: Vec<ManifestOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestOperation {
+    id: String,
+    name: Option<String>,
+    body: String,
+}
+
+impl PersistedOperations {
+    /// The operations of the `persisted_operations` table, if there is a
+    /// database, and of a manifest file, if given. Fails on operations that do
+    /// not parse or whose hash does not match their text.
+    pub async fn load(
+        pool: Option<&sqlx::PgPool>,
+        manifest: Option<&Path>,
+        strict: bool,
+    ) -> Result<Self, String> {
+        let mut operations = PersistedOperations {
+            documents: HashMap::new(),
+            strict,
+        };
+
+        if let Some(pool) = pool {
+            let rows = sqlx::query!(
+                r#"SELECT sha256 as "sha256!", name, query FROM persisted_operations"#
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("cannot load persisted operations: {e}"))?;
+            for row in rows {
+                operations.insert(&row.sha256, row.name.as_deref(), &row.query)?;
+            }
+        }
+
+        if let Some(path) = manifest {
+            let text =
+                std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let manifest: Manifest =
+                serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+            for operation in manifest.operations {
+                operations
+                    .insert(&operation.id, operation.name.as_deref(), &operation.body)
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+            }
+        }
+
+        Ok(operations)
+    }
+
+    fn insert(&mut self, hash: &str, name: Option<&str>, query: &str) -> Result<(), String> {
+        let operation = name.unwrap_or(hash);
+        if sha256(query) != hash {
+            return Err(format!(
+                "operation '{operation}' is registered as {hash} but its text hashes differently"
+            ));
+        }
+        let document = parse_query(query).map_err(|e| format!("operation '{operation}': {e}"))?;
+        self.documents.insert(hash.to_string(), document);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+}
+
+fn sha256(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+/// The schema extension serving persisted queries, shared by the GraphQL
+/// endpoints.
+#[derive(Clone)]
+pub struct PersistedQueries {
+    operations: Arc<PersistedOperations>,
+    cache: LruCacheStorage,
+}
+
+impl PersistedQueries {
+    pub fn new(operations: PersistedOperations) -> Self {
+        PersistedQueries {
+            operations: Arc::new(operations),
+            cache: LruCacheStorage::new(CACHE_SIZE),
+        }
+    }
+}
+
+impl Default for PersistedQueries {
+    fn default() -> Self {
+        PersistedQueries::new(PersistedOperations::default())
+    }
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(self.clone())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedQuery {
+    version: i32,
+    sha256_hash: String,
+}
+
+fn error(code: &str, message: impl Into<String>) -> ServerError {
+    let mut error = ServerError::new(message, None);
+    error
+        .extensions
+        .get_or_insert_with(Default::default)
+        .set("code", Value::from(code));
+    error
+}
+
+fn not_allowed() -> ServerError {
+    error(
+        "OPERATION_NOT_ALLOWED",
+        "only persisted operations are executed",
+    )
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for PersistedQueries {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let strict = self.operations.strict;
+        let Some(extension) = request.extensions.remove("persistedQuery") else {
+            if strict
+                && !self
+                    .operations
+                    .documents
+                    .contains_key(&sha256(&request.query))
+            {
+                return Err(not_allowed());
+            }
+            return next.run(ctx, request).await;
+        };
+
+        let persisted: PersistedQuery = async_graphql::from_value(extension).map_err(|_| {
+            error(
+                "BAD_USER_INPUT",
+                "invalid \"persistedQuery\" extension, expected version and sha256Hash",
+            )
+        })?;
+        if persisted.version != 1 {
+            return Err(error(
+                "PERSISTED_QUERY_NOT_SUPPORTED",
+                format!(
+                    "only version 1 of persisted queries is supported, not {}",
+                    persisted.version
+                ),
+            ));
+        }
+        let hash = persisted.sha256_hash;
+
+        let document =
+            if request.query.is_empty() {
+                let registered = self.operations.documents.get(&hash).cloned();
+                match registered {
+                    Some(document) => document,
+                    None if strict => return Err(not_allowed()),
+                    None => self.cache.get(hash).await.ok_or_else(|| {
+                        error("PERSISTED_QUERY_NOT_FOUND", "PersistedQueryNotFound")
+                    })?,
+                }
+            } else {
+                if sha256(&request.query) != hash {
+                    return Err(error(
+                        "BAD_USER_INPUT",
+                        "the sha256Hash does not match the query",
+                    ));
+                }
+                if let Some(document) = self.operations.documents.get(&hash) {
+                    document.clone()
+                } else if strict {
+                    return Err(not_allowed());
+                } else {
+                    let document = parse_query(&request.query)?;
+                    self.cache.set(hash, document.clone()).await;
+                    document
+                }
+            };
+
+        request.set_parsed_query(document);
+        next.run(ctx, request).await
+    }
+}
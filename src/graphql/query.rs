@@ -0,0 +1,58 @@
+use async_graphql::ErrorExtensions;
+
+use crate::{
+    error::Error,
+    query::{self, Bindings, QueryError},
+    storage::SharedStorage,
+};
+
+/// The cost of `graphQuery`, which may walk large parts of the graph.
+const GRAPH_QUERY_COST: usize = 100;
+
+#[derive(Default)]
+pub struct GraphQuery;
+
+#[async_graphql::Object]
+impl GraphQuery {
+    /// Run a pattern query such as
+    /// `MATCH (f:Food)-[:has-ingredient]->(i) WHERE i.name = $name RETURN f.name`.
+    /// A query matches at most six node and six relationship patterns and is
+    /// cancelled after five seconds. Only the Postgres storage runs queries.
+    #[graphql(complexity = "GRAPH_QUERY_COST")]
+    async fn graph_query(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        text: String,
+        params: Option<serde_json::Value>,
+    ) -> Result<Bindings, async_graphql::Error> {
+        let storage = ctx.data::<SharedStorage>()?;
+        let params = match params {
+            None | Some(serde_json::Value::Null) => serde_json::Map::new(),
+            Some(serde_json::Value::Object(params)) => params,
+            Some(_) => {
+                return Err(
+                    Error::ValidationFailed("params must be a JSON object".to_string()).into(),
+                );
+            }
+        };
+
+        query::run(storage.as_ref(), &text, &params)
+            .await
+            .map_err(|e| match e {
+                QueryError::Parse {
+                    ref message,
+                    offset,
+                    line,
+                    column,
+                } => async_graphql::Error::new(format!("{line}:{column}: {message}")).extend_with(
+                    |_, extensions| {
+                        extensions.set("code", "INVALID_QUERY");
+                        extensions.set("line", line);
+                        extensions.set("column", column);
+                        extensions.set("offset", offset);
+                    },
+                ),
+                QueryError::Storage(e) => e.into(),
+            })
+    }
+}
@@ -0,0 +1,151 @@
+//! Global object identification, as the Relay server specification asks.
+//!
+//! Schemas, nodes and edges are identified by an opaque `ID`, the base64 of
+//! their type name and public id (`DbNode:0192...`), and `node(id:)` refetches
+//! any of them. The integer ids stay internal to the storage.
+
+use async_graphql::ID;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    model::{DbEdge, DbNode, DbSchema},
+    repository::{EdgeRepository, NodeRepository, SchemaRepository},
+};
+
+/// What a global `ID` identifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalId {
+    Schema(Uuid),
+    Node(Uuid),
+    Edge(Uuid),
+}
+
+impl GlobalId {
+    fn type_name(&self) -> &'static str {
+        match self {
+            GlobalId::Schema(_) => "DbSchema",
+            GlobalId::Node(_) => "DbNode",
+            GlobalId::Edge(_) => "DbEdge",
+        }
+    }
+
+    pub fn uid(&self) -> Uuid {
+        match self {
+            GlobalId::Schema(uid) | GlobalId::Node(uid) | GlobalId::Edge(uid) => *uid,
+        }
+    }
+
+    /// The public id of the node `id` identifies.
+    pub fn node(id: &ID) -> Result<Uuid, Error> {
+        match GlobalId::try_from(id)? {
+            GlobalId::Node(uid) => Ok(uid),
+            other => Err(Error::ValidationFailed(format!(
+                "'{}' is the id of a {}, not of a node",
+                id.as_str(),
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// The public id of the edge `id` identifies.
+    pub fn edge(id: &ID) -> Result<Uuid, Error> {
+        match GlobalId::try_from(id)? {
+            GlobalId::Edge(uid) => Ok(uid),
+            other => Err(Error::ValidationFailed(format!(
+                "'{}' is the id of a {}, not of an edge",
+                id.as_str(),
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+impl From<GlobalId> for ID {
+    fn from(id: GlobalId) -> Self {
+        ID(BASE64_STANDARD.encode(format!("{}:{}", id.type_name(), id.uid())))
+    }
+}
+
+impl TryFrom<&ID> for GlobalId {
+    type Error = Error;
+
+    fn try_from(id: &ID) -> Result<Self, Error> {
+        let invalid = || Error::ValidationFailed(format!("'{}' is not a valid id", id.as_str()));
+        let decoded = BASE64_STANDARD.decode(id.as_str()).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (type_name, uid) = decoded.split_once(':').ok_or_else(invalid)?;
+        let uid = Uuid::parse_str(uid).map_err(|_| invalid())?;
+        match type_name {
+            "DbSchema" => Ok(GlobalId::Schema(uid)),
+            "DbNode" => Ok(GlobalId::Node(uid)),
+            "DbEdge" => Ok(GlobalId::Edge(uid)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// An object with a global `ID`.
+#[derive(async_graphql::Interface)]
+#[graphql(name = "Node", field(name = "id", ty = "ID"))]
+pub enum NodeInterface {
+    Schema(DbSchema),
+    Node(DbNode),
+    Edge(DbEdge),
+}
+
+/// The internal ids of the nodes `ids` identify, in the same order.
+pub async fn node_ids(
+    ctx: &async_graphql::Context<'_>,
+    ids: &[ID],
+) -> Result<Vec<i32>, async_graphql::Error> {
+    let uids = ids
+        .iter()
+        .map(GlobalId::node)
+        .collect::<Result<Vec<_>, _>>()?;
+    let nodes = ctx.data::<NodeRepository>()?.get_many_by_uid(&uids).await?;
+    let node_ids = uids
+        .iter()
+        .zip(ids)
+        .map(|(uid, id)| {
+            nodes
+                .iter()
+                .find(|node| node.uid == *uid)
+                .map(|node| node.id)
+                .ok_or_else(|| Error::NotFound(format!("node '{}' does not exist", id.as_str())))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(node_ids)
+}
+
+#[derive(Default)]
+pub struct RelayQuery;
+
+#[async_graphql::Object]
+impl RelayQuery {
+    /// Refetch the schema, node or edge with the given id.
+    async fn node(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> Result<Option<NodeInterface>, async_graphql::Error> {
+        Ok(match GlobalId::try_from(&id)? {
+            GlobalId::Schema(uid) => ctx
+                .data::<SchemaRepository>()?
+                .get_by_uid(uid)
+                .await?
+                .map(NodeInterface::Schema),
+            GlobalId::Node(uid) => ctx
+                .data::<NodeRepository>()?
+                .get_by_uid(uid)
+                .await?
+                .map(NodeInterface::Node),
+            GlobalId::Edge(uid) => ctx
+                .data::<EdgeRepository>()?
+                .get_by_uid(uid)
+                .await?
+                .map(NodeInterface::Edge),
+        })
+    }
+}
@@ -1,28 +1,26 @@
-use crate::model::DbSchema;
+use super::limits::list_cost;
+use crate::{
+    model::DbSchema,
+    repository::{NewSchema, SchemaRepository},
+};
 
 #[derive(Default)]
 pub struct Schema;
 
 #[async_graphql::Object]
 impl Schema {
+    /// Schemas, newest first.
+    #[graphql(complexity = "list_cost(limit, child_complexity)")]
     async fn schemas(
         &self,
         ctx: &async_graphql::Context<'_>,
+        limit: Option<i64>,
+        #[graphql(default = 0)] offset: i64,
     ) -> Result<Vec<DbSchema>, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let schemas = sqlx::query_as!(
-            DbSchema,
-            r#"
-            SELECT id, title, schema_json as "schema_json: serde_json::Value", created_at, updated_at
-            FROM schemas
-            ORDER BY created_at DESC
-            "#
-        )
-        .fetch_all(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
-
-        Ok(schemas)
+        Ok(ctx
+            .data::<SchemaRepository>()?
+            .list(super::page(limit, offset)?)
+            .await?)
     }
 
     async fn schema(
@@ -30,21 +28,20 @@ impl Schema {
         ctx: &async_graphql::Context<'_>,
         title: String,
     ) -> Result<Option<DbSchema>, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let schema = sqlx::query_as!(
-            DbSchema,
-            r#"
-            SELECT id, title, schema_json as "schema_json: serde_json::Value", created_at, updated_at
-            FROM schemas
-            WHERE title = $1
-            "#,
-            title
-        )
-        .fetch_optional(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+        Ok(ctx.data::<SchemaRepository>()?.get(&title).await?)
+    }
 
-        Ok(schema)
+    /// Whether `schemaTitle` is `ancestor` or extends it, directly or indirectly.
+    async fn is_a(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        schema_title: String,
+        ancestor: String,
+    ) -> Result<bool, async_graphql::Error> {
+        Ok(ctx
+            .data::<SchemaRepository>()?
+            .is_a(&schema_title, &ancestor)
+            .await?)
     }
 }
 
@@ -59,22 +56,8 @@ impl SchemaMutation {
         title: String,
         schema_json: serde_json::Value,
     ) -> Result<DbSchema, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let schema = sqlx::query_as!(
-            DbSchema,
-            r#"
-            INSERT INTO schemas (title, schema_json)
-            VALUES ($1, $2)
-            RETURNING id, title, schema_json as "schema_json: serde_json::Value", created_at, updated_at
-            "#,
-            title,
-            schema_json
-        )
-        .fetch_one(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
-
-        Ok(schema)
+        let schema = NewSchema { title, schema_json };
+        Ok(ctx.data::<SchemaRepository>()?.create(schema).await?)
     }
 
     async fn delete_schema(
@@ -82,18 +65,6 @@ impl SchemaMutation {
         ctx: &async_graphql::Context<'_>,
         title: String,
     ) -> Result<bool, async_graphql::Error> {
-        let pool = ctx.data::<sqlx::PgPool>()?;
-        let result = sqlx::query!(
-            r#"
-            DELETE FROM schemas
-            WHERE title = $1
-            "#,
-            title
-        )
-        .execute(pool)
-        .await
-        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
-
-        Ok(result.rows_affected() > 0)
+        Ok(ctx.data::<SchemaRepository>()?.delete(&title).await?)
     }
 }
@@ -0,0 +1,553 @@
+//! GraphQL types generated from the stored schemas, served at `/graphql/typed`.
+//!
+//! Every schema becomes an object type with `id`, `name` and one field per
+//! top-level property, inherited ones included, plus an input type and
+//! operations named after it:
+//!
+//! ```graphql
+//! type Ingredient { id: ID! name: String! kcal: Int data: JSON! ... }
+//! input IngredientInput { name: String! kcal: Int }
+//!
+//! type Query { ingredients: [Ingredient!]! ingredient(name: String!): Ingredient }
+//! type Mutation { createIngredient(input: IngredientInput!): Ingredient! }
+//! ```
+//!
+//! The GraphQL schema is rebuilt on the next request after a schema is
+//! created, changed or deleted.
+
+use std::{collections::HashSet, sync::Arc};
+
+use async_graphql::{
+    ID,
+    dynamic::{
+        Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext, Scalar,
+        Schema, TypeRef,
+    },
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{Extension, extract::State};
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+
+use super::{GlobalId, PersistedQueries, QueryLimits};
+use crate::{
+    catalog::SchemaCatalog,
+    error::Error,
+    model::DbNode,
+    repository::{NewNode, NodeFilter, NodeOrder, NodeRepository},
+    storage::SharedStorage,
+};
+
+const JSON: &str = "JSON";
+/// Type names the generated types must not take.
+const RESERVED: [&str; 8] = [
+    "Query", "Mutation", JSON, "Int", "Float", "String", "Boolean", "ID",
+];
+/// Fields every generated object has, properties cannot shadow them.
+const NODE_FIELDS: [&str; 5] = ["id", "name", "data", "createdAt", "updatedAt"];
+
+/// The generated schema with the `schema_version` it was built for.
+#[derive(Clone, Default)]
+pub struct TypedSchema {
+    current: Arc<RwLock<Option<(i64, Schema)>>>,
+    persisted: PersistedQueries,
+    limits: QueryLimits,
+}
+
+impl TypedSchema {
+    pub fn new(persisted: PersistedQueries, limits: QueryLimits) -> Self {
+        TypedSchema {
+            current: Arc::default(),
+            persisted,
+            limits,
+        }
+    }
+
+    async fn schema(&self, storage: &SharedStorage) -> Result<Schema, Error> {
+        let version = storage.version().await?.schemas;
+        if let Some((built, schema)) = &*self.current.read().await
+            && *built == version
+        {
+            return Ok(schema.clone());
+        }
+
+        let mut current = self.current.write().await;
+        if let Some((built, schema)) = &*current
+            && *built == version
+        {
+            return Ok(schema.clone());
+        }
+        let catalog = storage.catalog().await?;
+        let schema = build(
+            &catalog,
+            storage.clone(),
+            self.persisted.clone(),
+            self.limits,
+        )?;
+        *current = Some((version, schema.clone()));
+        Ok(schema)
+    }
+}
+
+/// `POST /graphql/typed`
+#[utoipa::path(
+    post,
+    path = "/graphql/typed",
+    tag = "graphql",
+    request_body(content = Object, description = "A GraphQL request against the types generated from the stored schemas"),
+    responses((status = 200, description = "A GraphQL response with `data` and `errors`", body = Object))
+)]
+pub async fn typed_graphql_handler(
+    State(storage): State<SharedStorage>,
+    Extension(typed): Extension<TypedSchema>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    match typed.schema(&storage).await {
+        Ok(schema) => schema.execute(request.into_inner()).await.into(),
+        Err(e) => {
+            let error = async_graphql::Error::from(e);
+            let mut server = async_graphql::ServerError::new(error.message, None);
+            server.extensions = error.extensions;
+            async_graphql::Response::from_errors(vec![server]).into()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FieldScalar {
+    String,
+    Int,
+    Float,
+    Boolean,
+    Json,
+}
+
+impl FieldScalar {
+    fn of(schema: &Value) -> Self {
+        if schema.get("type").is_none() {
+            return FieldScalar::Json;
+        }
+        match crate::model::declared_type(Some(schema)) {
+            "string" => FieldScalar::String,
+            "integer" => FieldScalar::Int,
+            "number" => FieldScalar::Float,
+            "boolean" => FieldScalar::Boolean,
+            _ => FieldScalar::Json,
+        }
+    }
+
+    fn type_name(self) -> &'static str {
+        match self {
+            FieldScalar::String => TypeRef::STRING,
+            FieldScalar::Int => TypeRef::INT,
+            FieldScalar::Float => TypeRef::FLOAT,
+            FieldScalar::Boolean => TypeRef::BOOLEAN,
+            FieldScalar::Json => JSON,
+        }
+    }
+}
+
+/// A property of `data` exposed as a field.
+#[derive(Debug, Clone)]
+struct Property {
+    field: String,
+    key: String,
+    scalar: FieldScalar,
+    list: bool,
+    required: bool,
+}
+
+impl Property {
+    fn type_ref(&self) -> TypeRef {
+        let name = self.scalar.type_name();
+        match (self.list, self.required) {
+            (false, false) => TypeRef::named(name),
+            (false, true) => TypeRef::named_nn(name),
+            (true, false) => TypeRef::named_list(name),
+            (true, true) => TypeRef::named_list_nn(name),
+        }
+    }
+}
+
+/// One generated object type.
+#[derive(Debug)]
+struct Kind {
+    title: String,
+    type_name: String,
+    properties: Vec<Property>,
+}
+
+impl Kind {
+    fn list_field(&self) -> String {
+        lower_first(&plural(&self.type_name))
+    }
+
+    fn single_field(&self) -> String {
+        lower_first(&self.type_name)
+    }
+
+    fn input_name(&self) -> String {
+        format!("{}Input", self.type_name)
+    }
+}
+
+fn build(
+    catalog: &SchemaCatalog,
+    storage: SharedStorage,
+    persisted: PersistedQueries,
+    limits: QueryLimits,
+) -> Result<Schema, Error> {
+    let kinds = kinds(catalog);
+
+    let titles: Vec<String> = kinds.iter().map(|kind| kind.title.clone()).collect();
+    let mut query = Object::new("Query").field(
+        Field::new(
+            "typedSchemas",
+            TypeRef::named_nn_list_nn(TypeRef::STRING),
+            move |_| {
+                let titles = titles.clone();
+                FieldFuture::new(async move {
+                    Ok(Some(FieldValue::list(
+                        titles.into_iter().map(FieldValue::value),
+                    )))
+                })
+            },
+        )
+        .description("Titles of the schemas that have generated types."),
+    );
+    let mut mutation = Object::new("Mutation");
+    let mut builder = Schema::build("Query", (!kinds.is_empty()).then_some("Mutation"), None)
+        .register(Scalar::new(JSON));
+
+    for kind in &kinds {
+        builder = builder.register(object(kind)).register(input(kind));
+        query = query.field(list_field(kind)).field(single_field(kind));
+        mutation = mutation.field(create_field(kind));
+    }
+
+    builder = builder.register(query);
+    if !kinds.is_empty() {
+        builder = builder.register(mutation);
+    }
+    builder
+        .extension(persisted)
+        .extension(limits.with_list_fields(kinds.iter().map(Kind::list_field).collect()))
+        .data(NodeRepository::new(storage))
+        .finish()
+        .map_err(Error::internal)
+}
+
+/// The kinds of every schema that maps onto valid, distinct GraphQL names.
+fn kinds(catalog: &SchemaCatalog) -> Vec<Kind> {
+    let mut titles: Vec<&str> = catalog.titles().collect();
+    titles.sort_unstable();
+
+    let mut taken: HashSet<String> = HashSet::from(["typedSchemas".to_string()]);
+    let mut kinds = Vec::new();
+    for title in titles {
+        let Some(type_name) = pascal_case(title) else {
+            tracing::warn!("schema '{title}' has no GraphQL type name");
+            continue;
+        };
+        let kind = Kind {
+            title: title.to_string(),
+            type_name,
+            properties: Vec::new(),
+        };
+        // operation names must not collide with those of other kinds either
+        let names = [
+            kind.type_name.clone(),
+            kind.input_name(),
+            kind.list_field(),
+            kind.single_field(),
+        ];
+        if RESERVED.contains(&kind.type_name.as_str())
+            || names.iter().any(|name| taken.contains(name))
+        {
+            let type_name = &kind.type_name;
+            tracing::warn!("schema '{title}' maps to the unavailable type name '{type_name}'");
+            continue;
+        }
+        taken.extend(names);
+        kinds.push(Kind {
+            properties: properties(catalog, title),
+            ..kind
+        });
+    }
+    kinds
+}
+
+/// Top-level properties of `title` and its ancestors, nearest declaration first.
+fn properties(catalog: &SchemaCatalog, title: &str) -> Vec<Property> {
+    let lineage = catalog.lineage(title).unwrap_or_default();
+    let mut properties: Vec<Property> = Vec::new();
+    let mut fields: HashSet<String> = NODE_FIELDS.iter().map(|f| f.to_string()).collect();
+    for schema in lineage.iter().filter_map(|title| catalog.get(title)) {
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let Some(declared) = schema.get("properties").and_then(Value::as_object) else {
+            continue;
+        };
+        for (key, property) in declared {
+            let Some(field) = camel_case(key) else {
+                continue;
+            };
+            if !fields.insert(field.clone()) {
+                continue;
+            }
+            let (scalar, list) = match crate::model::declared_type(Some(property)) {
+                "array" if property.get("type").is_some() => (
+                    property
+                        .get("items")
+                        .map_or(FieldScalar::Json, FieldScalar::of),
+                    true,
+                ),
+                _ => (FieldScalar::of(property), false),
+            };
+            properties.push(Property {
+                field,
+                key: key.clone(),
+                scalar,
+                list,
+                required: required.contains(&key.as_str()),
+            });
+        }
+    }
+    properties
+}
+
+fn object(kind: &Kind) -> Object {
+    let node_field = |name: &str, ty: TypeRef, value: fn(&DbNode) -> async_graphql::Value| {
+        Field::new(name, ty, move |ctx| {
+            FieldFuture::new(async move {
+                let node = ctx.parent_value.try_downcast_ref::<DbNode>()?;
+                Ok(Some(FieldValue::value(value(node))))
+            })
+        })
+    };
+
+    let mut object = Object::new(&kind.type_name)
+        .description(format!("A node of the schema '{}'.", kind.title))
+        .field(node_field("id", TypeRef::named_nn(TypeRef::ID), |node| {
+            async_graphql::Value::String(ID::from(GlobalId::Node(node.uid)).0)
+        }))
+        .field(node_field(
+            "name",
+            TypeRef::named_nn(TypeRef::STRING),
+            |node| node.name.clone().into(),
+        ))
+        .field(node_field("data", TypeRef::named_nn(JSON), |node| {
+            to_graphql(&node.data)
+        }))
+        .field(node_field(
+            "createdAt",
+            TypeRef::named(TypeRef::STRING),
+            |node| {
+                node.created_at
+                    .map_or(async_graphql::Value::Null, |dt| dt.to_rfc3339().into())
+            },
+        ))
+        .field(node_field(
+            "updatedAt",
+            TypeRef::named(TypeRef::STRING),
+            |node| {
+                node.updated_at
+                    .map_or(async_graphql::Value::Null, |dt| dt.to_rfc3339().into())
+            },
+        ));
+
+    for property in &kind.properties {
+        let key = property.key.clone();
+        object = object.field(Field::new(
+            &property.field,
+            property.type_ref(),
+            move |ctx| {
+                let key = key.clone();
+                FieldFuture::new(async move {
+                    let node = ctx.parent_value.try_downcast_ref::<DbNode>()?;
+                    Ok(node
+                        .data
+                        .get(&key)
+                        .filter(|value| !value.is_null())
+                        .map(|value| FieldValue::value(to_graphql(value))))
+                })
+            },
+        ));
+    }
+    object
+}
+
+fn input(kind: &Kind) -> InputObject {
+    kind.properties.iter().fold(
+        InputObject::new(kind.input_name())
+            .field(InputValue::new("name", TypeRef::named_nn(TypeRef::STRING))),
+        |input, property| input.field(InputValue::new(&property.field, property.type_ref())),
+    )
+}
+
+fn list_field(kind: &Kind) -> Field {
+    let title = kind.title.clone();
+    Field::new(
+        kind.list_field(),
+        TypeRef::named_nn_list_nn(&kind.type_name),
+        move |ctx| {
+            let filter = NodeFilter {
+                schema_title: Some(title.clone()),
+                include_subtypes: false,
+                order: NodeOrder::Name,
+            };
+            FieldFuture::new(async move {
+                let limit = match ctx.args.get("limit") {
+                    Some(limit) => Some(limit.i64()?),
+                    None => None,
+                };
+                let offset = match ctx.args.get("offset") {
+                    Some(offset) => offset.i64()?,
+                    None => 0,
+                };
+                let nodes = ctx
+                    .data::<NodeRepository>()?
+                    .list(&filter, super::page(limit, offset)?)
+                    .await?;
+                Ok(Some(FieldValue::list(
+                    nodes.into_iter().map(FieldValue::owned_any),
+                )))
+            })
+        },
+    )
+    .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT)))
+    .argument(InputValue::new("offset", TypeRef::named(TypeRef::INT)))
+}
+
+fn single_field(kind: &Kind) -> Field {
+    let title = kind.title.clone();
+    Field::new(
+        kind.single_field(),
+        TypeRef::named(&kind.type_name),
+        move |ctx| {
+            let title = title.clone();
+            FieldFuture::new(async move {
+                let name = ctx.args.try_get("name")?.string()?;
+                let node = ctx.data::<NodeRepository>()?.find(&title, name).await?;
+                Ok(node.map(FieldValue::owned_any))
+            })
+        },
+    )
+    .argument(InputValue::new("name", TypeRef::named_nn(TypeRef::STRING)))
+}
+
+fn create_field(kind: &Kind) -> Field {
+    let title = kind.title.clone();
+    let properties = kind.properties.clone();
+    Field::new(
+        format!("create{}", kind.type_name),
+        TypeRef::named_nn(&kind.type_name),
+        move |ctx| {
+            let title = title.clone();
+            let properties = properties.clone();
+            FieldFuture::new(async move {
+                let (name, data) = input_data(&ctx, &properties)?;
+                let node = NewNode {
+                    schema_title: title,
+                    name,
+                    data,
+                };
+                let node = ctx.data::<NodeRepository>()?.create(node, None).await?.node;
+                Ok(Some(FieldValue::owned_any(node)))
+            })
+        },
+    )
+    .argument(InputValue::new(
+        "input",
+        TypeRef::named_nn(kind.input_name()),
+    ))
+}
+
+/// The node name and `data` from the `input` argument, with fields renamed
+/// back to their property keys.
+fn input_data(
+    ctx: &ResolverContext<'_>,
+    properties: &[Property],
+) -> Result<(String, Value), async_graphql::Error> {
+    let input = ctx.args.try_get("input")?.object()?;
+    let name = input.try_get("name")?.string()?.to_string();
+    let mut data = Map::new();
+    for property in properties {
+        if let Some(value) = input.get(&property.field)
+            && !value.is_null()
+        {
+            data.insert(
+                property.key.clone(),
+                value
+                    .as_value()
+                    .clone()
+                    .into_json()
+                    .map_err(|e| Error::ValidationFailed(e.to_string()))?,
+            );
+        }
+    }
+    Ok((name, Value::Object(data)))
+}
+
+fn to_graphql(value: &Value) -> async_graphql::Value {
+    async_graphql::Value::from_json(value.clone()).unwrap_or_default()
+}
+
+/// `has-ingredient` → `HasIngredient`, `None` when nothing usable is left.
+fn pascal_case(text: &str) -> Option<String> {
+    let name: String = words(text).map(upper_first).collect();
+    valid_name(name)
+}
+
+/// `total kcal` → `totalKcal`.
+fn camel_case(text: &str) -> Option<String> {
+    let mut words = words(text);
+    let first = words.next()?.to_string();
+    let name = words.fold(first, |name, word| name + &upper_first(word));
+    valid_name(name)
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+fn valid_name(name: String) -> Option<String> {
+    match name.chars().next() {
+        Some(c) if c.is_ascii_digit() => Some(format!("_{name}")),
+        Some(_) if !name.starts_with("__") => Some(name),
+        _ => None,
+    }
+}
+
+fn upper_first(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+fn lower_first(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|c| c.to_ascii_lowercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+/// A plural good enough for operation names: `ingredients`, `berries`, `dishes`.
+fn plural(word: &str) -> String {
+    if let Some(stem) = word.strip_suffix('y')
+        && !stem.ends_with(['a', 'e', 'i', 'o', 'u'])
+    {
+        return format!("{stem}ies");
+    }
+    if word.ends_with(['s', 'x', 'z']) || word.ends_with("ch") || word.ends_with("sh") {
+        return format!("{word}es");
+    }
+    format!("{word}s")
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Multipart, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::{error::Error, storage::SharedStorage};
+
+mod csv;
+mod json;
+#[cfg(test)]
+mod tests;
+
+pub use csv::{CsvEdge, CsvMapping, import_csv};
+pub use json::{EdgeEntry, GraphDocument, NodeEntry, import_graph};
+
+/// A single problem found while validating an import.
+///
+/// `pointer` is a JSON pointer into the uploaded document, `line` and
+/// `column` are 1-based positions in its source text when they are known.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportError {
+    pub pointer: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{line}:{column}: ")?,
+            (Some(line), None) => write!(f, "{line}: ")?,
+            _ => {}
+        }
+        if !self.pointer.is_empty() {
+            write!(f, "{}: ", self.pointer)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Number of rows written by a successful import.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub schemas: usize,
+    pub nodes: usize,
+    pub edges: usize,
+}
+
+#[derive(Debug)]
+pub enum ImportFailure {
+    /// The document was rejected before anything was written.
+    Invalid(Vec<ImportError>),
+    Storage(Error),
+}
+
+impl From<Error> for ImportFailure {
+    fn from(error: Error) -> Self {
+        ImportFailure::Storage(error)
+    }
+}
+
+impl std::fmt::Display for ImportFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportFailure::Invalid(errors) => {
+                write!(f, "import rejected with {} error(s)", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {error}")?;
+                }
+                Ok(())
+            }
+            ImportFailure::Storage(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportFailure {}
+
+impl IntoResponse for ImportFailure {
+    fn into_response(self) -> Response {
+        match self {
+            ImportFailure::Invalid(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({ "errors": errors })),
+            )
+                .into_response(),
+            ImportFailure::Storage(error) => error.into_response(),
+        }
+    }
+}
+
+/// The multipart form of `POST /import`.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct ImportForm {
+    /// The JSON graph document.
+    #[schema(format = Binary)]
+    file: String,
+}
+
+/// The multipart form of `POST /import/csv`.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct CsvImportForm {
+    #[schema(format = Binary)]
+    file: String,
+    /// The [`CsvMapping`] as JSON.
+    mapping: String,
+}
+
+/// `POST /import` with the JSON graph document in a multipart field named `file`.
+#[utoipa::path(
+    post,
+    path = "/import",
+    tag = "import",
+    request_body(content = ImportForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Everything was imported", body = ImportReport),
+        (status = 400, description = "The form is incomplete"),
+        (status = 422, description = "Nothing was imported", body = Vec<ImportError>),
+    )
+)]
+pub async fn import_handler(
+    State(storage): State<SharedStorage>,
+    multipart: Multipart,
+) -> Result<Json<ImportReport>, Response> {
+    let mut fields = text_fields(multipart).await?;
+    let text = fields
+        .remove("file")
+        .ok_or_else(|| bad_request("missing multipart field 'file'".to_string()))?;
+
+    import_graph(storage.as_ref(), &text)
+        .await
+        .map(Json)
+        .map_err(IntoResponse::into_response)
+}
+
+/// `POST /import/csv` with the CSV in a multipart field named `file` and the
+/// [`CsvMapping`] as JSON in a field named `mapping`.
+#[utoipa::path(
+    post,
+    path = "/import/csv",
+    tag = "import",
+    request_body(content = CsvImportForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Every row was imported", body = ImportReport),
+        (status = 400, description = "The form or the mapping is invalid"),
+        (status = 422, description = "Nothing was imported", body = Vec<ImportError>),
+    )
+)]
+pub async fn import_csv_handler(
+    State(storage): State<SharedStorage>,
+    multipart: Multipart,
+) -> Result<Json<ImportReport>, Response> {
+    let mut fields = text_fields(multipart).await?;
+    let text = fields
+        .remove("file")
+        .ok_or_else(|| bad_request("missing multipart field 'file'".to_string()))?;
+    let mapping = fields
+        .remove("mapping")
+        .ok_or_else(|| bad_request("missing multipart field 'mapping'".to_string()))?;
+    let mapping: CsvMapping =
+        serde_json::from_str(&mapping).map_err(|e| bad_request(format!("mapping: {e}")))?;
+
+    import_csv(storage.as_ref(), &text, &mapping)
+        .await
+        .map(Json)
+        .map_err(IntoResponse::into_response)
+}
+
+async fn text_fields(mut multipart: Multipart) -> Result<HashMap<String, String>, Response> {
+    let mut fields = HashMap::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| bad_request(e.to_string()))?
+    {
+        let Some(name) = field.name().map(str::to_string) else {
+            continue;
+        };
+        let text = field.text().await.map_err(|e| bad_request(e.to_string()))?;
+        fields.insert(name, text);
+    }
+    Ok(fields)
+}
+
+fn bad_request(message: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "errors": [{ "message": message }] })),
+    )
+        .into_response()
+}
@@ -0,0 +1,374 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+use super::{ImportError, ImportFailure, ImportReport};
+use crate::{
+    model::{declared_type, property_schema},
+    repository::NewNode,
+    storage::{ImportBatch, NodeKey, Storage},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// How the columns of a CSV file map onto the nodes of one schema.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvMapping {
+    pub schema_title: String,
+    /// Column holding the node name.
+    #[serde(default = "default_name_column")]
+    pub name_column: String,
+    /// Column header to JSON pointer inside `data`, e.g. `"kcal": "/kcal"`.
+    #[serde(default)]
+    pub columns: HashMap<String, String>,
+    /// Update nodes that already exist instead of rejecting their rows.
+    #[serde(default)]
+    pub upsert: bool,
+    /// Separator used to split cells mapped onto array properties.
+    #[serde(default = "default_array_separator")]
+    pub array_separator: String,
+    #[serde(default)]
+    pub edge: Option<CsvEdge>,
+}
+
+/// A column whose cells name an existing node that each row links to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvEdge {
+    pub column: String,
+    pub target_schema: String,
+    pub weight: String,
+}
+
+fn default_name_column() -> String {
+    "name".to_string()
+}
+
+fn default_array_separator() -> String {
+    ";".to_string()
+}
+
+/// Import the rows of a CSV file as nodes of `mapping.schema_title`.
+///
+/// Cells are coerced to the JSON type the schema declares for their
+/// pointer and every row is validated before anything is written.
+pub async fn import_csv(
+    storage: &dyn Storage,
+    text: &str,
+    mapping: &CsvMapping,
+) -> Result<ImportReport, ImportFailure> {
+    let mut errors = Vec::new();
+
+    let catalog = storage.catalog().await?;
+    if catalog.get(&mapping.schema_title).is_none() {
+        errors.push(row_error(
+            None,
+            "",
+            format!("unknown schema '{}'", mapping.schema_title),
+        ));
+        return Err(ImportFailure::Invalid(errors));
+    }
+    let validator = catalog.validator(&mapping.schema_title).map_err(|e| {
+        ImportFailure::Invalid(vec![ImportError {
+            pointer: String::new(),
+            line: None,
+            column: None,
+            message: format!("stored schema '{}' is invalid: {e}", mapping.schema_title),
+        }])
+    })?;
+    // inherited properties are declared by the ancestors
+    let lineage: Vec<&Value> = catalog
+        .lineage(&mapping.schema_title)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|title| catalog.get(title))
+        .collect();
+
+    let mut reader = ::csv::ReaderBuilder::new()
+        .trim(::csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            errors.push(row_error(Some(1), "", e.to_string()));
+            return Err(ImportFailure::Invalid(errors));
+        }
+    };
+    let index_of = |column: &str| headers.iter().position(|h| h == column);
+
+    let Some(name_index) = index_of(&mapping.name_column) else {
+        errors.push(row_error(
+            Some(1),
+            "",
+            format!("missing name column '{}'", mapping.name_column),
+        ));
+        return Err(ImportFailure::Invalid(errors));
+    };
+    let mut columns = Vec::with_capacity(mapping.columns.len());
+    for (column, pointer) in &mapping.columns {
+        if !pointer.starts_with('/') {
+            errors.push(row_error(
+                None,
+                "",
+                format!("column '{column}': '{pointer}' is not a JSON pointer, e.g. '/{pointer}'"),
+            ));
+            continue;
+        }
+        match index_of(column) {
+            Some(index) => {
+                let declared = lineage
+                    .iter()
+                    .find_map(|schema| property_schema(schema, pointer));
+                columns.push((column, index, pointer, declared))
+            }
+            None => errors.push(row_error(
+                Some(1),
+                pointer,
+                format!("missing column '{column}'"),
+            )),
+        }
+    }
+    let edge_index = match &mapping.edge {
+        Some(edge) => match index_of(&edge.column) {
+            Some(index) => Some(index),
+            None => {
+                errors.push(row_error(
+                    Some(1),
+                    "",
+                    format!("missing edge column '{}'", edge.column),
+                ));
+                None
+            }
+        },
+        None => None,
+    };
+    if !errors.is_empty() {
+        return Err(ImportFailure::Invalid(errors));
+    }
+
+    // (line, name, data, edge target name)
+    let mut rows: Vec<(usize, String, Value, Option<String>)> = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line() as usize);
+                errors.push(row_error(line, "", e.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line() as usize);
+        let name = record.get(name_index).unwrap_or_default().to_string();
+        if name.is_empty() {
+            errors.push(row_error(
+                Some(line),
+                "/name",
+                format!("column '{}' is empty", mapping.name_column),
+            ));
+            continue;
+        }
+
+        let mut data = Value::Object(Map::new());
+        let mut valid = true;
+        for (column, index, pointer, property) in &columns {
+            let cell = record.get(*index).unwrap_or_default();
+            if cell.is_empty() {
+                continue;
+            }
+            match coerce(cell, *property, &mapping.array_separator) {
+                Ok(value) => insert_at(&mut data, pointer, value),
+                Err(message) => {
+                    errors.push(row_error(
+                        Some(line),
+                        pointer,
+                        format!("column '{column}': {message}"),
+                    ));
+                    valid = false;
+                }
+            }
+        }
+        let data = NewNode {
+            schema_title: mapping.schema_title.clone(),
+            name: name.clone(),
+            data,
+        }
+        .with_name_in_data()
+        .data;
+        for e in validator.iter_errors(&data) {
+            errors.push(row_error(
+                Some(line),
+                e.instance_path.as_str(),
+                e.to_string(),
+            ));
+            valid = false;
+        }
+
+        let target = edge_index
+            .and_then(|index| record.get(index))
+            .filter(|cell| !cell.is_empty())
+            .map(str::to_string);
+        if valid {
+            rows.push((line, name, data, target));
+        }
+    }
+
+    // duplicates inside the file and, unless upserting, against the storage
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (line, name, _, _) in &rows {
+        match seen.get(name.as_str()) {
+            Some(first) => errors.push(row_error(
+                Some(*line),
+                "/name",
+                format!("node '{name}' already appears on line {first}"),
+            )),
+            None => {
+                seen.insert(name, *line);
+            }
+        }
+    }
+    if !mapping.upsert {
+        let keys: Vec<NodeKey> = rows
+            .iter()
+            .map(|(_, name, _, _)| (mapping.schema_title.clone(), name.clone()))
+            .collect();
+        for node in storage.find_nodes(&keys).await? {
+            errors.push(row_error(
+                Some(seen[node.name.as_str()]),
+                "/name",
+                format!("node '{}/{}' already exists", mapping.schema_title, node.name),
+            ));
+        }
+    }
+
+    if let Some(edge) = &mapping.edge {
+        let wanted: Vec<NodeKey> = rows
+            .iter()
+            .filter_map(|row| row.3.clone())
+            .map(|target| (edge.target_schema.clone(), target))
+            .collect();
+        let targets: HashSet<String> = storage
+            .find_nodes(&wanted)
+            .await?
+            .into_iter()
+            .map(|node| node.name)
+            .collect();
+        for (line, _, _, target) in &rows {
+            if let Some(target) = target
+                && !targets.contains(target)
+            {
+                errors.push(row_error(
+                    Some(*line),
+                    "",
+                    format!(
+                        "column '{}': unknown node '{}/{target}'",
+                        edge.column, edge.target_schema
+                    ),
+                ));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        errors.sort_by_key(|e| e.line);
+        return Err(ImportFailure::Invalid(errors));
+    }
+
+    // another writer may have created one of the nodes since the check
+    // above, which then fails the whole batch unless upserting
+    let mut batch = ImportBatch {
+        upsert: mapping.upsert,
+        ..ImportBatch::default()
+    };
+    for (_, name, data, target) in rows {
+        if let (Some(edge), Some(target)) = (&mapping.edge, target) {
+            batch.edges.push((
+                (mapping.schema_title.clone(), name.clone()),
+                (edge.target_schema.clone(), target),
+                edge.weight.clone(),
+            ));
+        }
+        batch.nodes.push(NewNode {
+            schema_title: mapping.schema_title.clone(),
+            name,
+            data,
+        });
+    }
+    Ok(storage.import(batch).await?)
+}
+
+fn row_error(line: Option<usize>, pointer: &str, message: String) -> ImportError {
+    ImportError {
+        pointer: pointer.to_string(),
+        line,
+        column: None,
+        message,
+    }
+}
+
+fn coerce(cell: &str, property: Option<&Value>, separator: &str) -> Result<Value, String> {
+    match declared_type(property) {
+        "integer" => cell
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| format!("'{cell}' is not an integer")),
+        "number" => cell
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("'{cell}' is not a number")),
+        "boolean" => match cell.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" => Ok(Value::Bool(true)),
+            "false" | "no" | "n" | "0" => Ok(Value::Bool(false)),
+            _ => Err(format!("'{cell}' is not a boolean")),
+        },
+        "array" => {
+            let items = property.and_then(|p| p.get("items"));
+            cell.split(separator)
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| coerce(item, items, separator))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array)
+        }
+        "object" => match serde_json::from_str(cell) {
+            Ok(object @ Value::Object(_)) => Ok(object),
+            Ok(_) => Err(format!("'{cell}' is not a JSON object")),
+            Err(e) => Err(format!("invalid JSON object: {e}")),
+        },
+        _ => Ok(Value::String(cell.to_string())),
+    }
+}
+
+/// Set `value` at `pointer`, creating intermediate objects on the way.
+fn insert_at(data: &mut Value, pointer: &str, value: Value) {
+    let segments: Vec<String> = pointer
+        .split('/')
+        .skip(1)
+        .map(|s| s.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    let Some((last, parents)) = segments.split_last() else {
+        *data = value;
+        return;
+    };
+    let mut target = data;
+    for segment in parents {
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        target = target
+            .as_object_mut()
+            .expect("target was just made an object")
+            .entry(segment.as_str())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Some(object) = target.as_object_mut() {
+        object.insert(last.clone(), value);
+    }
+}
@@ -0,0 +1,82 @@
+use serde_json::{Value, json};
+
+use super::{coerce, insert_at};
+
+#[test]
+fn insert_at_creates_intermediate_objects() {
+    let mut data = json!({});
+    insert_at(&mut data, "/kcal", json!(52));
+    insert_at(&mut data, "/nutrients/sugar", json!(10.4));
+    insert_at(&mut data, "/nutrients/fibre", json!(2.4));
+    insert_at(&mut data, "/a~1b/c~0d", json!("escaped"));
+    assert_eq!(
+        data,
+        json!({
+            "kcal": 52,
+            "nutrients": { "sugar": 10.4, "fibre": 2.4 },
+            "a/b": { "c~d": "escaped" }
+        })
+    );
+
+    // a scalar in the way is replaced by an object
+    insert_at(&mut data, "/kcal/per100g", json!(52));
+    assert_eq!(data["kcal"], json!({ "per100g": 52 }));
+}
+
+#[test]
+fn coerce_to_the_declared_type() {
+    let cases = [
+        (json!({ "type": "integer" }), "42", Ok(json!(42))),
+        (
+            json!({ "type": "integer" }),
+            "4.2",
+            Err("'4.2' is not an integer"),
+        ),
+        (json!({ "type": "number" }), "4.2", Ok(json!(4.2))),
+        (
+            json!({ "type": "number" }),
+            "NaN",
+            Err("'NaN' is not a number"),
+        ),
+        (json!({ "type": "boolean" }), "Yes", Ok(json!(true))),
+        (json!({ "type": "boolean" }), "0", Ok(json!(false))),
+        (
+            json!({ "type": "boolean" }),
+            "maybe",
+            Err("'maybe' is not a boolean"),
+        ),
+        (
+            json!({ "type": "array", "items": { "type": "integer" } }),
+            "1; 2;;3",
+            Ok(json!([1, 2, 3])),
+        ),
+        (
+            json!({ "type": "array", "items": { "type": "integer" } }),
+            "1;two",
+            Err("'two' is not an integer"),
+        ),
+        (
+            json!({ "type": "object" }),
+            r#"{"a": 1}"#,
+            Ok(json!({ "a": 1 })),
+        ),
+        (json!({ "type": "string" }), "042", Ok(json!("042"))),
+        (json!({}), "042", Ok(json!("042"))),
+    ];
+    for (property, cell, expected) in cases {
+        assert_eq!(
+            coerce(cell, Some(&property), ";"),
+            expected.map_err(str::to_string),
+            "{cell:?} as {property}"
+        );
+    }
+    assert_eq!(coerce("42", None, ";"), Ok(Value::from("42")));
+    assert_eq!(
+        coerce("[1]", Some(&json!({ "type": "object" })), ";"),
+        Err("'[1]' is not a JSON object".to_string())
+    );
+    assert!(
+        coerce("{", Some(&json!({ "type": "object" })), ";")
+            .is_err_and(|e| e.starts_with("invalid JSON object: "))
+    );
+}
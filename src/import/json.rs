@@ -0,0 +1,432 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+
+use super::{ImportError, ImportFailure, ImportReport};
+use crate::{
+    repository::{NewNode, NewSchema},
+    storage::{ImportBatch, NodeKey, Storage},
+};
+
+/// The native interchange format: schema documents, nodes and the edges
+/// between them, with nodes referenced as `schema/name`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GraphDocument {
+    #[serde(default)]
+    pub schemas: Vec<Value>,
+    #[serde(default)]
+    pub nodes: Vec<NodeEntry>,
+    #[serde(default)]
+    pub edges: Vec<EdgeEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeEntry {
+    pub schema: String,
+    pub name: String,
+    #[serde(default = "empty_object")]
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EdgeEntry {
+    pub source: String,
+    pub target: String,
+    pub weight: String,
+}
+
+fn empty_object() -> Value {
+    Value::Object(Map::new())
+}
+
+/// Validate a JSON graph document and write it in a single transaction.
+///
+/// Schemas and nodes are upserted by title and by `(schema, name)`, edges
+/// that already exist are left alone. Every error in the document is
+/// collected before anything is written.
+pub async fn import_graph(storage: &dyn Storage, text: &str) -> Result<ImportReport, ImportFailure> {
+    let mut checker = Checker {
+        text,
+        errors: Vec::new(),
+    };
+
+    let root: Value = match serde_json::from_str(text) {
+        Ok(root) => root,
+        Err(e) => {
+            return Err(ImportFailure::Invalid(vec![ImportError {
+                pointer: String::new(),
+                line: Some(e.line()),
+                column: Some(e.column()),
+                message: e.to_string(),
+            }]));
+        }
+    };
+    let Some(root) = root.as_object() else {
+        checker.error("", "document must be a JSON object");
+        return Err(ImportFailure::Invalid(checker.errors));
+    };
+
+    // schemas, checked together with the stored ones they may extend or refer to
+    let mut catalog = storage.catalog().await?;
+    let mut schemas: Vec<(usize, String, Value)> = Vec::new();
+    for (i, schema) in checker.entries::<Value>(root, "schemas") {
+        let pointer = format!("/schemas/{i}");
+        if let Err(e) = jsonschema::meta::validate(&schema) {
+            checker.error(
+                &format!("{pointer}{}", e.instance_path.as_str()),
+                &e.to_string(),
+            );
+            continue;
+        }
+        let Some(title) = schema.get("title").and_then(Value::as_str) else {
+            checker.error(&pointer, "schema must contain a string field 'title'");
+            continue;
+        };
+        if schemas.iter().any(|(_, defined, _)| defined == title) {
+            checker.error(
+                &format!("{pointer}/title"),
+                &format!("schema '{title}' is defined more than once"),
+            );
+            continue;
+        }
+        schemas.push((i, title.to_string(), schema.clone()));
+    }
+    for (_, title, schema) in &schemas {
+        catalog.insert(title, schema.clone());
+    }
+    let mut validators: HashMap<String, jsonschema::Validator> = HashMap::new();
+    for (i, title, _) in &schemas {
+        match catalog.validator(title) {
+            Ok(validator) => {
+                validators.insert(title.clone(), validator);
+            }
+            Err(e) => checker.error(&format!("/schemas/{i}"), &e),
+        }
+    }
+    // parents are written before the schemas extending them
+    schemas.sort_by_key(|(_, title, _)| catalog.lineage(title).map_or(0, |l| l.len()));
+
+    // stored schemas that nodes use but the document does not define
+    let nodes = checker.entries::<NodeEntry>(root, "nodes");
+    for (_, node) in &nodes {
+        if validators.contains_key(&node.schema) || catalog.get(&node.schema).is_none() {
+            continue;
+        }
+        match catalog.validator(&node.schema) {
+            Ok(validator) => {
+                validators.insert(node.schema.clone(), validator);
+            }
+            Err(e) => checker.error(
+                "",
+                &format!("stored schema '{}' is invalid: {e}", node.schema),
+            ),
+        }
+    }
+
+    // nodes
+    let mut node_keys: HashSet<NodeKey> = HashSet::new();
+    let mut node_rows: Vec<NewNode> = Vec::new();
+    for (i, node) in nodes {
+        let pointer = format!("/nodes/{i}");
+        let Some(validator) = validators.get(&node.schema) else {
+            checker.error(
+                &format!("{pointer}/schema"),
+                &format!("unknown schema '{}'", node.schema),
+            );
+            continue;
+        };
+        if !node_keys.insert((node.schema.clone(), node.name.clone())) {
+            checker.error(
+                &format!("{pointer}/name"),
+                &format!(
+                    "node '{}/{}' is defined more than once",
+                    node.schema, node.name
+                ),
+            );
+            continue;
+        }
+        if !node.data.is_object() {
+            checker.error(
+                &format!("{pointer}/data"),
+                "node data must be a JSON object",
+            );
+            continue;
+        }
+        let node = NewNode {
+            schema_title: node.schema,
+            name: node.name,
+            data: node.data,
+        }
+        .with_name_in_data();
+        let mut valid = true;
+        for e in validator.iter_errors(&node.data) {
+            checker.error(
+                &format!("{pointer}/data{}", e.instance_path.as_str()),
+                &e.to_string(),
+            );
+            valid = false;
+        }
+        if valid {
+            node_rows.push(node);
+        }
+    }
+
+    // edges, resolving endpoints against the document and the storage
+    let edges = checker.entries::<EdgeEntry>(root, "edges");
+    let mut external: HashSet<NodeKey> = HashSet::new();
+    for (_, edge) in &edges {
+        for reference in [&edge.source, &edge.target] {
+            if let Some(key) = split_reference(reference)
+                && !node_keys.contains(&key)
+            {
+                external.insert(key);
+            }
+        }
+    }
+    let external: Vec<NodeKey> = external.into_iter().collect();
+    let existing: HashSet<NodeKey> = storage
+        .find_nodes(&external)
+        .await?
+        .into_iter()
+        .map(|node| (node.schema_title, node.name))
+        .collect();
+
+    let mut edge_rows: Vec<(NodeKey, NodeKey, String)> = Vec::new();
+    for (i, edge) in edges {
+        let mut endpoints = Vec::with_capacity(2);
+        for (field, reference) in [("source", &edge.source), ("target", &edge.target)] {
+            let pointer = format!("/edges/{i}/{field}");
+            match split_reference(reference) {
+                None => checker.error(
+                    &pointer,
+                    &format!("'{reference}' is not a 'schema/name' reference"),
+                ),
+                Some(key) if node_keys.contains(&key) || existing.contains(&key) => {
+                    endpoints.push(key)
+                }
+                Some(_) => checker.error(&pointer, &format!("unknown node '{reference}'")),
+            }
+        }
+        if let Ok([source, target]) = <[NodeKey; 2]>::try_from(endpoints) {
+            edge_rows.push((source, target, edge.weight));
+        }
+    }
+
+    if !checker.errors.is_empty() {
+        let mut errors = checker.errors;
+        errors.sort_by_key(|e| (e.line, e.column));
+        return Err(ImportFailure::Invalid(errors));
+    }
+
+    let batch = ImportBatch {
+        schemas: schemas
+            .into_iter()
+            .map(|(_, title, schema_json)| NewSchema { title, schema_json })
+            .collect(),
+        nodes: node_rows,
+        upsert: true,
+        edges: edge_rows,
+    };
+    Ok(storage.import(batch).await?)
+}
+
+/// Split a `schema/name` node reference at its first slash.
+fn split_reference(reference: &str) -> Option<NodeKey> {
+    let (schema, name) = reference.split_once('/')?;
+    if schema.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some((schema.to_string(), name.to_string()))
+}
+
+struct Checker<'a> {
+    text: &'a str,
+    errors: Vec<ImportError>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, pointer: &str, message: &str) {
+        let position = locate(self.text, pointer);
+        self.errors.push(ImportError {
+            pointer: pointer.to_string(),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            message: message.to_string(),
+        });
+    }
+
+    /// Deserialize every element of the top-level array `key`, recording
+    /// the ones that do not fit `T`.
+    fn entries<T: DeserializeOwned>(
+        &mut self,
+        root: &Map<String, Value>,
+        key: &str,
+    ) -> Vec<(usize, T)> {
+        let Some(value) = root.get(key) else {
+            return Vec::new();
+        };
+        let Some(array) = value.as_array() else {
+            self.error(&format!("/{key}"), &format!("'{key}' must be an array"));
+            return Vec::new();
+        };
+        let mut entries = Vec::with_capacity(array.len());
+        for (i, entry) in array.iter().enumerate() {
+            match T::deserialize(entry) {
+                Ok(entry) => entries.push((i, entry)),
+                Err(e) => self.error(&format!("/{key}/{i}"), &e.to_string()),
+            }
+        }
+        entries
+    }
+}
+
+/// Find the 1-based line and column where the value at `pointer` starts.
+///
+/// `serde_json` does not keep spans, so this walks the source text with a
+/// minimal scanner. It assumes the text already parsed successfully.
+fn locate(text: &str, pointer: &str) -> Option<(usize, usize)> {
+    let segments: Vec<String> = pointer
+        .split('/')
+        .skip(1)
+        .map(|s| s.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    // fall back to the closest enclosing value for keys that are missing
+    let offset = (0..=segments.len()).rev().find_map(|depth| {
+        Scanner {
+            bytes: text.as_bytes(),
+            pos: 0,
+        }
+        .find(&segments[..depth])
+    })?;
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    Some((line, column))
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Return the offset of the value addressed by `segments`, starting at
+    /// the value under the cursor.
+    fn find(&mut self, segments: &[String]) -> Option<usize> {
+        let start = {
+            self.skip_whitespace();
+            self.pos
+        };
+        let Some((segment, rest)) = segments.split_first() else {
+            return Some(start);
+        };
+        match self.peek()? {
+            b'{' => {
+                self.pos += 1;
+                while self.peek()? != b'}' {
+                    let key = self.string()?;
+                    self.peek()?;
+                    self.pos += 1; // ':'
+                    if &key == segment {
+                        return self.find(rest);
+                    }
+                    self.skip_value()?;
+                    if self.peek()? == b',' {
+                        self.pos += 1;
+                    }
+                }
+                None
+            }
+            b'[' => {
+                let index: usize = segment.parse().ok()?;
+                self.pos += 1;
+                let mut i = 0;
+                while self.peek()? != b']' {
+                    if i == index {
+                        return self.find(rest);
+                    }
+                    self.skip_value()?;
+                    if self.peek()? == b',' {
+                        self.pos += 1;
+                    }
+                    i += 1;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let start = self.pos;
+        self.skip_string()?;
+        serde_json::from_slice(&self.bytes[start..self.pos]).ok()
+    }
+
+    fn skip_string(&mut self) -> Option<()> {
+        self.pos += 1; // opening quote
+        loop {
+            match self.bytes.get(self.pos)? {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'"' => self.skip_string(),
+            b'{' | b'[' => {
+                let mut depth = 0usize;
+                loop {
+                    match self.bytes.get(self.pos)? {
+                        b'"' => {
+                            self.skip_string()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.pos += 1;
+                                return Some(());
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+            }
+            _ => {
+                while self
+                    .bytes
+                    .get(self.pos)
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    self.pos += 1;
+                }
+                Some(())
+            }
+        }
+    }
+}
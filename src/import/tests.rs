@@ -0,0 +1,161 @@
+use serde_json::{Value, json};
+
+use super::{CsvEdge, CsvMapping, ImportFailure, import_csv, import_graph};
+use crate::{
+    error::Error,
+    repository::{NewNode, NodeFilter, Page},
+    storage::{ImportBatch, MemoryStorage, Storage},
+};
+
+fn document() -> Value {
+    json!({
+        "schemas": [
+            { "title": "Food", "type": "object", "properties": { "kcal": { "type": "integer" } } },
+            { "title": "Ingredient", "type": "object" }
+        ],
+        "nodes": [
+            { "schema": "Food", "name": "Lasagne", "data": { "kcal": 600 } },
+            { "schema": "Ingredient", "name": "Tomatoes" }
+        ],
+        "edges": [
+            { "source": "Food/Lasagne", "target": "Ingredient/Tomatoes", "weight": "has-ingredient" }
+        ]
+    })
+}
+
+fn mapping(upsert: bool) -> CsvMapping {
+    serde_json::from_value(json!({
+        "schemaTitle": "Food",
+        "columns": { "kcal": "/kcal" },
+        "upsert": upsert,
+        "edge": { "column": "ingredient", "targetSchema": "Ingredient", "weight": "has-ingredient" }
+    }))
+    .expect("the mapping is valid")
+}
+
+fn invalid(result: Result<impl std::fmt::Debug, ImportFailure>) -> Vec<String> {
+    match result {
+        Err(ImportFailure::Invalid(errors)) => errors.iter().map(|e| e.to_string()).collect(),
+        other => panic!("the import was not rejected: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn documents_are_upserted() {
+    let storage = MemoryStorage::default();
+    let report = import_graph(&storage, &document().to_string())
+        .await
+        .expect("the document is imported");
+    assert_eq!((report.schemas, report.nodes, report.edges), (2, 2, 1));
+    let lasagne = storage
+        .find_node("Food", "Lasagne")
+        .await
+        .unwrap()
+        .expect("the node is stored");
+    assert_eq!(lasagne.data, json!({ "name": "Lasagne", "kcal": 600 }));
+
+    let mut changed = document();
+    changed["nodes"][0]["data"]["kcal"] = json!(650);
+    let report = import_graph(&storage, &changed.to_string())
+        .await
+        .expect("the document is imported again");
+    assert_eq!((report.schemas, report.nodes, report.edges), (2, 2, 0));
+    let lasagne = storage
+        .find_node("Food", "Lasagne")
+        .await
+        .unwrap()
+        .expect("the node is kept");
+    assert_eq!(lasagne.data["kcal"], 650);
+    assert_eq!(
+        storage.count_nodes(&NodeFilter::default()).await.unwrap(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn invalid_documents_write_nothing() {
+    let storage = MemoryStorage::default();
+    let mut document = document();
+    document["edges"][0]["target"] = json!("Ingredient/Basil");
+    document["nodes"][0]["data"]["kcal"] = json!("a lot");
+
+    let errors = invalid(import_graph(&storage, &document.to_string()).await);
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(
+        errors[0].contains("unknown node 'Ingredient/Basil'"),
+        "{errors:?}"
+    );
+    assert!(errors[1].contains("/nodes/0/data/kcal"), "{errors:?}");
+    assert_eq!(storage.count_schemas().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn csv_rows_link_to_stored_nodes() {
+    let storage = MemoryStorage::default();
+    import_graph(&storage, &document().to_string())
+        .await
+        .expect("the document is imported");
+    let csv = "name,kcal,ingredient\nPizza,800,Tomatoes\nSalad,150,\n";
+
+    let report = import_csv(&storage, csv, &mapping(false))
+        .await
+        .expect("the rows are imported");
+    assert_eq!((report.nodes, report.edges), (2, 1));
+    let pizza = storage
+        .find_node("Food", "Pizza")
+        .await
+        .unwrap()
+        .expect("the row is stored");
+    assert_eq!(pizza.data, json!({ "name": "Pizza", "kcal": 800 }));
+
+    let errors = invalid(import_csv(&storage, csv, &mapping(false)).await);
+    assert_eq!(
+        errors,
+        [
+            "2: /name: node 'Food/Pizza' already exists",
+            "3: /name: node 'Food/Salad' already exists"
+        ]
+    );
+    let report = import_csv(&storage, csv, &mapping(true))
+        .await
+        .expect("the rows are upserted");
+    assert_eq!((report.nodes, report.edges), (2, 0));
+
+    let mut unknown = mapping(true);
+    unknown.edge = Some(CsvEdge {
+        column: "ingredient".to_string(),
+        target_schema: "Food".to_string(),
+        weight: "contains".to_string(),
+    });
+    let errors = invalid(import_csv(&storage, csv, &unknown).await);
+    assert_eq!(
+        errors,
+        ["2: column 'ingredient': unknown node 'Food/Tomatoes'"]
+    );
+}
+
+#[tokio::test]
+async fn failed_batches_write_nothing() {
+    let storage = MemoryStorage::default();
+    import_graph(&storage, &document().to_string())
+        .await
+        .expect("the document is imported");
+    let node = |name: &str| NewNode {
+        schema_title: "Food".to_string(),
+        name: name.to_string(),
+        data: json!({ "name": name }),
+    };
+    let batch = ImportBatch {
+        nodes: vec![node("Pizza"), node("Lasagne")],
+        ..ImportBatch::default()
+    };
+
+    let error = storage.import(batch).await.expect_err("Lasagne exists");
+    assert!(matches!(error, Error::UniqueViolation { .. }), "{error:?}");
+    let nodes = storage
+        .list_nodes(&NodeFilter::default(), Page::default())
+        .await
+        .unwrap();
+    assert_eq!(nodes.len(), 2);
+    assert!(nodes.iter().all(|node| node.name != "Pizza"));
+}
@@ -1,4 +1,17 @@
+pub mod analytics;
+pub mod auth;
+pub mod backup;
+pub mod catalog;
+pub mod config;
 pub mod database;
+pub mod derived;
+pub mod error;
+pub mod export;
 pub mod graphql;
+pub mod import;
 mod model;
-// pub mod prelude;
+pub mod prelude;
+pub mod query;
+pub mod repository;
+pub mod rest;
+pub mod storage;
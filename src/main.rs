@@ -1,44 +1,107 @@
-use axum::{Extension, Router, routing::post}; // middleware,
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    routing::{get, post},
+}; // middleware,
+use clap::{Parser, Subcommand};
 use lixiv_backend::{
-    database::set_up_database,
-    graphql::{create_schema, graphql_handler},
+    config::{Config, ConfigArgs, LogConfig, LogFormat, StorageKind},
+    database::{set_up_database, set_up_sqlite},
+    export::{export_handler, rdf_handler},
+    graphql::{
+        PersistedOperations, PersistedQueries, TypedSchema, create_schema, graphql_handler,
+        typed_graphql_handler,
+    },
+    import::{import_csv_handler, import_handler},
+    repository::UserRepository,
+    rest::{self, ApiDoc},
+    storage::{MemoryStorage, PgStorage, SharedStorage, SqliteStorage},
 };
-use sqlx::PgPool;
 use tokio::signal;
-use tower_http::cors;
+use tower_http::{cors, timeout::TimeoutLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa::OpenApi;
 
-#[cfg(debug_assertions)]
 async fn graphql_playground() -> impl axum::response::IntoResponse {
     use async_graphql::http::{GraphQLPlaygroundConfig, playground_source};
     axum::response::Html(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
 }
 
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (the default)
+    Serve,
+    /// Print the OpenAPI document of the HTTP API
+    Openapi,
+}
+
 #[tokio::main]
 async fn main() {
-    // setup tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                format!(
-                    "{}=debug,tower_http=debug,axum::rejection=trace",
-                    env!("CARGO_CRATE_NAME")
-                )
-                .into()
-            }),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // variables from .env count as set in the environment
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+
+    if let Some(Command::Openapi) = cli.command {
+        let document = ApiDoc::openapi()
+            .to_pretty_json()
+            .expect("the OpenAPI document serializes");
+        println!("{document}");
+        return;
+    }
+
+    let config = Config::load(&cli.config).unwrap_or_else(|e| exit_with(e));
+    set_up_tracing(&config.log);
 
     // setup database connection pool
-    let database_pool = set_up_database().await;
-    let app = app(database_pool);
+    let database_pool = match config.storage {
+        StorageKind::Database if !config.database.is_sqlite() => Some(
+            set_up_database(&config.database)
+                .await
+                .unwrap_or_else(|e| exit_with(e)),
+        ),
+        StorageKind::Database | StorageKind::Memory => None,
+    };
 
-    #[cfg(debug_assertions)]
-    let app = debug_route(app);
+    let storage: SharedStorage = match (&database_pool, config.storage) {
+        (Some(database_pool), _) => Arc::new(PgStorage::new(database_pool.clone())),
+        (None, StorageKind::Database) => Arc::new(SqliteStorage::new(
+            set_up_sqlite(&config.database)
+                .await
+                .unwrap_or_else(|e| exit_with(e)),
+        )),
+        (None, StorageKind::Memory) => {
+            tracing::info!("keeping the graph in memory, it is lost on shutdown");
+            Arc::new(MemoryStorage::default())
+        }
+    };
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    let operations = PersistedOperations::load(
+        database_pool.as_ref(),
+        config.graphql.operations.as_deref(),
+        config.graphql.strict_operations,
+    )
+    .await
+    .unwrap_or_else(|e| exit_with(e));
+    if operations.is_strict() {
+        tracing::info!("only executing {} persisted operation(s)", operations.len());
+    }
+
+    let users = database_pool.map(UserRepository::new);
+    let app = app(storage, users, &config, PersistedQueries::new(operations));
+
+    let listener = tokio::net::TcpListener::bind(config.bind)
+        .await
+        .unwrap_or_else(|e| exit_with(format!("cannot listen on {}: {e}", config.bind)));
     tracing::info!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal())
@@ -46,27 +109,109 @@ async fn main() {
         .unwrap();
 }
 
-fn app(database_pool: PgPool) -> Router {
-    let schema = create_schema(database_pool.clone());
+fn exit_with(error: impl std::fmt::Display) -> ! {
+    eprintln!("{error}");
+    std::process::exit(1);
+}
 
-    let cors = cors::CorsLayer::new()
-        // allow `POST` when accessing the resource
-        .allow_methods([hyper::Method::POST])
-        .allow_headers([http::header::AUTHORIZATION, http::header::CONTENT_TYPE])
-        // allow requests from any origin
-        .allow_origin(cors::Any);
-
-    // build our application with a single route
-    Router::new()
-        .route(
-            "/graphql",
-            post(graphql_handler), //.layer(middleware::from_fn(auth)),
+fn set_up_tracing(log: &LogConfig) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| log.filter.as_str().into());
+    let registry = tracing_subscriber::registry().with(filter);
+    match log.format {
+        LogFormat::Pretty => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Compact => registry
+            .with(tracing_subscriber::fmt::layer().compact())
+            .init(),
+        LogFormat::Json => registry
+            .with(tracing_subscriber::fmt::layer().json())
+            .init(),
+    }
+}
+
+/// The HTTP API over `storage`, with the `users` of the Postgres database.
+fn app(
+    storage: SharedStorage,
+    users: Option<UserRepository>,
+    config: &Config,
+    persisted: PersistedQueries,
+) -> Router {
+    let limits = config.graphql.limits();
+    let features = &config.features;
+    let schema = create_schema(
+        storage.clone(),
+        users,
+        config.auth.clone(),
+        persisted.clone(),
+        limits,
+    );
+
+    let origins = &config.http.cors_origins;
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        cors::AllowOrigin::any()
+    } else {
+        // validated when the configuration is loaded
+        cors::AllowOrigin::list(
+            origins
+                .iter()
+                .map(|origin| origin.parse().expect("valid origin")),
         )
+    };
+    let cors = cors::CorsLayer::new()
+        .allow_methods([
+            hyper::Method::GET,
+            hyper::Method::POST,
+            hyper::Method::DELETE,
+        ])
+        .allow_headers([
+            http::header::AUTHORIZATION,
+            http::header::CONTENT_TYPE,
+            http::header::IF_NONE_MATCH,
+        ])
+        .expose_headers([
+            http::header::ETAG,
+            http::header::LINK,
+            http::header::LOCATION,
+        ])
+        .allow_origin(allow_origin);
+
+    let mut app = Router::new().route(
+        "/graphql",
+        post(graphql_handler), //.layer(middleware::from_fn(auth)),
+    );
+    if features.typed_graphql {
+        app = app.route("/graphql/typed", post(typed_graphql_handler));
+    }
+    if features.rest {
+        app = app.merge(rest::router());
+    }
+    if features.import {
+        app = app
+            .route("/import", post(import_handler))
+            .route("/import/csv", post(import_csv_handler));
+    }
+    if features.export {
+        app = app
+            .route("/export", get(export_handler))
+            .route("/export/rdf", get(rdf_handler));
+    }
+    let app = app
         .layer(Extension(schema))
+        .layer(Extension(TypedSchema::new(persisted, limits)))
         // .route("/login", post(login))
         // .route("/refresh", post(refresh))
-        .with_state(database_pool)
-        .layer(cors)
+        .with_state(storage);
+    let app = if features.playground {
+        debug_route(app)
+    } else {
+        app
+    };
+
+    app.layer(TimeoutLayer::with_status_code(
+        http::StatusCode::REQUEST_TIMEOUT,
+        config.http.request_timeout(),
+    ))
+    .layer(cors)
 }
 
 pub async fn shutdown_signal() {
@@ -89,9 +234,7 @@ pub async fn shutdown_signal() {
     }
 }
 
-#[cfg(debug_assertions)]
 fn debug_route(app: Router) -> Router {
-    use axum::routing::get;
     let debug = Router::new().route("/playground", get(graphql_playground));
 
     app.nest("/debug", debug)
@@ -1,24 +1,43 @@
 // use jsonschema::{ValidationError, paths::Location};
-// use petgraph::{
-//     graph::{EdgeIndex, NodeIndex},
-//     prelude::StableGraph,
-// };
-// use serde::{Deserialize, Serialize};
-use serde_json::{Value}; // Map,
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    stable_graph::StableDiGraph,
+    visit::EdgeRef,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use sqlx::FromRow;
+use uuid::Uuid;
 
-#[derive(Debug, FromRow)]
+use async_graphql::ID;
+
+use crate::{
+    graphql::{EdgesOf, GlobalId, GraphDataLoader},
+    repository::Direction,
+};
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct DbSchema {
     pub id: i32,
+    /// The public id, kept by backups and restores; nil in backups made
+    /// before there were public ids.
+    #[serde(default)]
+    pub uid: Uuid,
     pub title: String,
     pub schema_json: Value,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, FromRow)]
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct DbNode {
     pub id: i32,
+    /// The public id, kept by backups and restores; nil in backups made
+    /// before there were public ids.
+    #[serde(default)]
+    pub uid: Uuid,
     pub schema_title: String,
     pub name: String,
     pub data: Value,
@@ -26,19 +45,69 @@ pub struct DbNode {
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, FromRow)]
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct DbEdge {
     pub id: i32,
+    /// The public id, kept by backups and restores; nil in backups made
+    /// before there were public ids.
+    #[serde(default)]
+    pub uid: Uuid,
     pub source_node_id: i32,
     pub target_node_id: i32,
     pub weight: String,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// An account access tokens are issued to.
+#[derive(Debug, Clone, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbUser {
+    pub id: i32,
+    pub username: String,
+    /// May back up and restore the inventory over the API.
+    pub admin: bool,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A node matched by full-text search.
+#[derive(Debug)]
+pub struct DbSearchHit {
+    pub node: DbNode,
+    pub rank: f32,
+    pub snippet: String,
+}
+
+/// An existing node whose name is close to a looked up name.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct DbSimilarNode {
+    pub node: DbNode,
+    pub similarity: f32,
+}
+
+/// A created node with the existing nodes of its schema whose names are
+/// spelled alike, which may be the same thing under another name.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DbCreatedNode {
+    #[serde(flatten)]
+    pub node: DbNode,
+    /// Empty unless a duplicate threshold was given.
+    pub near_duplicates: Vec<DbSimilarNode>,
+}
+
+/// Two nodes of the same schema with similar names.
+#[derive(Debug)]
+pub struct DbDuplicateCandidate {
+    pub left: DbNode,
+    pub right: DbNode,
+    pub similarity: f32,
+}
+
 #[async_graphql::Object]
 impl DbSchema {
-    async fn id(&self) -> i32 {
-        self.id
+    pub(crate) async fn id(&self) -> ID {
+        GlobalId::Schema(self.uid).into()
     }
 
     async fn title(&self) -> &str {
@@ -49,6 +118,11 @@ impl DbSchema {
         &self.schema_json
     }
 
+    /// The title of the schema this one extends.
+    async fn extends(&self) -> Option<&str> {
+        crate::catalog::parent_title(&self.schema_json)
+    }
+
     async fn created_at(&self) -> Option<String> {
         self.created_at.map(|dt| dt.to_rfc3339())
     }
@@ -60,14 +134,24 @@ impl DbSchema {
 
 #[async_graphql::Object]
 impl DbNode {
-    async fn id(&self) -> i32 {
-        self.id
+    pub(crate) async fn id(&self) -> ID {
+        GlobalId::Node(self.uid).into()
     }
 
     async fn schema_title(&self) -> &str {
         &self.schema_title
     }
 
+    async fn schema(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<DbSchema, async_graphql::Error> {
+        ctx.data::<GraphDataLoader>()?
+            .load_one(self.schema_title.clone())
+            .await?
+            .ok_or_else(|| format!("schema '{}' does not exist", self.schema_title).into())
+    }
+
     async fn name(&self) -> &str {
         &self.name
     }
@@ -76,6 +160,40 @@ impl DbNode {
         &self.data
     }
 
+    /// The edges leaving and/or entering the node, newest first.
+    #[graphql(complexity = "crate::graphql::list_cost(limit, child_complexity)")]
+    async fn edges(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] direction: Direction,
+        limit: Option<i64>,
+        #[graphql(default = 0)] offset: i64,
+    ) -> Result<Vec<DbEdge>, async_graphql::Error> {
+        let key = EdgesOf {
+            node_id: self.id,
+            direction,
+            page: crate::graphql::page(limit, offset)?,
+        };
+        Ok(ctx
+            .data::<GraphDataLoader>()?
+            .load_one(key)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Values of the `x-derived` fields declared by the node's schema.
+    #[graphql(complexity = 10)]
+    async fn derived(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<Value, async_graphql::Error> {
+        let storage = ctx.data::<crate::storage::SharedStorage>()?;
+        let cache = ctx.data::<crate::derived::DerivedCache>()?;
+        Ok(crate::derived::evaluate(storage.as_ref(), cache, self)
+            .await
+            .map(Value::Object)?)
+    }
+
     async fn created_at(&self) -> Option<String> {
         self.created_at.map(|dt| dt.to_rfc3339())
     }
@@ -87,16 +205,36 @@ impl DbNode {
 
 #[async_graphql::Object]
 impl DbEdge {
-    async fn id(&self) -> i32 {
-        self.id
+    pub(crate) async fn id(&self) -> ID {
+        GlobalId::Edge(self.uid).into()
+    }
+
+    async fn source_node_id(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<ID, async_graphql::Error> {
+        Ok(GlobalId::Node(load_node(ctx, self.source_node_id).await?.uid).into())
     }
 
-    async fn source_node_id(&self) -> i32 {
-        self.source_node_id
+    async fn target_node_id(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<ID, async_graphql::Error> {
+        Ok(GlobalId::Node(load_node(ctx, self.target_node_id).await?.uid).into())
     }
 
-    async fn target_node_id(&self) -> i32 {
-        self.target_node_id
+    async fn source(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<DbNode, async_graphql::Error> {
+        load_node(ctx, self.source_node_id).await
+    }
+
+    async fn target(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<DbNode, async_graphql::Error> {
+        load_node(ctx, self.target_node_id).await
     }
 
     async fn weight(&self) -> &str {
@@ -108,6 +246,99 @@ impl DbEdge {
     }
 }
 
+#[async_graphql::Object]
+impl DbSearchHit {
+    async fn node(&self) -> &DbNode {
+        &self.node
+    }
+
+    async fn rank(&self) -> f32 {
+        self.rank
+    }
+
+    /// Matching fragments with the search terms wrapped in `<mark>`.
+    async fn snippet(&self) -> &str {
+        &self.snippet
+    }
+}
+
+#[async_graphql::Object]
+impl DbSimilarNode {
+    async fn node(&self) -> &DbNode {
+        &self.node
+    }
+
+    /// Trigram similarity of the lower-cased names, between 0 and 1.
+    async fn similarity(&self) -> f32 {
+        self.similarity
+    }
+}
+
+#[async_graphql::Object(name = "CreateNodePayload")]
+impl DbCreatedNode {
+    async fn node(&self) -> &DbNode {
+        &self.node
+    }
+
+    /// Existing nodes of the schema with names at least as similar as the
+    /// `duplicateThreshold`, most similar first.
+    async fn near_duplicates(&self) -> &[DbSimilarNode] {
+        &self.near_duplicates
+    }
+}
+
+#[async_graphql::Object]
+impl DbDuplicateCandidate {
+    async fn left(&self) -> &DbNode {
+        &self.left
+    }
+
+    async fn right(&self) -> &DbNode {
+        &self.right
+    }
+
+    async fn similarity(&self) -> f32 {
+        self.similarity
+    }
+}
+
+async fn load_node(
+    ctx: &async_graphql::Context<'_>,
+    id: i32,
+) -> Result<DbNode, async_graphql::Error> {
+    ctx.data::<GraphDataLoader>()?
+        .load_one(id)
+        .await?
+        .ok_or_else(|| format!("node {id} does not exist").into())
+}
+
+/// Walk `properties`/`items` of a schema down to the subschema that
+/// describes the value at `pointer`.
+pub(crate) fn property_schema<'a>(schema: &'a Value, pointer: &str) -> Option<&'a Value> {
+    pointer
+        .split('/')
+        .skip(1)
+        .try_fold(schema, |schema, segment| {
+            let segment = segment.replace("~1", "/").replace("~0", "~");
+            match schema.get("properties").and_then(|p| p.get(&segment)) {
+                Some(property) => Some(property),
+                None => segment.parse::<usize>().ok().and(schema.get("items")),
+            }
+        })
+}
+
+/// The first JSON type a subschema declares, `"string"` when there is none.
+pub(crate) fn declared_type(schema: Option<&Value>) -> &str {
+    match schema.and_then(|s| s.get("type")) {
+        Some(Value::String(t)) => t,
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|t| *t != "null")
+            .unwrap_or("string"),
+        _ => "string",
+    }
+}
 
 //type Schema = Value;
 
@@ -199,109 +430,133 @@ impl DbEdge {
 //    }
 //}
 
-///// A single node in the graph.
-///// And a schema which determines the structure
-///// of the data field
-//#[derive(Debug, Clone, Serialize)]
-//pub struct NodeInstance {
-//    schema: String,
-//    data: Value,
-//}
+/// A single node in the graph, with a schema which determines the structure
+/// of its `data`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeInstance {
+    schema: String,
+    name: String,
+    data: Value,
+}
 
-//impl NodeInstance {
-//    pub fn new(schema: impl Into<String>, name: impl Into<String>) -> Self {
-//        let mut init = Map::new();
-//        init.insert("name".into(), Value::String(name.into()));
+impl NodeInstance {
+    pub fn new(schema: impl Into<String>, name: impl Into<String>) -> Self {
+        let name = name.into();
+        let mut init = Map::new();
+        init.insert("name".into(), Value::String(name.clone()));
+
+        NodeInstance {
+            schema: schema.into(),
+            name,
+            data: Value::Object(init),
+        }
+    }
 
-//        NodeInstance {
-//            schema: schema.into(),
-//            data: Value::Object(init),
-//        }
-//    }
+    /// A node with the given `data`, which must be a JSON object.
+    pub fn with_data(
+        schema: impl Into<String>,
+        name: impl Into<String>,
+        data: Value,
+    ) -> Option<Self> {
+        data.is_object().then(|| NodeInstance {
+            schema: schema.into(),
+            name: name.into(),
+            data,
+        })
+    }
 
-//    pub fn schema(&self) -> &String {
-//        &self.schema
-//    }
+    pub fn schema(&self) -> &String {
+        &self.schema
+    }
 
-//    fn data(&self) -> &Map<String, Value> {
-//        self.data
-//            .as_object()
-//            .expect("NodeInstance invariant: data is always an object")
-//    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-//    fn data_mut(&mut self) -> &mut Map<String, Value> {
-//        self.data
-//            .as_object_mut()
-//            .expect("NodeInstance invariant: data is always an object")
-//    }
+    pub fn value(&self) -> &Value {
+        &self.data
+    }
 
-//    pub fn get(&self, field: &str) -> Option<&Value> {
-//        self.data().get(field)
-//    }
+    fn data(&self) -> &Map<String, Value> {
+        self.data
+            .as_object()
+            .expect("NodeInstance invariant: data is always an object")
+    }
 
-//    pub fn get_mut(&mut self, field: &str) -> Option<&mut Value> {
-//        self.data_mut().get_mut(field)
-//    }
+    fn data_mut(&mut self) -> &mut Map<String, Value> {
+        self.data
+            .as_object_mut()
+            .expect("NodeInstance invariant: data is always an object")
+    }
 
-//    pub fn name(&self) -> &str {
-//        self.get("name")
-//            .and_then(Value::as_str)
-//            .expect("name missing or not a string")
-//    }
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.data().get(field)
+    }
 
-//    pub fn insert(&mut self, k: impl Into<String>, v: Value) -> Option<Value> {
-//        self.data_mut().insert(k.into(), v)
-//    }
-//}
+    pub fn get_mut(&mut self, field: &str) -> Option<&mut Value> {
+        self.data_mut().get_mut(field)
+    }
 
-//impl<'de> Deserialize<'de> for NodeInstance {
-//    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-//    where
-//        D: serde::Deserializer<'de>,
-//    {
-//        #[derive(Deserialize)]
-//        struct RawNodeInstance {
-//            schema: String,
-//            data: Value,
-//        }
-
-//        let raw = RawNodeInstance::deserialize(deserializer)?;
-
-//        if !raw.data.is_object() {
-//            return Err(serde::de::Error::custom(
-//                "NodeInstance.data must be a JSON object",
-//            ));
-//        }
-//        //TODO: look up if raw.schema exists in SchemaRegistry database
-
-//        Ok(NodeInstance {
-//            schema: raw.schema,
-//            data: raw.data,
-//        })
-//    }
-//}
+    pub fn insert(&mut self, k: impl Into<String>, v: Value) -> Option<Value> {
+        self.data_mut().insert(k.into(), v)
+    }
+}
 
-//pub trait AddDedup {
-//    fn add_edge_s(&mut self, source: NodeIndex, target: NodeIndex, weight: String) -> EdgeIndex;
-//    fn add_node_s(&mut self, weight: NodeInstance) -> NodeIndex;
-//}
+impl<'de> Deserialize<'de> for NodeInstance {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawNodeInstance {
+            schema: String,
+            name: Option<String>,
+            data: Value,
+        }
+
+        let raw = RawNodeInstance::deserialize(deserializer)?;
+        // the name defaults to the `name` property of the data
+        let name = match raw.name {
+            Some(name) => name,
+            None => raw
+                .data
+                .get("name")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| serde::de::Error::missing_field("name"))?,
+        };
+
+        NodeInstance::with_data(raw.schema, name, raw.data)
+            .ok_or_else(|| serde::de::Error::custom("NodeInstance.data must be a JSON object"))
+    }
+}
 
-//impl AddDedup for StableGraph<NodeInstance, String> {
-//    fn add_edge_s(&mut self, source: NodeIndex, target: NodeIndex, weight: String) -> EdgeIndex {
-//        let edge = self.find_edge(source, target);
-//        match edge {
-//            Some(e) => e,
-//            None => self.add_edge(source, target, weight),
-//        }
-//    }
+/// Adding nodes and edges unless an equal one exists: a node of the same
+/// schema with the same name, or an edge between the same nodes with the same
+/// label.
+pub trait AddDedup {
+    fn add_edge_s(&mut self, source: NodeIndex, target: NodeIndex, weight: String) -> EdgeIndex;
+    fn add_node_s(&mut self, weight: NodeInstance) -> NodeIndex;
+}
 
-//    fn add_node_s(&mut self, weight: NodeInstance) -> NodeIndex {
-//        let node = self
-//            .node_indices()
-//            .find(|node| self[*node].name() == weight.name());
-//        match node {
-//            Some(n) => n,
-//            None => self.add_node(weight),
-//        }
-//    }
-//}
+impl AddDedup for StableDiGraph<NodeInstance, String> {
+    fn add_edge_s(&mut self, source: NodeIndex, target: NodeIndex, weight: String) -> EdgeIndex {
+        let edge = self
+            .edges_connecting(source, target)
+            .find(|edge| *edge.weight() == weight);
+        match edge {
+            Some(e) => e.id(),
+            None => self.add_edge(source, target, weight),
+        }
+    }
+
+    fn add_node_s(&mut self, weight: NodeInstance) -> NodeIndex {
+        let node = self.node_indices().find(|node| {
+            self[*node].schema() == weight.schema() && self[*node].name() == weight.name()
+        });
+        match node {
+            Some(n) => n,
+            None => self.add_node(weight),
+        }
+    }
+}
@@ -1,3 +1,38 @@
-// pub use crate::model::AddDedup;
-// pub use crate::model::NodeInstance;
+//! The domain layer, for embedding the inventory in another program:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use lixiv_backend::prelude::*;
+//!
+//! # async fn example(pool: sqlx::PgPool) -> Result<(), Error> {
+//! let repositories = Repositories::new(Arc::new(PgStorage::new(pool)));
+//! let food = NodeFilter {
+//!     schema_title: Some("Food".into()),
+//!     include_subtypes: true,
+//!     order: NodeOrder::Name,
+//! };
+//! let nodes = repositories.nodes.list(&food, Page::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`SqliteStorage`] keeps the graph in a SQLite file instead and
+//! [`MemoryStorage`] in memory, for tests and demos that should not need a
+//! database.
+
+pub use crate::error::Error;
+pub use crate::model::AddDedup;
+pub use crate::model::NodeInstance;
+pub use crate::model::{
+    DbCreatedNode, DbDuplicateCandidate, DbEdge, DbNode, DbSchema, DbSearchHit, DbSimilarNode,
+    DbUser,
+};
+pub use crate::repository::{
+    Direction, EdgeRepository, MergeNodes, MergeStrategy, NewEdge, NewNode, NewSchema, NewUser,
+    NodeFilter, NodeOrder, NodeRepository, Page, Repositories, SchemaRepository, UserRepository,
+};
 // pub use crate::model::SchemaRegistry;
+pub use crate::storage::{
+    GraphVersion, MemoryStorage, PgStorage, SharedStorage, SqliteStorage, Storage,
+};
@@ -0,0 +1,121 @@
+//! A small Cypher-like pattern language over `nodes` and `edges`.
+//!
+//! ```text
+//! MATCH (f:Food)-[:has-ingredient]->(i:Ingredient)-[:has-nutrition]->(n:Nutrition)
+//! WHERE n.kcal > $min
+//! RETURN f.name, i.name, n.kcal AS kcal
+//! ORDER BY kcal DESC
+//! LIMIT 10
+//! ```
+//!
+//! A query is tokenized and parsed into an [`ast::Query`], the planner
+//! resolves variables into table aliases and hops, and the compiler turns the
+//! plan into a single SQL statement. Variable length hops such as
+//! `-[:part-of*1..3]->` become recursive CTEs. Only the Postgres storage runs
+//! the statement.
+
+use serde_json::{Map, Value};
+
+use crate::{error::Error, storage::Storage};
+
+pub mod ast;
+mod compiler;
+mod lexer;
+mod parser;
+mod planner;
+#[cfg(test)]
+mod tests;
+
+pub use compiler::{Bind, CompiledQuery};
+
+/// Rows returned when the query has no `LIMIT`.
+pub const DEFAULT_LIMIT: u64 = 1000;
+/// Upper bound for `LIMIT` and for unbounded variable length hops.
+pub const MAX_LIMIT: u64 = 10_000;
+pub const MAX_HOPS: u32 = 10;
+/// Upper bounds for the node and relationship patterns of one query: every
+/// node pattern is another join over `nodes`, so unconnected ones multiply.
+pub const MAX_NODE_PATTERNS: usize = 6;
+pub const MAX_HOP_PATTERNS: usize = 6;
+/// How long the statement of a query may run before Postgres cancels it.
+pub const STATEMENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum QueryError {
+    /// The text is not a valid query. Positions are 1-based.
+    Parse {
+        message: String,
+        offset: usize,
+        line: usize,
+        column: usize,
+    },
+    /// The storage could not run the query, e.g. because it ran longer than
+    /// [`STATEMENT_TIMEOUT`].
+    Storage(Error),
+}
+
+impl QueryError {
+    pub(crate) fn parse(text: &str, offset: usize, message: &str) -> Self {
+        let offset = offset.min(text.len());
+        let before = &text[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        QueryError::Parse {
+            message: message.to_string(),
+            offset,
+            line,
+            column,
+        }
+    }
+}
+
+impl From<Error> for QueryError {
+    fn from(error: Error) -> Self {
+        QueryError::Storage(error)
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Parse {
+                message,
+                line,
+                column,
+                ..
+            } => write!(f, "{line}:{column}: {message}"),
+            QueryError::Storage(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Column names and one row of values per match.
+#[derive(Debug, Default, async_graphql::SimpleObject)]
+#[graphql(name = "QueryBindings")]
+pub struct Bindings {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Parse, plan and compile `text` without touching the database.
+pub fn compile(text: &str, params: &Map<String, Value>) -> Result<CompiledQuery, QueryError> {
+    let query = parser::parse(text)?;
+    let plan = planner::plan(text, &query, params)?;
+    Ok(compiler::compile(&plan))
+}
+
+pub async fn run(
+    storage: &dyn Storage,
+    text: &str,
+    params: &Map<String, Value>,
+) -> Result<Bindings, QueryError> {
+    let compiled = compile(text, params)?;
+    let rows = storage.graph_query(&compiled).await?;
+
+    Ok(Bindings {
+        columns: compiled.columns,
+        rows,
+    })
+}
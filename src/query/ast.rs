@@ -0,0 +1,122 @@
+use serde_json::Value;
+
+/// `MATCH patterns [WHERE predicate] RETURN items [ORDER BY ...] [LIMIT n]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub patterns: Vec<Pattern>,
+    pub predicate: Option<Expr>,
+    pub distinct: bool,
+    pub returns: Vec<ReturnItem>,
+    pub order: Vec<OrderItem>,
+    pub limit: Option<Spanned<u64>>,
+}
+
+/// A chain of node patterns connected by relationships.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub start: NodePattern,
+    pub hops: Vec<(RelPattern, NodePattern)>,
+}
+
+/// `(variable:Schema)`, both parts optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodePattern {
+    pub variable: Option<String>,
+    pub schema: Option<String>,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+/// `-[:label*min..max]->` or `<-[:label]-`, label and length optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelPattern {
+    pub label: Option<String>,
+    pub direction: Direction,
+    /// `None` for a single hop.
+    pub length: Option<(u32, Option<u32>)>,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Neq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    pub fn sql(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Neq => "<>",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Param {
+        name: String,
+        offset: usize,
+    },
+    Variable {
+        name: String,
+        offset: usize,
+    },
+    Property {
+        variable: String,
+        property: String,
+        offset: usize,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare {
+        op: CompareOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Contains {
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnItem {
+    pub expr: Expr,
+    pub alias: Option<String>,
+    /// The expression as written, used as column name without an alias.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderItem {
+    pub expr: Expr,
+    pub text: String,
+    pub descending: bool,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub offset: usize,
+}
@@ -0,0 +1,190 @@
+use serde_json::Value;
+
+use super::planner::{Hop, Operand, Plan};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bind {
+    Text(String),
+    Json(Value),
+}
+
+/// A single SQL statement returning one `jsonb` array per row.
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    pub sql: String,
+    pub binds: Vec<Bind>,
+    pub columns: Vec<String>,
+}
+
+pub fn compile(plan: &Plan) -> CompiledQuery {
+    let mut compiler = Compiler { binds: Vec::new() };
+    let mut from = Vec::new();
+    let mut conditions = Vec::new();
+
+    for node in &plan.nodes {
+        from.push(format!("nodes {}", node.alias));
+        if let Some(schema) = &node.schema {
+            let param = compiler.text(schema);
+            conditions.push(format!("{}.schema_title = {param}", node.alias));
+        }
+    }
+
+    for hop in &plan.hops {
+        match hop {
+            Hop::Edge {
+                alias,
+                source,
+                target,
+                label,
+            } => {
+                from.push(format!("edges {alias}"));
+                conditions.push(format!("{alias}.source_node_id = {source}.id"));
+                conditions.push(format!("{alias}.target_node_id = {target}.id"));
+                if let Some(label) = label {
+                    let param = compiler.text(label);
+                    conditions.push(format!("{alias}.weight = {param}"));
+                }
+            }
+            Hop::Path {
+                alias,
+                source,
+                target,
+                label,
+                min,
+                max,
+            } => {
+                let label = match label {
+                    Some(label) => format!(" AND e.weight = {}", compiler.text(label)),
+                    None => String::new(),
+                };
+                from.push(format!(
+                    "LATERAL (\
+                     WITH RECURSIVE walk(node_id, depth) AS (\
+                     SELECT {source}.id, 0 \
+                     UNION \
+                     SELECT e.target_node_id, w.depth + 1 \
+                     FROM edges e JOIN walk w ON e.source_node_id = w.node_id \
+                     WHERE w.depth < {max}{label}\
+                     ) \
+                     SELECT DISTINCT node_id FROM walk WHERE depth >= {min}\
+                     ) {alias}"
+                ));
+                conditions.push(format!("{alias}.node_id = {target}.id"));
+            }
+        }
+    }
+
+    if let Some(predicate) = &plan.predicate {
+        conditions.push(compiler.condition(predicate));
+    }
+
+    let columns: Vec<String> = plan
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, (_, operand))| format!("{} AS c{i}", compiler.value(operand)))
+        .collect();
+    let outputs: Vec<String> = (0..plan.columns.len()).map(|i| format!("c{i}")).collect();
+
+    let mut sql = format!(
+        "SELECT jsonb_build_array({}) FROM (SELECT {}{} FROM {} WHERE {}) q",
+        outputs.join(", "),
+        if plan.distinct { "DISTINCT " } else { "" },
+        columns.join(", "),
+        from.join(", "),
+        if conditions.is_empty() {
+            "true".to_string()
+        } else {
+            conditions.join(" AND ")
+        },
+    );
+    if !plan.order.is_empty() {
+        let order: Vec<String> = plan
+            .order
+            .iter()
+            .map(|(i, descending)| format!("c{i}{}", if *descending { " DESC" } else { "" }))
+            .collect();
+        sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+    }
+    sql.push_str(&format!(" LIMIT {}", plan.limit));
+
+    CompiledQuery {
+        sql,
+        binds: compiler.binds,
+        columns: plan.columns.iter().map(|(name, _)| name.clone()).collect(),
+    }
+}
+
+struct Compiler {
+    binds: Vec<Bind>,
+}
+
+impl Compiler {
+    fn text(&mut self, value: &str) -> String {
+        self.binds.push(Bind::Text(value.to_string()));
+        format!("${}::text", self.binds.len())
+    }
+
+    fn json(&mut self, value: &Value) -> String {
+        self.binds.push(Bind::Json(value.clone()));
+        format!("${}::jsonb", self.binds.len())
+    }
+
+    /// A `jsonb` expression.
+    fn value(&mut self, operand: &Operand) -> String {
+        match operand {
+            Operand::Literal(value) => self.json(value),
+            Operand::Node(alias) => format!(
+                "jsonb_build_object('id', {}, 'schemaTitle', {alias}.schema_title, \
+                 'name', {alias}.name, 'data', {alias}.data)",
+                global_id(alias)
+            ),
+            Operand::Property { alias, property } => match property.as_str() {
+                "id" => format!("to_jsonb({})", global_id(alias)),
+                "name" => format!("to_jsonb({alias}.name)"),
+                "schemaTitle" => format!("to_jsonb({alias}.schema_title)"),
+                _ => {
+                    let key = self.text(property);
+                    format!("({alias}.data -> {key})")
+                }
+            },
+            condition => format!("to_jsonb({})", self.condition(condition)),
+        }
+    }
+
+    /// A boolean expression. Plain values are true when they are JSON `true`.
+    fn condition(&mut self, operand: &Operand) -> String {
+        match operand {
+            Operand::Not(operand) => format!("NOT ({})", self.condition(operand)),
+            Operand::And(left, right) => {
+                format!("({} AND {})", self.condition(left), self.condition(right))
+            }
+            Operand::Or(left, right) => {
+                format!("({} OR {})", self.condition(left), self.condition(right))
+            }
+            Operand::Compare { op, left, right } => {
+                format!("({} {} {})", self.value(left), op.sql(), self.value(right))
+            }
+            Operand::Contains(left, right) => format!(
+                "(strpos({} #>> '{{}}', {} #>> '{{}}') > 0)",
+                self.value(left),
+                self.value(right)
+            ),
+            Operand::IsNull { operand, negated } => {
+                let value = self.value(operand);
+                let is_null = format!("({value} IS NULL OR {value} = 'null'::jsonb)");
+                if *negated {
+                    format!("NOT {is_null}")
+                } else {
+                    is_null
+                }
+            }
+            value => format!("({} = 'true'::jsonb)", self.value(value)),
+        }
+    }
+}
+
+/// The Relay global `ID` of the node `alias`, as `/graphql` returns it.
+fn global_id(alias: &str) -> String {
+    format!("encode(convert_to('DbNode:' || {alias}.uid, 'UTF8'), 'base64')")
+}
@@ -0,0 +1,250 @@
+use super::QueryError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    Keyword(Keyword),
+    String(String),
+    Number(serde_json::Number),
+    Param(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Colon,
+    Comma,
+    Dot,
+    DotDot,
+    Star,
+    Dash,
+    Arrow,
+    LeftArrow,
+    Eq,
+    Neq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyword {
+    Match,
+    Where,
+    Return,
+    Distinct,
+    As,
+    And,
+    Or,
+    Not,
+    Is,
+    Null,
+    True,
+    False,
+    Contains,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Limit,
+}
+
+impl Keyword {
+    fn from_ident(ident: &str) -> Option<Self> {
+        Some(match ident.to_ascii_uppercase().as_str() {
+            "MATCH" => Keyword::Match,
+            "WHERE" => Keyword::Where,
+            "RETURN" => Keyword::Return,
+            "DISTINCT" => Keyword::Distinct,
+            "AS" => Keyword::As,
+            "AND" => Keyword::And,
+            "OR" => Keyword::Or,
+            "NOT" => Keyword::Not,
+            "IS" => Keyword::Is,
+            "NULL" => Keyword::Null,
+            "TRUE" => Keyword::True,
+            "FALSE" => Keyword::False,
+            "CONTAINS" => Keyword::Contains,
+            "ORDER" => Keyword::Order,
+            "BY" => Keyword::By,
+            "ASC" => Keyword::Asc,
+            "DESC" => Keyword::Desc,
+            "LIMIT" => Keyword::Limit,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte offset of the first character.
+    pub offset: usize,
+}
+
+pub fn tokenize(text: &str) -> Result<Vec<Token>, QueryError> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let c = bytes[pos];
+        let kind = match c {
+            c if c.is_ascii_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            b'(' => single(&mut pos, TokenKind::LParen),
+            b')' => single(&mut pos, TokenKind::RParen),
+            b'[' => single(&mut pos, TokenKind::LBracket),
+            b']' => single(&mut pos, TokenKind::RBracket),
+            b':' => single(&mut pos, TokenKind::Colon),
+            b',' => single(&mut pos, TokenKind::Comma),
+            b'*' => single(&mut pos, TokenKind::Star),
+            b'=' => single(&mut pos, TokenKind::Eq),
+            b'.' if bytes.get(pos + 1) == Some(&b'.') => {
+                pos += 2;
+                TokenKind::DotDot
+            }
+            b'.' => single(&mut pos, TokenKind::Dot),
+            b'-' if bytes.get(pos + 1) == Some(&b'>') => {
+                pos += 2;
+                TokenKind::Arrow
+            }
+            b'-' => single(&mut pos, TokenKind::Dash),
+            b'<' => match bytes.get(pos + 1) {
+                Some(b'-') => {
+                    pos += 2;
+                    TokenKind::LeftArrow
+                }
+                Some(b'=') => {
+                    pos += 2;
+                    TokenKind::Le
+                }
+                Some(b'>') => {
+                    pos += 2;
+                    TokenKind::Neq
+                }
+                _ => single(&mut pos, TokenKind::Lt),
+            },
+            b'>' if bytes.get(pos + 1) == Some(&b'=') => {
+                pos += 2;
+                TokenKind::Ge
+            }
+            b'>' => single(&mut pos, TokenKind::Gt),
+            b'!' if bytes.get(pos + 1) == Some(&b'=') => {
+                pos += 2;
+                TokenKind::Neq
+            }
+            b'\'' | b'"' => TokenKind::String(string(text, &mut pos)?),
+            b'`' => {
+                pos += 1;
+                let end = text[pos..].find('`').ok_or_else(|| {
+                    QueryError::parse(text, start, "unterminated quoted identifier")
+                })?;
+                let ident = text[pos..pos + end].to_string();
+                pos += end + 1;
+                TokenKind::Ident(ident)
+            }
+            b'$' => {
+                pos += 1;
+                let ident = ident(bytes, &mut pos);
+                if ident.is_empty() {
+                    return Err(QueryError::parse(
+                        text,
+                        start,
+                        "expected a parameter name after '$'",
+                    ));
+                }
+                TokenKind::Param(ident.to_string())
+            }
+            c if c.is_ascii_digit() => {
+                while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+                // a single dot is a fraction, two dots are a range
+                if bytes.get(pos) == Some(&b'.')
+                    && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit)
+                {
+                    pos += 1;
+                    while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                        pos += 1;
+                    }
+                }
+                let literal = &text[start..pos];
+                let number = serde_json::from_str(literal)
+                    .map_err(|_| QueryError::parse(text, start, "invalid number"))?;
+                TokenKind::Number(number)
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let ident = ident(bytes, &mut pos);
+                match Keyword::from_ident(ident) {
+                    Some(keyword) => TokenKind::Keyword(keyword),
+                    None => TokenKind::Ident(ident.to_string()),
+                }
+            }
+            _ => {
+                let c = text[pos..].chars().next().unwrap_or_default();
+                return Err(QueryError::parse(
+                    text,
+                    start,
+                    &format!("unexpected character '{c}'"),
+                ));
+            }
+        };
+        tokens.push(Token {
+            kind,
+            offset: start,
+        });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        offset: text.len(),
+    });
+    Ok(tokens)
+}
+
+fn single(pos: &mut usize, kind: TokenKind) -> TokenKind {
+    *pos += 1;
+    kind
+}
+
+fn ident<'a>(bytes: &'a [u8], pos: &mut usize) -> &'a str {
+    let start = *pos;
+    while *pos < bytes.len() && (bytes[*pos].is_ascii_alphanumeric() || bytes[*pos] == b'_') {
+        *pos += 1;
+    }
+    std::str::from_utf8(&bytes[start..*pos]).expect("identifiers are ASCII")
+}
+
+fn string(text: &str, pos: &mut usize) -> Result<String, QueryError> {
+    let start = *pos;
+    let quote = text.as_bytes()[start] as char;
+    let mut value = String::new();
+    let mut chars = text[start + 1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            c if c == quote => {
+                *pos = start + 1 + i + 1;
+                return Ok(value);
+            }
+            c => value.push(c),
+        }
+    }
+    Err(QueryError::parse(text, start, "unterminated string"))
+}
@@ -0,0 +1,412 @@
+use serde_json::Value;
+
+use super::{
+    QueryError,
+    ast::{
+        CompareOp, Direction, Expr, NodePattern, OrderItem, Pattern, Query, RelPattern, ReturnItem,
+        Spanned,
+    },
+    lexer::{Keyword, Token, TokenKind, tokenize},
+};
+
+pub fn parse(text: &str) -> Result<Query, QueryError> {
+    let mut parser = Parser {
+        text,
+        tokens: tokenize(text)?,
+        pos: 0,
+    };
+    let query = parser.query()?;
+    parser.expect(TokenKind::Eof, "end of query")?;
+    Ok(query)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &TokenKind {
+        &self.tokens[self.pos].kind
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens[self.pos].offset
+    }
+
+    fn advance(&mut self) -> &Token {
+        let token = &self.tokens[self.pos];
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek() == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+        self.eat(&TokenKind::Keyword(keyword))
+    }
+
+    fn error(&self, expected: &str) -> QueryError {
+        let found = match self.peek() {
+            TokenKind::Eof => "end of query".to_string(),
+            _ => {
+                let start = self.offset();
+                let end = self
+                    .tokens
+                    .get(self.pos + 1)
+                    .map_or(self.text.len(), |t| t.offset);
+                format!("'{}'", self.text[start..end].trim())
+            }
+        };
+        QueryError::parse(
+            self.text,
+            self.offset(),
+            &format!("expected {expected}, found {found}"),
+        )
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<(), QueryError> {
+        if self.eat(&kind) {
+            Ok(())
+        } else {
+            Err(self.error(expected))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword, expected: &str) -> Result<(), QueryError> {
+        self.expect(TokenKind::Keyword(keyword), expected)
+    }
+
+    fn ident(&mut self, expected: &str) -> Result<String, QueryError> {
+        match self.peek().clone() {
+            TokenKind::Ident(ident) => {
+                self.advance();
+                Ok(ident)
+            }
+            _ => Err(self.error(expected)),
+        }
+    }
+
+    /// Schema titles and edge labels, which may be quoted or contain dashes
+    /// such as `has-ingredient`.
+    fn name(&mut self, expected: &str) -> Result<String, QueryError> {
+        if let TokenKind::String(name) = self.peek().clone() {
+            self.advance();
+            return Ok(name);
+        }
+        let mut name = self.ident(expected)?;
+        while self.peek() == &TokenKind::Dash
+            && matches!(self.tokens[self.pos + 1].kind, TokenKind::Ident(_))
+        {
+            self.advance();
+            name.push('-');
+            name.push_str(&self.ident(expected)?);
+        }
+        Ok(name)
+    }
+
+    fn query(&mut self) -> Result<Query, QueryError> {
+        self.expect_keyword(Keyword::Match, "MATCH")?;
+        let mut patterns = vec![self.pattern()?];
+        while self.eat(&TokenKind::Comma) {
+            patterns.push(self.pattern()?);
+        }
+
+        let predicate = if self.eat_keyword(Keyword::Where) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        self.expect_keyword(Keyword::Return, "WHERE or RETURN")?;
+        let distinct = self.eat_keyword(Keyword::Distinct);
+        let mut returns = vec![self.return_item()?];
+        while self.eat(&TokenKind::Comma) {
+            returns.push(self.return_item()?);
+        }
+
+        let mut order = Vec::new();
+        if self.eat_keyword(Keyword::Order) {
+            self.expect_keyword(Keyword::By, "BY")?;
+            loop {
+                let offset = self.offset();
+                let (expr, text) = self.spanned_expr()?;
+                let descending = if self.eat_keyword(Keyword::Desc) {
+                    true
+                } else {
+                    self.eat_keyword(Keyword::Asc);
+                    false
+                };
+                order.push(OrderItem {
+                    expr,
+                    text,
+                    descending,
+                    offset,
+                });
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let limit = if self.eat_keyword(Keyword::Limit) {
+            let offset = self.offset();
+            match self.peek().clone() {
+                TokenKind::Number(n) if n.as_u64().is_some() => {
+                    self.advance();
+                    Some(Spanned {
+                        value: n.as_u64().unwrap_or_default(),
+                        offset,
+                    })
+                }
+                _ => return Err(self.error("a non-negative integer")),
+            }
+        } else {
+            None
+        };
+
+        Ok(Query {
+            patterns,
+            predicate,
+            distinct,
+            returns,
+            order,
+            limit,
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, QueryError> {
+        let start = self.node()?;
+        let mut hops = Vec::new();
+        while matches!(
+            self.peek(),
+            TokenKind::Dash | TokenKind::LeftArrow | TokenKind::Arrow
+        ) {
+            let rel = self.rel()?;
+            hops.push((rel, self.node()?));
+        }
+        Ok(Pattern { start, hops })
+    }
+
+    fn node(&mut self) -> Result<NodePattern, QueryError> {
+        let offset = self.offset();
+        self.expect(TokenKind::LParen, "'(' to start a node pattern")?;
+        let variable = match self.peek() {
+            TokenKind::Ident(_) => Some(self.ident("a variable")?),
+            _ => None,
+        };
+        let schema = if self.eat(&TokenKind::Colon) {
+            Some(self.name("a schema title")?)
+        } else {
+            None
+        };
+        self.expect(TokenKind::RParen, "')' to close the node pattern")?;
+        Ok(NodePattern {
+            variable,
+            schema,
+            offset,
+        })
+    }
+
+    /// `-[...]->`, `<-[...]-`, `-->` and `<--`.
+    fn rel(&mut self) -> Result<RelPattern, QueryError> {
+        let offset = self.offset();
+        let incoming = self.eat(&TokenKind::LeftArrow);
+        if !incoming && self.eat(&TokenKind::Arrow) {
+            // `-->` lexes as `-` `->`, a bare `->` is an unlabelled hop
+            return Ok(RelPattern {
+                label: None,
+                direction: Direction::Outgoing,
+                length: None,
+                offset,
+            });
+        }
+        if !incoming {
+            self.expect(TokenKind::Dash, "'-' or '<-'")?;
+        }
+
+        let mut label = None;
+        let mut length = None;
+        if self.eat(&TokenKind::LBracket) {
+            if self.eat(&TokenKind::Colon) {
+                label = Some(self.name("an edge label")?);
+            }
+            if self.eat(&TokenKind::Star) {
+                length = Some(self.range()?);
+            }
+            self.expect(TokenKind::RBracket, "']' to close the relationship")?;
+        }
+
+        let direction = if incoming {
+            self.expect(TokenKind::Dash, "'-' after an incoming relationship")?;
+            Direction::Incoming
+        } else if self.eat(&TokenKind::Arrow) {
+            Direction::Outgoing
+        } else {
+            return Err(self.error("'->', undirected relationships are not supported"));
+        };
+
+        Ok(RelPattern {
+            label,
+            direction,
+            length,
+            offset,
+        })
+    }
+
+    /// The part after `*`: nothing, `n`, `n..`, `..m` or `n..m`.
+    fn range(&mut self) -> Result<(u32, Option<u32>), QueryError> {
+        let min = self.hop_count()?;
+        if self.eat(&TokenKind::DotDot) {
+            Ok((min.unwrap_or(1), self.hop_count()?))
+        } else {
+            match min {
+                Some(n) => Ok((n, Some(n))),
+                None => Ok((1, None)),
+            }
+        }
+    }
+
+    fn hop_count(&mut self) -> Result<Option<u32>, QueryError> {
+        match self.peek().clone() {
+            TokenKind::Number(n) => {
+                let count = n
+                    .as_u64()
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or_else(|| self.error("a hop count"))?;
+                self.advance();
+                Ok(Some(count))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn return_item(&mut self) -> Result<ReturnItem, QueryError> {
+        let (expr, text) = self.spanned_expr()?;
+        let alias = if self.eat_keyword(Keyword::As) {
+            Some(self.ident("a column alias")?)
+        } else {
+            None
+        };
+        Ok(ReturnItem { expr, alias, text })
+    }
+
+    fn spanned_expr(&mut self) -> Result<(Expr, String), QueryError> {
+        let start = self.offset();
+        let expr = self.expr()?;
+        let end = self.offset();
+        Ok((expr, self.text[start..end].trim().to_string()))
+    }
+
+    fn expr(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.and()?;
+        while self.eat_keyword(Keyword::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.not()?;
+        while self.eat_keyword(Keyword::And) {
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, QueryError> {
+        if self.eat_keyword(Keyword::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, QueryError> {
+        let left = self.operand()?;
+        let op = match self.peek() {
+            TokenKind::Eq => CompareOp::Eq,
+            TokenKind::Neq => CompareOp::Neq,
+            TokenKind::Lt => CompareOp::Lt,
+            TokenKind::Le => CompareOp::Le,
+            TokenKind::Gt => CompareOp::Gt,
+            TokenKind::Ge => CompareOp::Ge,
+            TokenKind::Keyword(Keyword::Contains) => {
+                self.advance();
+                return Ok(Expr::Contains {
+                    left: Box::new(left),
+                    right: Box::new(self.operand()?),
+                });
+            }
+            TokenKind::Keyword(Keyword::Is) => {
+                self.advance();
+                let negated = self.eat_keyword(Keyword::Not);
+                self.expect_keyword(Keyword::Null, "NULL")?;
+                return Ok(Expr::IsNull {
+                    expr: Box::new(left),
+                    negated,
+                });
+            }
+            _ => return Ok(left),
+        };
+        self.advance();
+        Ok(Expr::Compare {
+            op,
+            left: Box::new(left),
+            right: Box::new(self.operand()?),
+        })
+    }
+
+    fn operand(&mut self) -> Result<Expr, QueryError> {
+        let offset = self.offset();
+        let expr = match self.peek().clone() {
+            TokenKind::String(s) => Expr::Literal(Value::String(s)),
+            TokenKind::Number(n) => Expr::Literal(Value::Number(n)),
+            TokenKind::Dash => {
+                self.advance();
+                match self.peek().clone() {
+                    TokenKind::Number(n) => {
+                        let negative: Value = serde_json::from_str(&format!("-{n}"))
+                            .map_err(|_| self.error("a number"))?;
+                        Expr::Literal(negative)
+                    }
+                    _ => return Err(self.error("a number after '-'")),
+                }
+            }
+            TokenKind::Keyword(Keyword::True) => Expr::Literal(Value::Bool(true)),
+            TokenKind::Keyword(Keyword::False) => Expr::Literal(Value::Bool(false)),
+            TokenKind::Keyword(Keyword::Null) => Expr::Literal(Value::Null),
+            TokenKind::Param(name) => Expr::Param { name, offset },
+            TokenKind::LParen => {
+                self.advance();
+                let expr = self.expr()?;
+                self.expect(TokenKind::RParen, "')'")?;
+                return Ok(expr);
+            }
+            TokenKind::Ident(name) => {
+                self.advance();
+                if self.eat(&TokenKind::Dot) {
+                    let property = self.ident("a property name")?;
+                    return Ok(Expr::Property {
+                        variable: name,
+                        property,
+                        offset,
+                    });
+                }
+                return Ok(Expr::Variable { name, offset });
+            }
+            _ => return Err(self.error("an expression")),
+        };
+        self.advance();
+        Ok(expr)
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use super::{
+    DEFAULT_LIMIT, MAX_HOP_PATTERNS, MAX_HOPS, MAX_LIMIT, MAX_NODE_PATTERNS, QueryError,
+    ast::{CompareOp, Direction, Expr, NodePattern, Query},
+};
+
+/// A node variable bound to a `nodes` alias.
+#[derive(Debug)]
+pub struct PlanNode {
+    pub alias: String,
+    pub schema: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Hop {
+    /// A single edge, joined directly.
+    Edge {
+        alias: String,
+        source: String,
+        target: String,
+        label: Option<String>,
+    },
+    /// A variable length path, walked with a recursive CTE.
+    Path {
+        alias: String,
+        source: String,
+        target: String,
+        label: Option<String>,
+        min: u32,
+        max: u32,
+    },
+}
+
+/// An expression with variables resolved to aliases and parameters
+/// replaced by their values.
+#[derive(Debug)]
+pub enum Operand {
+    Literal(Value),
+    Node(String),
+    Property {
+        alias: String,
+        property: String,
+    },
+    Not(Box<Operand>),
+    And(Box<Operand>, Box<Operand>),
+    Or(Box<Operand>, Box<Operand>),
+    Compare {
+        op: CompareOp,
+        left: Box<Operand>,
+        right: Box<Operand>,
+    },
+    Contains(Box<Operand>, Box<Operand>),
+    IsNull {
+        operand: Box<Operand>,
+        negated: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct Plan {
+    pub nodes: Vec<PlanNode>,
+    pub hops: Vec<Hop>,
+    pub predicate: Option<Operand>,
+    pub distinct: bool,
+    pub columns: Vec<(String, Operand)>,
+    /// Column index and whether it sorts descending.
+    pub order: Vec<(usize, bool)>,
+    pub limit: u64,
+}
+
+pub fn plan(text: &str, query: &Query, params: &Map<String, Value>) -> Result<Plan, QueryError> {
+    let mut planner = Planner {
+        text,
+        params,
+        nodes: Vec::new(),
+        variables: HashMap::new(),
+    };
+
+    let mut hops = Vec::new();
+    for pattern in &query.patterns {
+        let mut previous = planner.bind(&pattern.start)?;
+        for (rel, node) in &pattern.hops {
+            if hops.len() == MAX_HOP_PATTERNS {
+                return Err(QueryError::parse(
+                    text,
+                    rel.offset,
+                    &format!("a query may have at most {MAX_HOP_PATTERNS} relationships"),
+                ));
+            }
+            let next = planner.bind(node)?;
+            let (source, target) = match rel.direction {
+                Direction::Outgoing => (previous.clone(), next.clone()),
+                Direction::Incoming => (next.clone(), previous.clone()),
+            };
+            let alias = format!("h{}", hops.len());
+            hops.push(match rel.length {
+                None => Hop::Edge {
+                    alias,
+                    source,
+                    target,
+                    label: rel.label.clone(),
+                },
+                Some((min, max)) => {
+                    let max = max.unwrap_or(MAX_HOPS);
+                    if max > MAX_HOPS || min > max {
+                        return Err(QueryError::parse(
+                            text,
+                            rel.offset,
+                            &format!("path length must be within 0..{MAX_HOPS} with min <= max"),
+                        ));
+                    }
+                    Hop::Path {
+                        alias,
+                        source,
+                        target,
+                        label: rel.label.clone(),
+                        min,
+                        max,
+                    }
+                }
+            });
+            previous = next;
+        }
+    }
+
+    let predicate = query
+        .predicate
+        .as_ref()
+        .map(|expr| planner.resolve(expr))
+        .transpose()?;
+
+    let mut columns = Vec::with_capacity(query.returns.len());
+    for item in &query.returns {
+        let name = item.alias.clone().unwrap_or_else(|| item.text.clone());
+        columns.push((name, planner.resolve(&item.expr)?));
+    }
+
+    let mut order = Vec::with_capacity(query.order.len());
+    for item in &query.order {
+        let position = query
+            .returns
+            .iter()
+            .position(|r| r.text == item.text || r.alias.as_deref() == Some(item.text.as_str()));
+        match position {
+            Some(index) => order.push((index, item.descending)),
+            None => {
+                return Err(QueryError::parse(
+                    text,
+                    item.offset,
+                    "ORDER BY must refer to a returned column or its alias",
+                ));
+            }
+        }
+    }
+
+    let limit = match query.limit {
+        Some(limit) if limit.value > MAX_LIMIT => {
+            return Err(QueryError::parse(
+                text,
+                limit.offset,
+                &format!("LIMIT must not exceed {MAX_LIMIT}"),
+            ));
+        }
+        Some(limit) => limit.value,
+        None => DEFAULT_LIMIT,
+    };
+
+    Ok(Plan {
+        nodes: planner.nodes,
+        hops,
+        predicate,
+        distinct: query.distinct,
+        columns,
+        order,
+        limit,
+    })
+}
+
+struct Planner<'a> {
+    text: &'a str,
+    params: &'a Map<String, Value>,
+    nodes: Vec<PlanNode>,
+    variables: HashMap<String, usize>,
+}
+
+impl Planner<'_> {
+    /// Give a node pattern its alias. A repeated variable refers to the
+    /// same node, which is how cycles and shared endpoints are written.
+    fn bind(&mut self, node: &NodePattern) -> Result<String, QueryError> {
+        if let Some(variable) = &node.variable
+            && let Some(&index) = self.variables.get(variable)
+        {
+            let bound = &mut self.nodes[index];
+            match (&bound.schema, &node.schema) {
+                (Some(a), Some(b)) if a != b => {
+                    return Err(QueryError::parse(
+                        self.text,
+                        node.offset,
+                        &format!("variable '{variable}' is already bound to schema '{a}'"),
+                    ));
+                }
+                (None, Some(schema)) => bound.schema = Some(schema.clone()),
+                _ => {}
+            }
+            return Ok(bound.alias.clone());
+        }
+
+        if self.nodes.len() == MAX_NODE_PATTERNS {
+            return Err(QueryError::parse(
+                self.text,
+                node.offset,
+                &format!("a query may have at most {MAX_NODE_PATTERNS} nodes"),
+            ));
+        }
+        let alias = format!("n{}", self.nodes.len());
+        if let Some(variable) = &node.variable {
+            self.variables.insert(variable.clone(), self.nodes.len());
+        }
+        self.nodes.push(PlanNode {
+            alias: alias.clone(),
+            schema: node.schema.clone(),
+        });
+        Ok(alias)
+    }
+
+    fn alias(&self, variable: &str, offset: usize) -> Result<String, QueryError> {
+        self.variables
+            .get(variable)
+            .map(|&index| self.nodes[index].alias.clone())
+            .ok_or_else(|| {
+                QueryError::parse(self.text, offset, &format!("unknown variable '{variable}'"))
+            })
+    }
+
+    fn resolve(&self, expr: &Expr) -> Result<Operand, QueryError> {
+        let boxed = |expr: &Expr| self.resolve(expr).map(Box::new);
+        Ok(match expr {
+            Expr::Literal(value) => Operand::Literal(value.clone()),
+            Expr::Param { name, offset } => match self.params.get(name) {
+                Some(value) => Operand::Literal(value.clone()),
+                None => {
+                    return Err(QueryError::parse(
+                        self.text,
+                        *offset,
+                        &format!("missing parameter '${name}'"),
+                    ));
+                }
+            },
+            Expr::Variable { name, offset } => Operand::Node(self.alias(name, *offset)?),
+            Expr::Property {
+                variable,
+                property,
+                offset,
+            } => Operand::Property {
+                alias: self.alias(variable, *offset)?,
+                property: property.clone(),
+            },
+            Expr::Not(expr) => Operand::Not(boxed(expr)?),
+            Expr::And(left, right) => Operand::And(boxed(left)?, boxed(right)?),
+            Expr::Or(left, right) => Operand::Or(boxed(left)?, boxed(right)?),
+            Expr::Compare { op, left, right } => Operand::Compare {
+                op: *op,
+                left: boxed(left)?,
+                right: boxed(right)?,
+            },
+            Expr::Contains { left, right } => Operand::Contains(boxed(left)?, boxed(right)?),
+            Expr::IsNull { expr, negated } => Operand::IsNull {
+                operand: boxed(expr)?,
+                negated: *negated,
+            },
+        })
+    }
+}
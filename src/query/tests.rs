@@ -0,0 +1,365 @@
+use serde_json::{Map, Value, json};
+
+use super::{
+    Bind, MAX_HOP_PATTERNS, MAX_HOPS, MAX_NODE_PATTERNS, QueryError,
+    ast::{Expr, RelPattern},
+    compile,
+    parser::parse,
+};
+
+fn params(value: Value) -> Map<String, Value> {
+    value.as_object().cloned().unwrap_or_default()
+}
+
+fn parse_error(text: &str) -> (usize, usize, String) {
+    match compile(text, &params(json!({ "min": 1 }))) {
+        Err(QueryError::Parse {
+            line,
+            column,
+            message,
+            ..
+        }) => (line, column, message),
+        other => panic!("{text:?} compiled: {other:?}"),
+    }
+}
+
+/// The predicate with every operator parenthesized.
+fn grouping(expr: &Expr) -> String {
+    match expr {
+        Expr::Literal(value) => value.to_string(),
+        Expr::Param { name, .. } => format!("${name}"),
+        Expr::Variable { name, .. } => name.clone(),
+        Expr::Property {
+            variable, property, ..
+        } => format!("{variable}.{property}"),
+        Expr::Not(expr) => format!("(NOT {})", grouping(expr)),
+        Expr::And(left, right) => format!("({} AND {})", grouping(left), grouping(right)),
+        Expr::Or(left, right) => format!("({} OR {})", grouping(left), grouping(right)),
+        Expr::Compare { op, left, right } => {
+            format!("({} {} {})", grouping(left), op.sql(), grouping(right))
+        }
+        Expr::Contains { left, right } => {
+            format!("({} CONTAINS {})", grouping(left), grouping(right))
+        }
+        Expr::IsNull { expr, negated } => format!(
+            "({} IS {}NULL)",
+            grouping(expr),
+            if *negated { "NOT " } else { "" }
+        ),
+    }
+}
+
+/// The `*min..max` of a relationship, `None` for a single hop.
+type Length = Option<(u32, Option<u32>)>;
+
+fn rel(text: &str) -> RelPattern {
+    parse(text).expect("query parses").patterns[0].hops[0]
+        .0
+        .clone()
+}
+
+#[test]
+fn parse_errors_point_at_line_and_column() {
+    let cases = [
+        ("RETURN n", 1, 1, "expected MATCH, found 'RETURN'"),
+        (
+            "MATCH (n RETURN n",
+            1,
+            10,
+            "expected ')' to close the node pattern, found 'RETURN'",
+        ),
+        (
+            "MATCH (n)\nRETURN",
+            2,
+            7,
+            "expected an expression, found end of query",
+        ),
+        (
+            "MATCH (n)\n  WHERE n.kcal >\nRETURN n",
+            3,
+            1,
+            "expected an expression, found 'RETURN'",
+        ),
+        (
+            "MATCH (a)-[:x]-(b) RETURN a",
+            1,
+            15,
+            "expected '->', undirected relationships are not supported, found '-'",
+        ),
+        (
+            "MATCH (n) RETURN n.name LIMIT -1",
+            1,
+            31,
+            "expected a non-negative integer, found '-'",
+        ),
+        ("MATCH (n) RETURN 'open", 1, 18, "unterminated string"),
+        ("MATCH (n) RETURN n # 1", 1, 20, "unexpected character '#'"),
+        ("MATCH (n)\nRETURN m", 2, 8, "unknown variable 'm'"),
+        (
+            "MATCH (n) WHERE n.kcal > $max RETURN n",
+            1,
+            26,
+            "missing parameter '$max'",
+        ),
+        (
+            "MATCH (n) RETURN n.name ORDER BY n.kcal",
+            1,
+            34,
+            "ORDER BY must refer to a returned column or its alias",
+        ),
+        (
+            "MATCH (n) RETURN n LIMIT 10001",
+            1,
+            26,
+            "LIMIT must not exceed 10000",
+        ),
+        (
+            "MATCH (a:Food)-->(a:Ingredient) RETURN a",
+            1,
+            18,
+            "variable 'a' is already bound to schema 'Food'",
+        ),
+    ];
+    for (text, line, column, message) in cases {
+        assert_eq!(
+            parse_error(text),
+            (line, column, message.to_string()),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+fn columns_count_characters_not_bytes() {
+    let (line, column, _) = parse_error("MATCH (n:'Gemüse') RETURN ?");
+    assert_eq!((line, column), (1, 27));
+}
+
+#[test]
+fn variable_length_paths() {
+    let cases: [(&str, Length); 7] = [
+        ("MATCH (a)-[:part-of]->(b) RETURN b", None),
+        ("MATCH (a)-[:part-of*]->(b) RETURN b", Some((1, None))),
+        ("MATCH (a)-[:part-of*2]->(b) RETURN b", Some((2, Some(2)))),
+        ("MATCH (a)-[:part-of*2..]->(b) RETURN b", Some((2, None))),
+        ("MATCH (a)-[:part-of*..3]->(b) RETURN b", Some((1, Some(3)))),
+        (
+            "MATCH (a)-[:part-of*0..3]->(b) RETURN b",
+            Some((0, Some(3))),
+        ),
+        ("MATCH (a)<-[*1..2]-(b) RETURN b", Some((1, Some(2)))),
+    ];
+    for (text, length) in cases {
+        assert_eq!(rel(text).length, length, "{text:?}");
+    }
+    assert_eq!(
+        rel("MATCH (a)-[:part-of*]->(b) RETURN b").label.as_deref(),
+        Some("part-of")
+    );
+
+    let unbounded = compile("MATCH (a)-[*]->(b) RETURN b", &Map::new()).expect("compiles");
+    assert!(
+        unbounded
+            .sql
+            .contains(&format!("WHERE w.depth < {MAX_HOPS}")),
+        "{}",
+        unbounded.sql
+    );
+    let ranged = compile("MATCH (a)-[:part-of*2..3]->(b) RETURN b", &Map::new()).expect("compiles");
+    assert!(ranged.sql.contains("WITH RECURSIVE walk"), "{}", ranged.sql);
+    assert!(
+        ranged
+            .sql
+            .contains("WHERE w.depth < 3 AND e.weight = $1::text"),
+        "{}",
+        ranged.sql
+    );
+    assert!(ranged.sql.contains("WHERE depth >= 2"), "{}", ranged.sql);
+
+    for text in [
+        "MATCH (a)-[*3..2]->(b) RETURN b",
+        "MATCH (a)-[*..11]->(b) RETURN b",
+    ] {
+        let (_, column, message) = parse_error(text);
+        assert_eq!(column, 10, "{text:?}");
+        assert!(
+            message.starts_with("path length must be within"),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+fn patterns_are_capped() {
+    let nodes: Vec<String> = (0..=MAX_NODE_PATTERNS).map(|i| format!("(n{i})")).collect();
+    let allowed = format!("MATCH {} RETURN n0", nodes[..MAX_NODE_PATTERNS].join(", "));
+    compile(&allowed, &Map::new()).expect("compiles");
+    let text = format!("MATCH {} RETURN n0", nodes.join(", "));
+    let (_, column, message) = parse_error(&text);
+    assert_eq!(column, text.rfind('(').unwrap() + 1);
+    assert_eq!(
+        message,
+        format!("a query may have at most {MAX_NODE_PATTERNS} nodes")
+    );
+
+    // repeated variables do not add nodes, but every hop adds a join
+    let hops = |count: usize| {
+        let mut text = "MATCH (a)".to_string();
+        for i in 0..count {
+            text.push_str(if i % 2 == 0 { "-->(b)" } else { "-->(a)" });
+        }
+        text + " RETURN a"
+    };
+    compile(&hops(MAX_HOP_PATTERNS), &Map::new()).expect("compiles");
+    let text = hops(MAX_HOP_PATTERNS + 1);
+    let (_, column, message) = parse_error(&text);
+    assert_eq!(column, text.rfind("-->").unwrap() + 1);
+    assert_eq!(
+        message,
+        format!("a query may have at most {MAX_HOP_PATTERNS} relationships")
+    );
+}
+
+#[test]
+fn where_precedence() {
+    let cases = [
+        (
+            "a.x = 1 OR a.y = 2 AND a.z = 3",
+            "((a.x = 1) OR ((a.y = 2) AND (a.z = 3)))",
+        ),
+        (
+            "a.x = 1 AND a.y = 2 OR a.z = 3",
+            "(((a.x = 1) AND (a.y = 2)) OR (a.z = 3))",
+        ),
+        ("NOT a.x = 1 AND a.y", "((NOT (a.x = 1)) AND a.y)"),
+        ("NOT NOT a.x", "(NOT (NOT a.x))"),
+        ("(a.x OR a.y) AND a.z", "((a.x OR a.y) AND a.z)"),
+        ("a.x OR a.y OR a.z", "((a.x OR a.y) OR a.z)"),
+        (
+            "a.name CONTAINS 'to' OR a.kcal IS NOT NULL",
+            "((a.name CONTAINS \"to\") OR (a.kcal IS NOT NULL))",
+        ),
+        (
+            "a.kcal >= -1.5 AND a.kcal <> $min",
+            "((a.kcal >= -1.5) AND (a.kcal <> $min))",
+        ),
+    ];
+    for (predicate, expected) in cases {
+        let text = format!("MATCH (a) WHERE {predicate} RETURN a");
+        let query = parse(&text).expect("query parses");
+        let expr = query.predicate.expect("query has a predicate");
+        assert_eq!(grouping(&expr), expected, "{predicate:?}");
+    }
+}
+
+#[test]
+fn parameters_are_bound_not_inlined() {
+    let compiled = compile(
+        "MATCH (n:Nutrition) WHERE n.kcal > $min AND n.source = $source RETURN n.name, $min AS min",
+        &params(json!({ "min": 100, "source": "USDA' OR 1=1 --" })),
+    )
+    .expect("compiles");
+    assert_eq!(
+        compiled.binds,
+        vec![
+            Bind::Text("Nutrition".into()),
+            Bind::Text("kcal".into()),
+            Bind::Json(json!(100)),
+            Bind::Text("source".into()),
+            Bind::Json(json!("USDA' OR 1=1 --")),
+            Bind::Json(json!(100)),
+        ]
+    );
+    assert!(!compiled.sql.contains("USDA"), "{}", compiled.sql);
+    assert!(
+        compiled.sql.contains("(n0.data -> $2::text) > $3::jsonb"),
+        "{}",
+        compiled.sql
+    );
+    assert_eq!(compiled.columns, ["n.name", "min"]);
+}
+
+#[test]
+fn generated_sql() {
+    let global_id = "encode(convert_to('DbNode:' || n0.uid, 'UTF8'), 'base64')";
+    let cases = [
+        (
+            "MATCH (n) RETURN n.name",
+            "SELECT jsonb_build_array(c0) FROM (SELECT to_jsonb(n0.name) AS c0 \
+             FROM nodes n0 WHERE true) q LIMIT 1000"
+                .to_string(),
+        ),
+        (
+            "MATCH (f:Food)-[:has-ingredient]->(i) RETURN DISTINCT i.name AS name ORDER BY name DESC LIMIT 5",
+            "SELECT jsonb_build_array(c0) FROM (SELECT DISTINCT to_jsonb(n1.name) AS c0 \
+             FROM nodes n0, nodes n1, edges h0 \
+             WHERE n0.schema_title = $1::text AND h0.source_node_id = n0.id \
+             AND h0.target_node_id = n1.id AND h0.weight = $2::text) q \
+             ORDER BY c0 DESC LIMIT 5"
+                .to_string(),
+        ),
+        (
+            "MATCH (i)<--(f) RETURN i.id",
+            format!(
+                "SELECT jsonb_build_array(c0) FROM (SELECT to_jsonb({global_id}) AS c0 \
+                 FROM nodes n0, nodes n1, edges h0 \
+                 WHERE h0.source_node_id = n1.id AND h0.target_node_id = n0.id) q LIMIT 1000"
+            ),
+        ),
+        (
+            "MATCH (n) WHERE n.vegan RETURN n.schemaTitle",
+            "SELECT jsonb_build_array(c0) FROM (SELECT to_jsonb(n0.schema_title) AS c0 \
+             FROM nodes n0 WHERE ((n0.data -> $1::text) = 'true'::jsonb)) q LIMIT 1000"
+                .to_string(),
+        ),
+    ];
+    for (text, sql) in cases {
+        let compiled = compile(text, &Map::new()).expect("compiles");
+        assert_eq!(compiled.sql, sql, "{text:?}");
+    }
+}
+
+#[test]
+fn names_and_keys_cannot_inject_sql() {
+    let payload = "x') OR true; DROP TABLE nodes; --";
+    let cases = [
+        format!("MATCH (n:\"{payload}\") RETURN n"),
+        format!("MATCH (a)-[:\"{payload}\"]->(b) RETURN b"),
+        format!("MATCH (a)-[:\"{payload}\"*1..2]->(b) RETURN b"),
+        format!("MATCH (n) WHERE n.`{payload}` = 1 RETURN n"),
+        format!("MATCH (n) RETURN n.`{payload}`"),
+        format!("MATCH (`{payload}`) RETURN `{payload}`.name"),
+        format!("MATCH (n) RETURN n.name AS `{payload}` ORDER BY n.name"),
+        format!("MATCH (n) WHERE n.name = \"{payload}\" RETURN n"),
+    ];
+    for text in cases {
+        let compiled = compile(&text, &Map::new()).expect("compiles");
+        assert!(
+            !compiled.sql.contains("DROP"),
+            "{text:?} gave {}",
+            compiled.sql
+        );
+    }
+
+    // identifiers never reach the SQL, names and keys are bound
+    let compiled = compile(
+        &format!(
+            "MATCH (`{payload}`:\"{payload}\")-[:\"{payload}\"]->(b) RETURN `{payload}`.`{payload}`"
+        ),
+        &Map::new(),
+    )
+    .expect("compiles");
+    assert_eq!(compiled.binds, vec![Bind::Text(payload.into()); 3]);
+    assert_eq!(compiled.columns, [format!("`{payload}`.`{payload}`")]);
+}
+
+#[tokio::test]
+async fn only_postgres_runs_queries() {
+    let storage = crate::storage::MemoryStorage::default();
+    match super::run(&storage, "MATCH (f:Food) RETURN f.name", &Map::new()).await {
+        Err(QueryError::Storage(crate::error::Error::Unsupported { storages, .. })) => {
+            assert_eq!(storages, ["sqlite", "memory"]);
+        }
+        other => panic!("the query ran: {other:?}"),
+    }
+}
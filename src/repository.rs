@@ -0,0 +1,49 @@
+//! The domain layer: reading and writing schemas, nodes, edges and users.
+//!
+//! The GraphQL resolvers, the REST handlers and the `lixiv` command line tool
+//! are all thin adapters over these repositories, which validate their input
+//! and run multi-statement changes in a transaction. Schemas, nodes and edges
+//! are read and written through a [`Storage`](crate::storage::Storage), users
+//! only live in the database. Repositories are cheap to clone.
+
+mod edges;
+mod nodes;
+mod schemas;
+#[cfg(test)]
+mod tests;
+mod users;
+
+pub use edges::{Direction, EdgeRepository, NewEdge};
+pub use nodes::{
+    DUPLICATE_CANDIDATES_LIMIT, MAX_SEARCH_HITS, MergeNodes, MergeStrategy, NewNode, NodeFilter,
+    NodeOrder, NodeRepository, SIMILAR_NODES_LIMIT,
+};
+pub use schemas::{NewSchema, SchemaRepository};
+pub use users::{NewUser, UserRepository};
+
+use crate::storage::SharedStorage;
+
+/// A window into a listing; no `limit` returns everything.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Page {
+    pub limit: Option<i64>,
+    pub offset: i64,
+}
+
+/// The repositories over one storage.
+#[derive(Clone)]
+pub struct Repositories {
+    pub schemas: SchemaRepository,
+    pub nodes: NodeRepository,
+    pub edges: EdgeRepository,
+}
+
+impl Repositories {
+    pub fn new(storage: SharedStorage) -> Self {
+        Repositories {
+            schemas: SchemaRepository::new(storage.clone()),
+            nodes: NodeRepository::new(storage.clone()),
+            edges: EdgeRepository::new(storage),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::Page;
+use crate::{error::Error, model::DbEdge, storage::SharedStorage};
+
+/// Which edges of a node to list.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Deserialize,
+    async_graphql::Enum,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+    #[default]
+    Both,
+}
+
+/// An edge from one node to another, labelled by its `weight`.
+#[derive(Debug, Clone)]
+pub struct NewEdge {
+    pub source_node_id: i32,
+    pub target_node_id: i32,
+    pub weight: String,
+}
+
+#[derive(Clone)]
+pub struct EdgeRepository {
+    storage: SharedStorage,
+}
+
+impl EdgeRepository {
+    pub fn new(storage: SharedStorage) -> Self {
+        EdgeRepository { storage }
+    }
+
+    /// Edges, newest first.
+    pub async fn list(&self, page: Page) -> Result<Vec<DbEdge>, Error> {
+        self.storage.list_edges(page).await
+    }
+
+    pub async fn count(&self) -> Result<i64, Error> {
+        self.storage.count_edges().await
+    }
+
+    pub async fn get(&self, id: i32) -> Result<Option<DbEdge>, Error> {
+        self.storage.get_edge(id).await
+    }
+
+    /// The edge with the public id `uid`.
+    pub async fn get_by_uid(&self, uid: Uuid) -> Result<Option<DbEdge>, Error> {
+        self.storage.get_edge_by_uid(uid).await
+    }
+
+    /// The edges leaving and/or entering `node_id`, newest first.
+    pub async fn of_node(
+        &self,
+        node_id: i32,
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<DbEdge>, Error> {
+        self.storage.edges_of(&[node_id], direction, page).await
+    }
+
+    pub async fn count_of_node(&self, node_id: i32, direction: Direction) -> Result<i64, Error> {
+        self.storage.count_edges_of(&[node_id], direction).await
+    }
+
+    /// A `page` of the edges leaving and/or entering each of `node_ids`,
+    /// newest first, by node id.
+    pub async fn of_each_node(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<HashMap<i32, Vec<DbEdge>>, Error> {
+        let mut edges: HashMap<i32, Vec<DbEdge>> = HashMap::new();
+        for (node_id, edge) in self
+            .storage
+            .edges_of_each(node_ids, direction, page)
+            .await?
+        {
+            edges.entry(node_id).or_default().push(edge);
+        }
+        Ok(edges)
+    }
+
+    pub async fn create(&self, edge: NewEdge) -> Result<DbEdge, Error> {
+        self.storage.insert_edge(edge).await
+    }
+
+    pub async fn delete(&self, id: i32) -> Result<bool, Error> {
+        self.storage.delete_edge(id).await
+    }
+}
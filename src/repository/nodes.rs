@@ -0,0 +1,300 @@
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::Page;
+use crate::{
+    catalog::SchemaCatalog,
+    error::Error,
+    model::{DbCreatedNode, DbDuplicateCandidate, DbNode, DbSearchHit, DbSimilarNode},
+    storage::SharedStorage,
+};
+
+/// At most this many nodes are returned by [`NodeRepository::similar`].
+pub const SIMILAR_NODES_LIMIT: i64 = 20;
+/// At most this many pairs are returned by
+/// [`NodeRepository::duplicate_candidates`].
+pub const DUPLICATE_CANDIDATES_LIMIT: i64 = 100;
+/// At most this many hits are returned by [`NodeRepository::search`].
+pub const MAX_SEARCH_HITS: i64 = 100;
+
+/// A node of `schema_title`, whose `data` must be valid against the schema.
+#[derive(Debug, Clone)]
+pub struct NewNode {
+    pub schema_title: String,
+    pub name: String,
+    pub data: Value,
+}
+
+impl NewNode {
+    /// The node with its `name` in `data` as well, unless `data` has one: like
+    /// in [`NodeInstance::new`](crate::model::NodeInstance::new), the name is
+    /// part of the data that is validated and stored, however the node is
+    /// created.
+    pub fn with_name_in_data(mut self) -> Self {
+        if let Some(data) = self.data.as_object_mut() {
+            data.entry("name")
+                .or_insert_with(|| Value::String(self.name.clone()));
+        }
+        self
+    }
+}
+
+/// All nodes, or those of `schema_title` and, with `include_subtypes`, of the
+/// schemas extending it, listed in `order`.
+#[derive(Debug, Default, Clone)]
+pub struct NodeFilter {
+    pub schema_title: Option<String>,
+    pub include_subtypes: bool,
+    pub order: NodeOrder,
+}
+
+/// The order of [`NodeRepository::list`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NodeOrder {
+    /// Newest first.
+    #[default]
+    Newest,
+    /// By name, then newest first.
+    Name,
+}
+
+/// How [`NodeRepository::merge`] combines the `data` of the merged nodes into
+/// the survivor.
+#[derive(async_graphql::Enum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the survivor's data unchanged.
+    Keep,
+    /// Add properties the survivor lacks, the survivor wins on conflicts.
+    #[default]
+    PreferKeep,
+    /// Add all properties, the merged nodes win on conflicts.
+    PreferMerged,
+}
+
+/// Fold the nodes `merge_ids` into `keep_id`.
+#[derive(Debug, Clone)]
+pub struct MergeNodes {
+    pub keep_id: i32,
+    pub merge_ids: Vec<i32>,
+    pub strategy: MergeStrategy,
+}
+
+#[derive(Clone)]
+pub struct NodeRepository {
+    storage: SharedStorage,
+}
+
+/// Check `data` against the schema `title` of the catalog.
+fn validate(catalog: &SchemaCatalog, title: &str, data: &Value) -> Result<(), Error> {
+    if catalog.get(title).is_none() {
+        return Err(Error::ForeignKeyViolation(format!(
+            "schema '{title}' does not exist"
+        )));
+    }
+    let validator = catalog.validator(title).map_err(Error::ValidationFailed)?;
+    let errors: Vec<String> = validator
+        .iter_errors(data)
+        .map(|e| format!("{}: {e}", e.instance_path.as_str()))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::ValidationFailed(format!(
+            "invalid {title}: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+impl NodeRepository {
+    pub fn new(storage: SharedStorage) -> Self {
+        NodeRepository { storage }
+    }
+
+    /// The nodes matching `filter`, in its order.
+    pub async fn list(&self, filter: &NodeFilter, page: Page) -> Result<Vec<DbNode>, Error> {
+        self.storage.list_nodes(filter, page).await
+    }
+
+    pub async fn count(&self, filter: &NodeFilter) -> Result<i64, Error> {
+        self.storage.count_nodes(filter).await
+    }
+
+    pub async fn get(&self, id: i32) -> Result<Option<DbNode>, Error> {
+        let mut nodes = self.storage.get_nodes(&[id]).await?;
+        Ok(nodes.pop())
+    }
+
+    /// The node with the public id `uid`.
+    pub async fn get_by_uid(&self, uid: Uuid) -> Result<Option<DbNode>, Error> {
+        let mut nodes = self.storage.get_nodes_by_uid(&[uid]).await?;
+        Ok(nodes.pop())
+    }
+
+    /// The nodes with the given public ids, in no particular order.
+    pub async fn get_many_by_uid(&self, uids: &[Uuid]) -> Result<Vec<DbNode>, Error> {
+        self.storage.get_nodes_by_uid(uids).await
+    }
+
+    /// The node of `schema_title` called `name`.
+    pub async fn find(&self, schema_title: &str, name: &str) -> Result<Option<DbNode>, Error> {
+        self.storage.find_node(schema_title, name).await
+    }
+
+    /// The nodes with the given ids, in no particular order.
+    pub async fn get_many(&self, ids: &[i32]) -> Result<Vec<DbNode>, Error> {
+        self.storage.get_nodes(ids).await
+    }
+
+    /// Store a node after validating its `data`, with the
+    /// [name in it](NewNode::with_name_in_data), against its schema. With a
+    /// `duplicate_threshold` the existing nodes of the schema whose names are
+    /// at least that similar are returned with it, as a warning.
+    pub async fn create(
+        &self,
+        node: NewNode,
+        duplicate_threshold: Option<f32>,
+    ) -> Result<DbCreatedNode, Error> {
+        let node = node.with_name_in_data();
+        let catalog = self.storage.catalog().await?;
+        validate(&catalog, &node.schema_title, &node.data)?;
+        let near_duplicates = match duplicate_threshold {
+            Some(threshold) => {
+                self.similar(&node.name, Some(&node.schema_title), threshold)
+                    .await?
+            }
+            None => Vec::new(),
+        };
+        let node = self.storage.insert_node(node).await?;
+        Ok(DbCreatedNode {
+            node,
+            near_duplicates,
+        })
+    }
+
+    /// Delete a node and its edges.
+    pub async fn delete(&self, id: i32) -> Result<bool, Error> {
+        self.storage.delete_node(id).await
+    }
+
+    /// Ranked full-text search over node names and string properties, in at
+    /// most `limit` (clamped to [`MAX_SEARCH_HITS`]) hits.
+    pub async fn search(
+        &self,
+        text: &str,
+        schema_titles: &[String],
+        limit: i64,
+    ) -> Result<Vec<DbSearchHit>, Error> {
+        self.storage
+            .search_nodes(text, schema_titles, limit.clamp(1, MAX_SEARCH_HITS))
+            .await
+    }
+
+    /// Nodes whose names are spelled similarly to `name`, e.g. "tomatos" and
+    /// "Tomatoes", most similar first.
+    pub async fn similar(
+        &self,
+        name: &str,
+        schema_title: Option<&str>,
+        threshold: f32,
+    ) -> Result<Vec<DbSimilarNode>, Error> {
+        self.storage
+            .similar_nodes(
+                name,
+                schema_title,
+                threshold.clamp(0.0, 1.0),
+                SIMILAR_NODES_LIMIT,
+            )
+            .await
+    }
+
+    /// Pairs of nodes in a schema whose names are spelled alike, most similar
+    /// first.
+    pub async fn duplicate_candidates(
+        &self,
+        schema_title: &str,
+        threshold: f32,
+    ) -> Result<Vec<DbDuplicateCandidate>, Error> {
+        self.storage
+            .duplicate_candidates(
+                schema_title,
+                threshold.clamp(0.0, 1.0),
+                DUPLICATE_CANDIDATES_LIMIT,
+            )
+            .await
+    }
+
+    /// Fold `merge_ids` into `keep_id` in one transaction: edges are re-pointed
+    /// to the survivor, `data` is combined and re-validated and the merged
+    /// nodes are deleted.
+    pub async fn merge(&self, merge: MergeNodes) -> Result<DbNode, Error> {
+        let MergeNodes {
+            keep_id,
+            merge_ids,
+            strategy,
+        } = merge;
+        if merge_ids.is_empty() || merge_ids.contains(&keep_id) {
+            return Err(Error::ValidationFailed(
+                "mergeIds must be non-empty and must not contain keepId".to_string(),
+            ));
+        }
+        let catalog = self.storage.catalog().await?;
+
+        let combine = |nodes: &[DbNode]| {
+            let Some((keep, merged)) = nodes.split_first().filter(|(keep, _)| keep.id == keep_id)
+            else {
+                return Err(Error::NotFound(format!("node {keep_id} does not exist")));
+            };
+            if let Some(missing) = merge_ids
+                .iter()
+                .find(|id| !merged.iter().any(|node| node.id == **id))
+            {
+                return Err(Error::NotFound(format!("node {missing} does not exist")));
+            }
+            if let Some(other) = merged
+                .iter()
+                .find(|node| node.schema_title != keep.schema_title)
+            {
+                return Err(Error::ValidationFailed(format!(
+                    "node {} belongs to schema '{}', not '{}'",
+                    other.id, other.schema_title, keep.schema_title
+                )));
+            }
+
+            let mut data = keep.data.clone();
+            if let Some(target) = data.as_object_mut() {
+                for node in merged {
+                    let Some(source) = node.data.as_object() else {
+                        continue;
+                    };
+                    for (key, value) in source {
+                        match strategy {
+                            MergeStrategy::Keep => {}
+                            MergeStrategy::PreferKeep => {
+                                target.entry(key.clone()).or_insert_with(|| value.clone());
+                            }
+                            MergeStrategy::PreferMerged => {
+                                target.insert(key.clone(), value.clone());
+                            }
+                        }
+                    }
+                }
+                // the survivor keeps its own name
+                if target.contains_key("name") {
+                    target.insert("name".to_string(), Value::String(keep.name.clone()));
+                }
+            }
+
+            validate(&catalog, &keep.schema_title, &data).map_err(|e| match e {
+                Error::ValidationFailed(message) => {
+                    Error::ValidationFailed(format!("merged data is {message}"))
+                }
+                e => e,
+            })?;
+            Ok(data)
+        };
+        self.storage
+            .merge_nodes(keep_id, &merge_ids, &combine)
+            .await
+    }
+}
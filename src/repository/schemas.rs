@@ -0,0 +1,90 @@
+use uuid::Uuid;
+
+use super::Page;
+use crate::{
+    catalog::SchemaCatalog, derived, error::Error, model::DbSchema, storage::SharedStorage,
+};
+
+/// A schema to store under `title`.
+#[derive(Debug, Clone)]
+pub struct NewSchema {
+    pub title: String,
+    pub schema_json: serde_json::Value,
+}
+
+#[derive(Clone)]
+pub struct SchemaRepository {
+    storage: SharedStorage,
+}
+
+impl SchemaRepository {
+    pub fn new(storage: SharedStorage) -> Self {
+        SchemaRepository { storage }
+    }
+
+    /// Schemas, newest first.
+    pub async fn list(&self, page: Page) -> Result<Vec<DbSchema>, Error> {
+        self.storage.list_schemas(page).await
+    }
+
+    pub async fn count(&self) -> Result<i64, Error> {
+        self.storage.count_schemas().await
+    }
+
+    pub async fn get(&self, title: &str) -> Result<Option<DbSchema>, Error> {
+        let mut schemas = self.storage.get_schemas(&[title.to_string()]).await?;
+        Ok(schemas.pop())
+    }
+
+    /// The schema with the public id `uid`.
+    pub async fn get_by_uid(&self, uid: Uuid) -> Result<Option<DbSchema>, Error> {
+        self.storage.get_schema_by_uid(uid).await
+    }
+
+    /// The schemas with the given titles, in no particular order.
+    pub async fn get_many(&self, titles: &[String]) -> Result<Vec<DbSchema>, Error> {
+        self.storage.get_schemas(titles).await
+    }
+
+    /// Whether `title` is `ancestor` or extends it, directly or indirectly.
+    pub async fn is_a(&self, title: &str, ancestor: &str) -> Result<bool, Error> {
+        let catalog = self.storage.catalog().await?;
+        Ok(catalog
+            .lineage(title)
+            .is_ok_and(|lineage| lineage.contains(&ancestor)))
+    }
+
+    /// Store a schema after checking its `x-derived` declarations and that it
+    /// compiles together with the stored schemas it extends or refers to.
+    pub async fn create(&self, schema: NewSchema) -> Result<DbSchema, Error> {
+        derived::definitions(&schema.schema_json).map_err(Error::ValidationFailed)?;
+        let check = |stored: &SchemaCatalog| {
+            let mut catalog = stored.clone();
+            catalog.insert(&schema.title, schema.schema_json.clone());
+            catalog
+                .validator(&schema.title)
+                .map(drop)
+                .map_err(Error::ValidationFailed)
+        };
+        self.storage.insert_schema(schema.clone(), &check).await
+    }
+
+    /// Delete a schema and its nodes, unless other schemas still use it.
+    pub async fn delete(&self, title: &str) -> Result<bool, Error> {
+        let check = |stored: &SchemaCatalog| {
+            let dependents = stored.dependents(title);
+            if dependents.is_empty() {
+                return Ok(());
+            }
+            Err(Error::ForeignKeyViolation(format!(
+                "schema '{title}' is still used by {}",
+                dependents
+                    .iter()
+                    .map(|dependent| format!("'{dependent}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )))
+        };
+        self.storage.delete_schema(title, &check).await
+    }
+}
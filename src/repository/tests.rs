@@ -0,0 +1,442 @@
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+
+use super::{
+    Direction, MergeNodes, MergeStrategy, NewEdge, NewNode, NewSchema, NodeFilter, NodeOrder, Page,
+    Repositories,
+};
+use crate::{
+    error::Error,
+    model::DbNode,
+    storage::{MemoryStorage, SharedStorage},
+};
+
+fn storage() -> SharedStorage {
+    Arc::new(MemoryStorage::default())
+}
+
+async fn add_schema(repositories: &Repositories, schema_json: Value) {
+    let title = schema_json["title"].as_str().expect("schema has a title");
+    repositories
+        .schemas
+        .create(NewSchema {
+            title: title.to_string(),
+            schema_json,
+        })
+        .await
+        .expect("schema is stored");
+}
+
+async fn add_node(repositories: &Repositories, schema_title: &str, name: &str, data: Value) -> i32 {
+    repositories
+        .nodes
+        .create(new_node(schema_title, name, data), None)
+        .await
+        .expect("node is stored")
+        .node
+        .id
+}
+
+async fn add_edge(repositories: &Repositories, source: i32, target: i32, weight: &str) {
+    repositories
+        .edges
+        .create(new_edge(source, target, weight))
+        .await
+        .expect("edge is stored");
+}
+
+fn new_node(schema_title: &str, name: &str, data: Value) -> NewNode {
+    NewNode {
+        schema_title: schema_title.to_string(),
+        name: name.to_string(),
+        data,
+    }
+}
+
+fn new_edge(source_node_id: i32, target_node_id: i32, weight: &str) -> NewEdge {
+    NewEdge {
+        source_node_id,
+        target_node_id,
+        weight: weight.to_string(),
+    }
+}
+
+/// The edges of the graph as `(source, target, weight)`, sorted.
+async fn edges(repositories: &Repositories) -> Vec<(i32, i32, String)> {
+    let mut edges: Vec<_> = repositories
+        .edges
+        .list(Page::default())
+        .await
+        .expect("edges are listed")
+        .into_iter()
+        .map(|edge| (edge.source_node_id, edge.target_node_id, edge.weight))
+        .collect();
+    edges.sort();
+    edges
+}
+
+fn names(mut nodes: Vec<DbNode>) -> Vec<String> {
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    nodes.into_iter().map(|node| node.name).collect()
+}
+
+fn food() -> Value {
+    json!({
+        "title": "Food",
+        "type": "object",
+        "properties": { "kcal": { "type": "integer" }, "vegan": { "type": "boolean" } }
+    })
+}
+
+#[tokio::test]
+async fn create_rejects_invalid_duplicate_and_dangling_rows() {
+    let repositories = Repositories::new(storage());
+    add_schema(&repositories, food()).await;
+
+    let error = repositories
+        .schemas
+        .create(NewSchema {
+            title: "Food".to_string(),
+            schema_json: food(),
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::UniqueViolation { key } if key == "(title)=(Food)"),
+        "{error:?}"
+    );
+    let error = repositories
+        .schemas
+        .create(NewSchema {
+            title: "Dessert".to_string(),
+            schema_json: json!({ "title": "Dessert", "x-extends": "Sweets" }),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::ValidationFailed(_)), "{error:?}");
+
+    let apple = add_node(&repositories, "Food", "Apple", json!({ "kcal": 52 })).await;
+    let error = repositories
+        .nodes
+        .create(new_node("Fruit", "Pear", json!({})), None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::ForeignKeyViolation(message) if message == "schema 'Fruit' does not exist"),
+        "{error:?}"
+    );
+    let error = repositories
+        .nodes
+        .create(new_node("Food", "Pear", json!({ "kcal": "a few" })), None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::ValidationFailed(message) if message.starts_with("invalid Food: /kcal: ")),
+        "{error:?}"
+    );
+    let error = repositories
+        .nodes
+        .create(new_node("Food", "Apple", json!({ "kcal": 50 })), None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::UniqueViolation { key } if key == "(schema_title, name)=(Food, Apple)"),
+        "{error:?}"
+    );
+
+    let pear = add_node(&repositories, "Food", "Pear", json!({ "kcal": 57 })).await;
+    add_edge(&repositories, apple, pear, "like").await;
+    let error = repositories
+        .edges
+        .create(new_edge(apple, pear, "like"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::UniqueViolation { .. }), "{error:?}");
+    let error = repositories
+        .edges
+        .create(new_edge(apple, 999, "like"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::ForeignKeyViolation(message) if message == "node 999 does not exist"),
+        "{error:?}"
+    );
+
+    let created = repositories
+        .nodes
+        .create(new_node("Food", "Apples", json!({})), Some(0.5))
+        .await
+        .expect("near-duplicates do not prevent creating a node");
+    assert_eq!(created.near_duplicates.len(), 1);
+    assert_eq!(created.near_duplicates[0].node.id, apple);
+}
+
+#[tokio::test]
+async fn list_pages_by_name_or_newest_first() {
+    let repositories = Repositories::new(storage());
+    add_schema(&repositories, food()).await;
+    for name in ["Pear", "Apple", "Quince", "Banana"] {
+        add_node(&repositories, "Food", name, json!({})).await;
+    }
+
+    let list = |order: NodeOrder, offset: i64| {
+        let filter = NodeFilter {
+            schema_title: Some("Food".to_string()),
+            include_subtypes: false,
+            order,
+        };
+        let nodes = repositories.nodes.clone();
+        async move {
+            let page = Page {
+                limit: Some(2),
+                offset,
+            };
+            let nodes = nodes.list(&filter, page).await.expect("nodes are listed");
+            nodes.into_iter().map(|node| node.name).collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(list(NodeOrder::Name, 0).await, ["Apple", "Banana"]);
+    assert_eq!(list(NodeOrder::Name, 2).await, ["Pear", "Quince"]);
+    assert_eq!(list(NodeOrder::Newest, 0).await, ["Banana", "Quince"]);
+}
+
+#[tokio::test]
+async fn merge_repoints_edges_and_drops_self_loops() {
+    let repositories = Repositories::new(storage());
+    add_schema(&repositories, food()).await;
+    let tomato = add_node(&repositories, "Food", "Tomato", json!({ "kcal": 18 })).await;
+    let tomatoes = add_node(
+        &repositories,
+        "Food",
+        "Tomatoes",
+        json!({ "kcal": 20, "vegan": true }),
+    )
+    .await;
+    let soup = add_node(&repositories, "Food", "Soup", json!({})).await;
+    let pizza = add_node(&repositories, "Food", "Pizza", json!({})).await;
+    add_edge(&repositories, soup, tomato, "has-ingredient").await;
+    add_edge(&repositories, soup, tomatoes, "has-ingredient").await;
+    add_edge(&repositories, pizza, tomatoes, "has-ingredient").await;
+    add_edge(&repositories, tomatoes, tomato, "same-as").await;
+    add_edge(&repositories, tomato, tomatoes, "same-as").await;
+
+    let merged = repositories
+        .nodes
+        .merge(MergeNodes {
+            keep_id: tomato,
+            merge_ids: vec![tomatoes],
+            strategy: MergeStrategy::PreferKeep,
+        })
+        .await
+        .expect("nodes are merged");
+    assert_eq!(
+        merged.data,
+        json!({ "name": "Tomato", "kcal": 18, "vegan": true })
+    );
+    assert!(
+        repositories
+            .nodes
+            .get(tomatoes)
+            .await
+            .expect("node is read")
+            .is_none()
+    );
+    assert_eq!(
+        edges(&repositories).await,
+        [
+            (soup, tomato, "has-ingredient".to_string()),
+            (pizza, tomato, "has-ingredient".to_string()),
+        ]
+    );
+
+    let error = repositories
+        .nodes
+        .merge(MergeNodes {
+            keep_id: tomato,
+            merge_ids: vec![tomatoes],
+            strategy: MergeStrategy::Keep,
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::NotFound(message) if message == &format!("node {tomatoes} does not exist")),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn walk_follows_labelled_edges() {
+    let storage = storage();
+    let repositories = Repositories::new(storage.clone());
+    add_schema(&repositories, food()).await;
+    let mut ids = Vec::new();
+    for name in ["Dinner", "Lasagne", "Salad", "Pasta", "Tomato", "Oil"] {
+        ids.push(add_node(&repositories, "Food", name, json!({})).await);
+    }
+    let [dinner, lasagne, salad, pasta, tomato, oil] = ids[..] else {
+        unreachable!()
+    };
+    add_edge(&repositories, dinner, lasagne, "course").await;
+    add_edge(&repositories, dinner, salad, "course").await;
+    add_edge(&repositories, lasagne, pasta, "has-ingredient").await;
+    add_edge(&repositories, lasagne, tomato, "has-ingredient").await;
+    add_edge(&repositories, salad, tomato, "has-ingredient").await;
+    add_edge(&repositories, salad, oil, "garnish").await;
+
+    let walk = |start: i32, path: &[&str]| {
+        let storage = storage.clone();
+        let path: Vec<String> = path.iter().map(|label| label.to_string()).collect();
+        async move { names(storage.walk(start, &path).await.expect("walk succeeds")) }
+    };
+    assert_eq!(walk(dinner, &[]).await, ["Dinner"]);
+    assert_eq!(walk(dinner, &["course"]).await, ["Lasagne", "Salad"]);
+    assert_eq!(
+        walk(dinner, &["course", "has-ingredient"]).await,
+        ["Pasta", "Tomato"]
+    );
+    assert!(walk(dinner, &["has-ingredient"]).await.is_empty());
+    assert!(walk(tomato, &["course"]).await.is_empty());
+    assert!(walk(999, &[]).await.is_empty());
+}
+
+#[tokio::test]
+async fn edges_are_paged_per_node() {
+    let repositories = Repositories::new(storage());
+    add_schema(&repositories, food()).await;
+    let mut ids = Vec::new();
+    for name in ["Soup", "Pizza", "Tomato", "Basil", "Oil"] {
+        ids.push(add_node(&repositories, "Food", name, json!({})).await);
+    }
+    let [soup, pizza, tomato, basil, oil] = ids[..] else {
+        unreachable!()
+    };
+    add_edge(&repositories, soup, tomato, "has-ingredient").await;
+    add_edge(&repositories, soup, basil, "has-ingredient").await;
+    add_edge(&repositories, soup, oil, "has-ingredient").await;
+    add_edge(&repositories, pizza, tomato, "has-ingredient").await;
+    add_edge(&repositories, tomato, tomato, "same-as").await;
+
+    let targets = |page: Page| {
+        let edges = repositories.edges.clone();
+        let ids = [soup, pizza, tomato, oil];
+        async move {
+            let found = edges
+                .of_each_node(&ids, Direction::Outgoing, page)
+                .await
+                .expect("edges are listed");
+            ids.map(|id| {
+                found.get(&id).map_or_else(Vec::new, |edges| {
+                    edges.iter().map(|edge| edge.target_node_id).collect()
+                })
+            })
+        }
+    };
+    let page = |limit, offset| Page {
+        limit: Some(limit),
+        offset,
+    };
+    assert_eq!(
+        targets(page(2, 0)).await,
+        [vec![oil, basil], vec![tomato], vec![tomato], vec![]]
+    );
+    assert_eq!(
+        targets(page(2, 1)).await,
+        [vec![basil, tomato], vec![], vec![], vec![]]
+    );
+
+    let both = repositories
+        .edges
+        .of_each_node(&[tomato], Direction::Both, Page::default())
+        .await
+        .unwrap();
+    assert_eq!(both[&tomato].len(), 3, "the loop is listed once");
+}
+
+#[tokio::test]
+async fn delete_schema_removes_its_nodes_and_their_edges() {
+    let repositories = Repositories::new(storage());
+    add_schema(&repositories, food()).await;
+    add_schema(
+        &repositories,
+        json!({ "title": "Ingredient", "type": "object" }),
+    )
+    .await;
+    add_schema(
+        &repositories,
+        json!({ "title": "Dessert", "x-extends": "Food", "type": "object" }),
+    )
+    .await;
+    let lasagne = add_node(&repositories, "Food", "Lasagne", json!({})).await;
+    let pizza = add_node(&repositories, "Food", "Pizza", json!({})).await;
+    let tomato = add_node(&repositories, "Ingredient", "Tomato", json!({})).await;
+    add_edge(&repositories, lasagne, tomato, "has-ingredient").await;
+    add_edge(&repositories, pizza, tomato, "has-ingredient").await;
+    add_edge(&repositories, lasagne, pizza, "goes-with").await;
+
+    let error = repositories.schemas.delete("Food").await.unwrap_err();
+    assert!(
+        matches!(&error, Error::ForeignKeyViolation(message) if message == "schema 'Food' is still used by 'Dessert'"),
+        "{error:?}"
+    );
+
+    assert!(repositories.schemas.delete("Ingredient").await.unwrap());
+    assert!(
+        repositories
+            .schemas
+            .get("Ingredient")
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(repositories.nodes.get(tomato).await.unwrap().is_none());
+    let remaining = repositories
+        .nodes
+        .list(&NodeFilter::default(), Page::default())
+        .await
+        .unwrap();
+    assert_eq!(names(remaining), ["Lasagne", "Pizza"]);
+    assert_eq!(
+        edges(&repositories).await,
+        [(lasagne, pizza, "goes-with".to_string())]
+    );
+    assert_eq!(
+        repositories
+            .edges
+            .count_of_node(lasagne, Direction::Both)
+            .await
+            .unwrap(),
+        1
+    );
+
+    assert!(!repositories.schemas.delete("Ingredient").await.unwrap());
+}
+
+#[tokio::test]
+async fn created_nodes_carry_their_name_in_data() {
+    let repositories = Repositories::new(storage());
+    add_schema(
+        &repositories,
+        json!({
+            "title": "Strict",
+            "type": "object",
+            "properties": { "name": { "type": "string" } },
+            "required": ["name"],
+            "additionalProperties": false
+        }),
+    )
+    .await;
+
+    let created = repositories
+        .nodes
+        .create(new_node("Strict", "Apple", json!({})), None)
+        .await
+        .expect("the name satisfies `required`");
+    assert_eq!(created.node.data, json!({ "name": "Apple" }));
+    let created = repositories
+        .nodes
+        .create(new_node("Strict", "Pear", json!({ "name": "Pyrus" })), None)
+        .await
+        .expect("node is stored");
+    assert_eq!(created.node.data, json!({ "name": "Pyrus" }));
+}
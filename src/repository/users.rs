@@ -0,0 +1,98 @@
+use crate::{auth, error::Error, model::DbUser};
+
+/// A user and their password in plain text, which is only stored hashed.
+#[derive(Clone)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+    pub admin: bool,
+}
+
+#[derive(Clone)]
+pub struct UserRepository {
+    pool: sqlx::PgPool,
+}
+
+impl UserRepository {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        UserRepository { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<DbUser>, Error> {
+        let users = sqlx::query_as!(
+            DbUser,
+            r#"
+            SELECT id, username, admin, created_at
+            FROM users
+            ORDER BY username
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    pub async fn get(&self, username: &str) -> Result<Option<DbUser>, Error> {
+        let user = sqlx::query_as!(
+            DbUser,
+            r#"
+            SELECT id, username, admin, created_at
+            FROM users
+            WHERE username = $1
+            "#,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Store a user with the password hashed by
+    /// [`hash_password`](auth::hash_password).
+    pub async fn create(&self, user: NewUser) -> Result<DbUser, Error> {
+        let password_hash = auth::hash_password(&user.password)?;
+        let user = sqlx::query_as!(
+            DbUser,
+            r#"
+            INSERT INTO users (username, password_hash, admin)
+            VALUES ($1, $2, $3)
+            RETURNING id, username, admin, created_at
+            "#,
+            user.username,
+            password_hash,
+            user.admin
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// The user, if `password` is theirs.
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<DbUser, Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, username, password_hash, admin, created_at
+            FROM users
+            WHERE username = $1
+            "#,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) if auth::verify_password(password, &row.password_hash) => Ok(DbUser {
+                id: row.id,
+                username: row.username,
+                admin: row.admin,
+                created_at: row.created_at,
+            }),
+            _ => Err(Error::Unauthorized(
+                "unknown username or wrong password".to_string(),
+            )),
+        }
+    }
+}
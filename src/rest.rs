@@ -0,0 +1,634 @@
+//! REST resources for schemas, nodes and edges, backed by the same
+//! [repositories](crate::repository) as the GraphQL resolvers, and the OpenAPI document generated from
+//! the handlers in this crate.
+//!
+//! Listings are paginated with `limit` and `offset`, link to the next page in
+//! a `Link` header and, like single resources, carry an `ETag` that makes a
+//! repeated `GET` with `If-None-Match` answer `304 Not Modified`.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{FromRef, Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    error::Error,
+    model::{DbCreatedNode, DbEdge, DbNode, DbSchema},
+    repository::{
+        self, Direction, EdgeRepository, NewEdge, NewNode, NewSchema, NodeRepository, Page,
+        SchemaRepository,
+    },
+    storage::SharedStorage,
+};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/// The characters escaped in a path segment of a `Location`: the URL
+/// standard's path percent-encode set, `/` and `%`.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'%');
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "LixIv",
+        description = "Inventory for displaying a horizontal datastructure",
+        contact(name = "Squad Mandalore", url = "https://github.com/Squad-Mandalore"),
+        license(name = "AGPL-3.0-only", identifier = "AGPL-3.0-only")
+    ),
+    servers((url = "http://localhost:3000", description = "Debug")),
+    paths(
+        list_schemas,
+        create_schema,
+        get_schema,
+        delete_schema,
+        list_nodes,
+        create_node,
+        get_node,
+        delete_node,
+        node_edges,
+        list_edges,
+        create_edge,
+        get_edge,
+        delete_edge,
+        openapi_handler,
+        crate::graphql::graphql_handler,
+        crate::graphql::typed::typed_graphql_handler,
+        crate::import::import_handler,
+        crate::import::import_csv_handler,
+        crate::export::export_handler,
+        crate::export::rdf::rdf_handler,
+    ),
+    components(schemas(ErrorBody, Direction))
+)]
+pub struct ApiDoc;
+
+impl FromRef<SharedStorage> for SchemaRepository {
+    fn from_ref(storage: &SharedStorage) -> Self {
+        SchemaRepository::new(storage.clone())
+    }
+}
+
+impl FromRef<SharedStorage> for NodeRepository {
+    fn from_ref(storage: &SharedStorage) -> Self {
+        NodeRepository::new(storage.clone())
+    }
+}
+
+impl FromRef<SharedStorage> for EdgeRepository {
+    fn from_ref(storage: &SharedStorage) -> Self {
+        EdgeRepository::new(storage.clone())
+    }
+}
+
+pub fn router() -> Router<SharedStorage> {
+    Router::new()
+        .route("/schemas", get(list_schemas).post(create_schema))
+        .route("/schemas/{title}", get(get_schema).delete(delete_schema))
+        .route("/nodes", get(list_nodes).post(create_node))
+        .route("/nodes/{id}", get(get_node).delete(delete_node))
+        .route("/nodes/{id}/edges", get(node_edges))
+        .route("/edges", get(list_edges).post(create_edge))
+        .route("/edges/{id}", get(get_edge).delete(delete_edge))
+        .route("/openapi.json", get(openapi_handler))
+}
+
+/// `{ "errors": [{ "message": ..., "code": ... }] }`, the body of every
+/// [`Error`] response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    errors: Vec<ErrorMessage>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorMessage {
+    message: String,
+    /// `NOT_FOUND`, `UNIQUE_VIOLATION`, `FOREIGN_KEY_VIOLATION`,
+    /// `VALIDATION_FAILED`, `UNAUTHORIZED` or `INTERNAL`.
+    code: String,
+}
+
+/// One page of a listing.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Paginated<T> {
+    items: Vec<T>,
+    total: i64,
+    limit: i64,
+    offset: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    /// At most this many items, 50 by default and 500 at most.
+    limit: Option<i64>,
+    /// Number of items to skip.
+    offset: Option<i64>,
+}
+
+impl PageParams {
+    fn page(&self) -> Result<Page, Error> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let offset = self.offset.unwrap_or(0);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+            return Err(Error::ValidationFailed(format!(
+                "limit must be within 1..={MAX_PAGE_SIZE} and offset must not be negative"
+            )));
+        }
+        Ok(Page {
+            limit: Some(limit),
+            offset,
+        })
+    }
+}
+
+/// A weak ETag over the serialized body.
+fn etag(body: &[u8]) -> HeaderValue {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    HeaderValue::from_str(&format!("W/\"{:016x}\"", hasher.finish()))
+        .expect("hex digits are a valid header value")
+}
+
+/// `value` as JSON with an ETag, or `304 Not Modified` when the client
+/// already has it.
+fn json_with_etag<T: Serialize>(request: &HeaderMap, status: StatusCode, value: &T) -> Response {
+    let body = match serde_json::to_vec(value) {
+        Ok(body) => body,
+        Err(e) => return Error::internal(e).into_response(),
+    };
+    let tag = etag(&body);
+    let cached = request
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|candidate| candidate.trim() == "*" || candidate.trim() == tag)
+        });
+    if cached && status == StatusCode::OK {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, tag)]).into_response();
+    }
+    (
+        status,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            ),
+            (header::ETAG, tag),
+        ],
+        Body::from(body),
+    )
+        .into_response()
+}
+
+/// A page with a `Link` to the next one, keeping the other query parameters.
+fn paginated<T: Serialize>(
+    request: &HeaderMap,
+    uri: &Uri,
+    items: Vec<T>,
+    total: i64,
+    page: Page,
+) -> Response {
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let next = page.offset + limit;
+    let mut response = json_with_etag(
+        request,
+        StatusCode::OK,
+        &Paginated {
+            items,
+            total,
+            limit,
+            offset: page.offset,
+        },
+    );
+    if next < total {
+        let mut query: Vec<&str> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty() && !pair.starts_with("offset="))
+            .collect();
+        let offset = format!("offset={next}");
+        query.push(&offset);
+        let link = format!("<{}?{}>; rel=\"next\"", uri.path(), query.join("&"));
+        if let Ok(link) = HeaderValue::from_str(&link) {
+            response.headers_mut().insert(header::LINK, link);
+        }
+    }
+    response
+}
+
+fn created<T: Serialize>(request: &HeaderMap, location: String, value: &T) -> Response {
+    let mut response = json_with_etag(request, StatusCode::CREATED, value);
+    if let Ok(location) = HeaderValue::from_str(&location) {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    response
+}
+
+fn deleted(found: bool, what: String) -> Result<Response, Error> {
+    if found {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Err(Error::NotFound(format!("{what} does not exist")))
+    }
+}
+
+fn found<T: Serialize>(
+    request: &HeaderMap,
+    value: Option<T>,
+    what: String,
+) -> Result<Response, Error> {
+    match value {
+        Some(value) => Ok(json_with_etag(request, StatusCode::OK, &value)),
+        None => Err(Error::NotFound(format!("{what} does not exist"))),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/schemas",
+    tag = "schemas",
+    params(PageParams),
+    responses(
+        (status = 200, description = "A page of schemas, newest first", body = Paginated<DbSchema>),
+        (status = 304, description = "Unchanged since the given ETag"),
+        (status = 422, description = "Invalid pagination", body = ErrorBody),
+    )
+)]
+async fn list_schemas(
+    State(schemas): State<SchemaRepository>,
+    Query(params): Query<PageParams>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, Error> {
+    let page = params.page()?;
+    let total = schemas.count().await?;
+    let schemas = schemas.list(page).await?;
+    Ok(paginated(&headers, &uri, schemas, total, page))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSchema {
+    title: String,
+    schema_json: Value,
+}
+
+#[utoipa::path(
+    post,
+    path = "/schemas",
+    tag = "schemas",
+    request_body = CreateSchema,
+    responses(
+        (status = 201, description = "The stored schema", body = DbSchema),
+        (status = 409, description = "The title or `$id` is taken", body = ErrorBody),
+        (status = 422, description = "The schema does not compile", body = ErrorBody),
+    )
+)]
+async fn create_schema(
+    State(schemas): State<SchemaRepository>,
+    headers: HeaderMap,
+    Json(body): Json<CreateSchema>,
+) -> Result<Response, Error> {
+    let schema = NewSchema {
+        title: body.title,
+        schema_json: body.schema_json,
+    };
+    let schema = schemas.create(schema).await?;
+    Ok(created(
+        &headers,
+        format!(
+            "/schemas/{}",
+            utf8_percent_encode(&schema.title, PATH_SEGMENT)
+        ),
+        &schema,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/schemas/{title}",
+    tag = "schemas",
+    params(("title" = String, Path, description = "Schema title")),
+    responses(
+        (status = 200, description = "The schema", body = DbSchema),
+        (status = 304, description = "Unchanged since the given ETag"),
+        (status = 404, description = "No such schema", body = ErrorBody),
+    )
+)]
+async fn get_schema(
+    State(schemas): State<SchemaRepository>,
+    Path(title): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let schema = schemas.get(&title).await?;
+    found(&headers, schema, format!("schema '{title}'"))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/schemas/{title}",
+    tag = "schemas",
+    params(("title" = String, Path, description = "Schema title")),
+    responses(
+        (status = 204, description = "The schema and its nodes were deleted"),
+        (status = 404, description = "No such schema", body = ErrorBody),
+        (status = 409, description = "Other schemas extend or refer to it", body = ErrorBody),
+    )
+)]
+async fn delete_schema(
+    State(schemas): State<SchemaRepository>,
+    Path(title): Path<String>,
+) -> Result<Response, Error> {
+    let found = schemas.delete(&title).await?;
+    deleted(found, format!("schema '{title}'"))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeFilter {
+    /// Only nodes of this schema.
+    schema_title: Option<String>,
+    /// Also nodes of schemas extending `schemaTitle`.
+    #[serde(default)]
+    include_subtypes: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/nodes",
+    tag = "nodes",
+    params(NodeFilter, PageParams),
+    responses(
+        (status = 200, description = "A page of nodes, newest first", body = Paginated<DbNode>),
+        (status = 304, description = "Unchanged since the given ETag"),
+        (status = 422, description = "Invalid pagination", body = ErrorBody),
+    )
+)]
+async fn list_nodes(
+    State(nodes): State<NodeRepository>,
+    Query(filter): Query<NodeFilter>,
+    Query(params): Query<PageParams>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, Error> {
+    let page = params.page()?;
+    let filter = repository::NodeFilter {
+        schema_title: filter.schema_title,
+        include_subtypes: filter.include_subtypes,
+        order: repository::NodeOrder::Newest,
+    };
+    let total = nodes.count(&filter).await?;
+    let nodes = nodes.list(&filter, page).await?;
+    Ok(paginated(&headers, &uri, nodes, total, page))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateNode {
+    schema_title: String,
+    name: String,
+    #[serde(default)]
+    data: Value,
+    /// Also return the nodes of the schema whose names are at least this similar.
+    #[serde(default)]
+    duplicate_threshold: Option<f32>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/nodes",
+    tag = "nodes",
+    request_body = CreateNode,
+    responses(
+        (status = 201, description = "The stored node with its `nearDuplicates`", body = DbCreatedNode),
+        (status = 409, description = "The name is taken or the schema does not exist", body = ErrorBody),
+        (status = 422, description = "`data` does not satisfy the schema", body = ErrorBody),
+    )
+)]
+async fn create_node(
+    State(nodes): State<NodeRepository>,
+    headers: HeaderMap,
+    Json(body): Json<CreateNode>,
+) -> Result<Response, Error> {
+    let data = match body.data {
+        Value::Null => json!({}),
+        data => data,
+    };
+    let node = NewNode {
+        schema_title: body.schema_title,
+        name: body.name,
+        data,
+    };
+    let created_node = nodes.create(node, body.duplicate_threshold).await?;
+    Ok(created(
+        &headers,
+        format!("/nodes/{}", created_node.node.id),
+        &created_node,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/nodes/{id}",
+    tag = "nodes",
+    params(("id" = i32, Path, description = "Node id")),
+    responses(
+        (status = 200, description = "The node", body = DbNode),
+        (status = 304, description = "Unchanged since the given ETag"),
+        (status = 404, description = "No such node", body = ErrorBody),
+    )
+)]
+async fn get_node(
+    State(nodes): State<NodeRepository>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let node = nodes.get(id).await?;
+    found(&headers, node, format!("node {id}"))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/nodes/{id}",
+    tag = "nodes",
+    params(("id" = i32, Path, description = "Node id")),
+    responses(
+        (status = 204, description = "The node and its edges were deleted"),
+        (status = 404, description = "No such node", body = ErrorBody),
+    )
+)]
+async fn delete_node(
+    State(nodes): State<NodeRepository>,
+    Path(id): Path<i32>,
+) -> Result<Response, Error> {
+    let found = nodes.delete(id).await?;
+    deleted(found, format!("node {id}"))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EdgeFilter {
+    /// `outgoing`, `incoming` or `both` (the default).
+    #[serde(default)]
+    direction: Direction,
+}
+
+#[utoipa::path(
+    get,
+    path = "/nodes/{id}/edges",
+    tag = "nodes",
+    params(("id" = i32, Path, description = "Node id"), EdgeFilter, PageParams),
+    responses(
+        (status = 200, description = "A page of the edges of the node, newest first", body = Paginated<DbEdge>),
+        (status = 304, description = "Unchanged since the given ETag"),
+        (status = 404, description = "No such node", body = ErrorBody),
+        (status = 422, description = "Invalid pagination", body = ErrorBody),
+    )
+)]
+async fn node_edges(
+    State(nodes): State<NodeRepository>,
+    State(edges): State<EdgeRepository>,
+    Path(id): Path<i32>,
+    Query(filter): Query<EdgeFilter>,
+    Query(params): Query<PageParams>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, Error> {
+    let page = params.page()?;
+    if nodes.get(id).await?.is_none() {
+        return Err(Error::NotFound(format!("node {id} does not exist")));
+    }
+    let total = edges.count_of_node(id, filter.direction).await?;
+    let edges = edges.of_node(id, filter.direction, page).await?;
+    Ok(paginated(&headers, &uri, edges, total, page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/edges",
+    tag = "edges",
+    params(PageParams),
+    responses(
+        (status = 200, description = "A page of edges, newest first", body = Paginated<DbEdge>),
+        (status = 304, description = "Unchanged since the given ETag"),
+        (status = 422, description = "Invalid pagination", body = ErrorBody),
+    )
+)]
+async fn list_edges(
+    State(edges): State<EdgeRepository>,
+    Query(params): Query<PageParams>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, Error> {
+    let page = params.page()?;
+    let total = edges.count().await?;
+    let edges = edges.list(page).await?;
+    Ok(paginated(&headers, &uri, edges, total, page))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateEdge {
+    source_node_id: i32,
+    target_node_id: i32,
+    weight: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/edges",
+    tag = "edges",
+    request_body = CreateEdge,
+    responses(
+        (status = 201, description = "The stored edge", body = DbEdge),
+        (status = 409, description = "The edge exists or an endpoint does not", body = ErrorBody),
+    )
+)]
+async fn create_edge(
+    State(edges): State<EdgeRepository>,
+    headers: HeaderMap,
+    Json(body): Json<CreateEdge>,
+) -> Result<Response, Error> {
+    let edge = NewEdge {
+        source_node_id: body.source_node_id,
+        target_node_id: body.target_node_id,
+        weight: body.weight,
+    };
+    let edge = edges.create(edge).await?;
+    Ok(created(&headers, format!("/edges/{}", edge.id), &edge))
+}
+
+#[utoipa::path(
+    get,
+    path = "/edges/{id}",
+    tag = "edges",
+    params(("id" = i32, Path, description = "Edge id")),
+    responses(
+        (status = 200, description = "The edge", body = DbEdge),
+        (status = 304, description = "Unchanged since the given ETag"),
+        (status = 404, description = "No such edge", body = ErrorBody),
+    )
+)]
+async fn get_edge(
+    State(edges): State<EdgeRepository>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let edge = edges.get(id).await?;
+    found(&headers, edge, format!("edge {id}"))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/edges/{id}",
+    tag = "edges",
+    params(("id" = i32, Path, description = "Edge id")),
+    responses(
+        (status = 204, description = "The edge was deleted"),
+        (status = 404, description = "No such edge", body = ErrorBody),
+    )
+)]
+async fn delete_edge(
+    State(edges): State<EdgeRepository>,
+    Path(id): Path<i32>,
+) -> Result<Response, Error> {
+    let found = edges.delete(id).await?;
+    deleted(found, format!("edge {id}"))
+}
+
+/// `GET /openapi.json`
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "meta",
+    responses((status = 200, description = "This document"))
+)]
+async fn openapi_handler() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -0,0 +1,190 @@
+//! Where schemas, nodes and edges are kept.
+//!
+//! The [repositories](crate::repository) validate their input and then read
+//! and write through a [`Storage`]: [`PgStorage`] keeps the graph in Postgres,
+//! [`SqliteStorage`] in a SQLite file for single-user deployments and
+//! [`MemoryStorage`] in a [`StableDiGraph`](petgraph::stable_graph::StableDiGraph)
+//! that lives as long as the process, for demos and tests that should not need
+//! a database. All of them report constraint violations with the same
+//! [`Error`]s. Only Postgres runs [pattern queries](crate::query); the others
+//! fail them with [`Error::Unsupported`].
+
+use std::sync::Arc;
+
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    backup::{Backup, RestoreMode, RestoreReport},
+    catalog::SchemaCatalog,
+    error::Error,
+    import::ImportReport,
+    model::{DbDuplicateCandidate, DbEdge, DbNode, DbSchema, DbSearchHit, DbSimilarNode},
+    query::CompiledQuery,
+    repository::{Direction, NewEdge, NewNode, NewSchema, NodeFilter, Page},
+};
+
+mod memory;
+mod postgres;
+mod sqlite;
+mod text;
+
+pub use memory::MemoryStorage;
+pub use postgres::PgStorage;
+pub use sqlite::SqliteStorage;
+
+/// A storage shared by the repositories, resolvers and handlers.
+pub type SharedStorage = Arc<dyn Storage>;
+
+/// Combines the nodes about to be merged, the survivor first, into the data
+/// the survivor keeps, or refuses the merge.
+pub type Combine<'a> = dyn Fn(&[DbNode]) -> Result<Value, Error> + Send + Sync + 'a;
+
+/// Checks the stored schemas before one is added or deleted, or refuses the
+/// write.
+pub type CheckSchemas<'a> = dyn Fn(&SchemaCatalog) -> Result<(), Error> + Send + Sync + 'a;
+
+/// The storages that fail pattern queries with [`Error::Unsupported`].
+pub const WITHOUT_GRAPH_QUERIES: &[&str] = &["sqlite", "memory"];
+
+/// A node by its schema title and name.
+pub type NodeKey = (String, String);
+
+/// Every schema, node and edge, each ordered by id, as of one moment.
+#[derive(Debug, Default, Clone)]
+pub struct Snapshot {
+    pub schemas: Vec<DbSchema>,
+    pub nodes: Vec<DbNode>,
+    pub edges: Vec<DbEdge>,
+}
+
+/// The validated rows of an import, written in one transaction.
+#[derive(Debug, Default)]
+pub struct ImportBatch {
+    /// Upserted by title in this order, parents before the schemas extending
+    /// them.
+    pub schemas: Vec<NewSchema>,
+    pub nodes: Vec<NewNode>,
+    /// Update the nodes that exist by `(schema_title, name)`; otherwise one
+    /// that exists fails the batch with [`Error::UniqueViolation`].
+    pub upsert: bool,
+    /// `(source, target, weight)` between nodes of the batch or stored ones.
+    /// Edges that exist are left alone.
+    pub edges: Vec<(NodeKey, NodeKey, String)>,
+}
+
+/// Counters bumped by every write, for caches of values computed from the
+/// graph.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GraphVersion {
+    /// Bumped by writes to schemas, nodes or edges.
+    pub graph: i64,
+    /// Bumped by writes to schemas only.
+    pub schemas: i64,
+}
+
+/// Schema, node and edge storage. Listings are newest first; lookups of many
+/// rows return them in no particular order and skip those that do not exist.
+/// Every row gets a version 7 UUID as its public `uid` when it is inserted.
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    async fn version(&self) -> Result<GraphVersion, Error>;
+
+    async fn list_schemas(&self, page: Page) -> Result<Vec<DbSchema>, Error>;
+    async fn count_schemas(&self) -> Result<i64, Error>;
+    async fn get_schemas(&self, titles: &[String]) -> Result<Vec<DbSchema>, Error>;
+    async fn get_schema_by_uid(&self, uid: Uuid) -> Result<Option<DbSchema>, Error>;
+    /// Every stored schema, for validation.
+    async fn catalog(&self) -> Result<SchemaCatalog, Error>;
+    /// Store a schema once `check` accepts the stored schemas. No other
+    /// schema is written between the check and the insert.
+    async fn insert_schema(
+        &self,
+        schema: NewSchema,
+        check: &CheckSchemas<'_>,
+    ) -> Result<DbSchema, Error>;
+    /// Delete a schema with its nodes and their edges once `check` accepts the
+    /// stored schemas, with no other schema written in between.
+    async fn delete_schema(&self, title: &str, check: &CheckSchemas<'_>) -> Result<bool, Error>;
+
+    async fn list_nodes(&self, filter: &NodeFilter, page: Page) -> Result<Vec<DbNode>, Error>;
+    async fn count_nodes(&self, filter: &NodeFilter) -> Result<i64, Error>;
+    async fn get_nodes(&self, ids: &[i32]) -> Result<Vec<DbNode>, Error>;
+    async fn get_nodes_by_uid(&self, uids: &[Uuid]) -> Result<Vec<DbNode>, Error>;
+    async fn find_node(&self, schema_title: &str, name: &str) -> Result<Option<DbNode>, Error>;
+    async fn find_nodes(&self, keys: &[NodeKey]) -> Result<Vec<DbNode>, Error>;
+    async fn insert_node(&self, node: NewNode) -> Result<DbNode, Error>;
+    /// Delete a node with its edges.
+    async fn delete_node(&self, id: i32) -> Result<bool, Error>;
+    /// Atomically fold `merge_ids` into `keep_id`: `combine` is given the
+    /// nodes that exist, in the order `keep_id, merge_ids..`, and the data it
+    /// returns is stored on the survivor. The edges of the merged nodes are
+    /// re-pointed to the survivor, dropping duplicates and the self-loops that
+    /// edges between them would become, and the merged nodes are deleted.
+    async fn merge_nodes(
+        &self,
+        keep_id: i32,
+        merge_ids: &[i32],
+        combine: &Combine<'_>,
+    ) -> Result<DbNode, Error>;
+    /// Full-text search over names and string properties, best first.
+    async fn search_nodes(
+        &self,
+        text: &str,
+        schema_titles: &[String],
+        limit: i64,
+    ) -> Result<Vec<DbSearchHit>, Error>;
+    /// Nodes whose lower-cased names share at least `threshold` of their
+    /// trigrams with `name`, most similar first.
+    async fn similar_nodes(
+        &self,
+        name: &str,
+        schema_title: Option<&str>,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbSimilarNode>, Error>;
+    async fn duplicate_candidates(
+        &self,
+        schema_title: &str,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbDuplicateCandidate>, Error>;
+
+    async fn list_edges(&self, page: Page) -> Result<Vec<DbEdge>, Error>;
+    async fn count_edges(&self) -> Result<i64, Error>;
+    async fn get_edge(&self, id: i32) -> Result<Option<DbEdge>, Error>;
+    async fn get_edge_by_uid(&self, uid: Uuid) -> Result<Option<DbEdge>, Error>;
+    /// The edges leaving and/or entering any of `node_ids`.
+    async fn edges_of(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<DbEdge>, Error>;
+    async fn count_edges_of(&self, node_ids: &[i32], direction: Direction) -> Result<i64, Error>;
+    /// A `page` of the edges leaving and/or entering each of `node_ids`,
+    /// newest first, with the node they were found for.
+    async fn edges_of_each(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<(i32, DbEdge)>, Error>;
+    async fn insert_edge(&self, edge: NewEdge) -> Result<DbEdge, Error>;
+    async fn delete_edge(&self, id: i32) -> Result<bool, Error>;
+
+    /// The distinct nodes reached from `start` by following outgoing edges
+    /// labelled `path[0]`, then `path[1]` and so on.
+    async fn walk(&self, start: i32, path: &[String]) -> Result<Vec<DbNode>, Error>;
+    /// The rows of a compiled pattern query, one array of values per match.
+    async fn graph_query(&self, query: &CompiledQuery) -> Result<Vec<Vec<Value>>, Error>;
+
+    /// Everything stored, read in one consistent snapshot.
+    async fn snapshot(&self) -> Result<Snapshot, Error>;
+    /// Write `batch` in one transaction, counting the schemas and nodes
+    /// written and the edges added.
+    async fn import(&self, batch: ImportBatch) -> Result<ImportReport, Error>;
+    /// Write `backup` in one transaction, as `mode` says. Every edge of the
+    /// backup refers to nodes in it.
+    async fn restore(&self, backup: &Backup, mode: RestoreMode) -> Result<RestoreReport, Error>;
+}
@@ -0,0 +1,899 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use super::{
+    CheckSchemas, Combine, GraphVersion, ImportBatch, NodeKey, Snapshot, Storage,
+    WITHOUT_GRAPH_QUERIES, text,
+};
+use crate::{
+    backup::{Backup, RestoreMode, RestoreReport, new_public_id, public_id},
+    catalog::{self, SchemaCatalog},
+    error::Error,
+    import::ImportReport,
+    model::{
+        AddDedup, DbDuplicateCandidate, DbEdge, DbNode, DbSchema, DbSearchHit, DbSimilarNode,
+        NodeInstance,
+    },
+    query::CompiledQuery,
+    repository::{Direction, NewEdge, NewNode, NewSchema, NodeFilter, NodeOrder, Page},
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    stable_graph::StableDiGraph,
+    visit::EdgeRef,
+};
+use uuid::Uuid;
+
+/// The graph in memory, gone when the process exits.
+///
+/// Nodes and edges live in a [`StableDiGraph`], so their indices survive
+/// deletions; ids and timestamps are kept next to it. Constraints are checked
+/// like the database does, [`AddDedup`] refusing nodes and edges that already
+/// exist. Imports and restores are written to a copy of the graph that only
+/// replaces it once everything was written, so that a failure leaves nothing
+/// behind.
+#[derive(Default)]
+pub struct MemoryStorage {
+    graph: RwLock<Graph>,
+}
+
+#[derive(Default, Clone)]
+struct Graph {
+    /// In the order they were created.
+    schemas: Vec<DbSchema>,
+    graph: StableDiGraph<NodeInstance, String>,
+    nodes: BTreeMap<i32, NodeIndex>,
+    node_rows: HashMap<NodeIndex, Row>,
+    edges: BTreeMap<i32, EdgeIndex>,
+    edge_rows: HashMap<EdgeIndex, Row>,
+    last_schema_id: i32,
+    last_node_id: i32,
+    last_edge_id: i32,
+    version: GraphVersion,
+}
+
+/// The columns of a node or edge that are not part of the graph.
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    id: i32,
+    uid: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Row {
+    fn new(id: i32) -> Self {
+        let now = Utc::now();
+        Row {
+            id,
+            uid: Uuid::now_v7(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// The row of a restored schema, node or edge, created at `created_at` or
+    /// now, with a new public id unless it has `uid`.
+    fn restored(
+        id: i32,
+        uid: Option<Uuid>,
+        created_at: Option<DateTime<Utc>>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        let created_at = created_at.unwrap_or_else(Utc::now);
+        Row {
+            id,
+            uid: uid.unwrap_or_else(|| new_public_id(Some(created_at))),
+            created_at,
+            updated_at: updated_at.unwrap_or(created_at),
+        }
+    }
+}
+
+impl MemoryStorage {
+    fn read(&self) -> RwLockReadGuard<'_, Graph> {
+        self.graph.read().expect("memory storage poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Graph> {
+        self.graph.write().expect("memory storage poisoned")
+    }
+}
+
+impl Graph {
+    fn bump(&mut self, schemas: bool) {
+        self.version.graph += 1;
+        if schemas {
+            self.version.schemas += 1;
+        }
+    }
+
+    fn schema(&self, title: &str) -> Option<&DbSchema> {
+        self.schemas.iter().find(|schema| schema.title == title)
+    }
+
+    fn find(&self, schema_title: &str, name: &str) -> Option<NodeIndex> {
+        self.nodes.values().copied().find(|index| {
+            let instance = &self.graph[*index];
+            instance.schema() == schema_title && instance.name() == name
+        })
+    }
+
+    /// Add a schema as `row` says, or update the one stored under its title
+    /// when `upsert` is set.
+    fn add_schema(&mut self, schema: NewSchema, row: Row, upsert: bool) -> Result<DbSchema, Error> {
+        let stored = self
+            .schemas
+            .iter()
+            .position(|stored| stored.title == schema.title);
+        if stored.is_some() && !upsert {
+            return Err(Error::UniqueViolation {
+                key: format!("(title)=({})", schema.title),
+            });
+        }
+        if let Some(id) = schema.schema_json.get("$id")
+            && self.schemas.iter().any(|other| {
+                other.title != schema.title && other.schema_json.get("$id") == Some(id)
+            })
+        {
+            return Err(Error::UniqueViolation {
+                key: format!(
+                    "(schema_json ->> '$id')=({})",
+                    id.as_str().unwrap_or_default()
+                ),
+            });
+        }
+        if let Some(parent) = catalog::parent_title(&schema.schema_json)
+            && self.schema(parent).is_none()
+        {
+            return Err(Error::ForeignKeyViolation(format!(
+                "schema '{parent}' does not exist"
+            )));
+        }
+
+        if let Some(position) = stored {
+            let stored = &mut self.schemas[position];
+            stored.schema_json = schema.schema_json;
+            stored.updated_at = Some(Utc::now());
+            return Ok(stored.clone());
+        }
+        let schema = DbSchema {
+            id: row.id,
+            uid: row.uid,
+            title: schema.title,
+            schema_json: schema.schema_json,
+            created_at: Some(row.created_at),
+            updated_at: Some(row.updated_at),
+        };
+        self.last_schema_id = self.last_schema_id.max(row.id);
+        self.schemas.push(schema.clone());
+        Ok(schema)
+    }
+
+    /// Add a node as `row` says, or update the data of the one stored under
+    /// its schema and name when `upsert` is set.
+    fn add_node(&mut self, node: NewNode, row: Row, upsert: bool) -> Result<NodeIndex, Error> {
+        if self.schema(&node.schema_title).is_none() {
+            return Err(Error::ForeignKeyViolation(format!(
+                "schema '{}' does not exist",
+                node.schema_title
+            )));
+        }
+        let key = format!(
+            "(schema_title, name)=({}, {})",
+            node.schema_title, node.name
+        );
+        let instance = NodeInstance::with_data(node.schema_title, node.name, node.data)
+            .ok_or_else(|| Error::ValidationFailed("data must be a JSON object".to_string()))?;
+
+        let count = self.graph.node_count();
+        let index = self.graph.add_node_s(instance.clone());
+        if self.graph.node_count() == count {
+            if !upsert {
+                return Err(Error::UniqueViolation { key });
+            }
+            self.graph[index] = instance;
+            if let Some(stored) = self.node_rows.get_mut(&index) {
+                stored.updated_at = Utc::now();
+            }
+            return Ok(index);
+        }
+        self.last_node_id = self.last_node_id.max(row.id);
+        self.nodes.insert(row.id, index);
+        self.node_rows.insert(index, row);
+        Ok(index)
+    }
+
+    fn catalog(&self) -> SchemaCatalog {
+        let mut catalog = SchemaCatalog::default();
+        for schema in &self.schemas {
+            catalog.insert(&schema.title, schema.schema_json.clone());
+        }
+        catalog
+    }
+
+    fn node(&self, index: NodeIndex) -> DbNode {
+        let instance = &self.graph[index];
+        let row = self.node_rows[&index];
+        DbNode {
+            id: row.id,
+            uid: row.uid,
+            schema_title: instance.schema().clone(),
+            name: instance.name().to_string(),
+            data: instance.value().clone(),
+            created_at: Some(row.created_at),
+            updated_at: Some(row.updated_at),
+        }
+    }
+
+    fn edge(&self, index: EdgeIndex) -> DbEdge {
+        let (source, target) = self
+            .graph
+            .edge_endpoints(index)
+            .expect("edge rows only refer to existing edges");
+        DbEdge {
+            id: self.edge_rows[&index].id,
+            uid: self.edge_rows[&index].uid,
+            source_node_id: self.node_rows[&source].id,
+            target_node_id: self.node_rows[&target].id,
+            weight: self.graph[index].clone(),
+            created_at: Some(self.edge_rows[&index].created_at),
+        }
+    }
+
+    /// The schema titles matching `filter`, `None` for all.
+    fn kinds(&self, filter: &NodeFilter) -> Option<HashSet<String>> {
+        let title = filter.schema_title.as_ref()?;
+        let mut kinds = HashSet::new();
+        if self.schema(title).is_some() {
+            kinds.insert(title.clone());
+        }
+        if !filter.include_subtypes {
+            return Some(kinds);
+        }
+        loop {
+            let extending: Vec<String> = self
+                .schemas
+                .iter()
+                .filter(|schema| {
+                    !kinds.contains(&schema.title)
+                        && catalog::parent_title(&schema.schema_json)
+                            .is_some_and(|parent| kinds.contains(parent))
+                })
+                .map(|schema| schema.title.clone())
+                .collect();
+            if extending.is_empty() {
+                break;
+            }
+            kinds.extend(extending);
+        }
+        Some(kinds)
+    }
+
+    /// The nodes matching `filter`, in its order.
+    fn filtered_nodes(&self, filter: &NodeFilter) -> Vec<DbNode> {
+        let kinds = self.kinds(filter);
+        let mut nodes: Vec<DbNode> = self
+            .nodes
+            .values()
+            .filter(|index| {
+                kinds
+                    .as_ref()
+                    .is_none_or(|kinds| kinds.contains(self.graph[**index].schema()))
+            })
+            .map(|index| self.node(*index))
+            .collect();
+        nodes.sort_by_key(|node| std::cmp::Reverse((node.created_at, node.id)));
+        if filter.order == NodeOrder::Name {
+            // stable, so nodes of the same name stay newest first
+            nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        nodes
+    }
+
+    fn nodes_of<'a>(&'a self, schema_title: Option<&'a str>) -> impl Iterator<Item = DbNode> + 'a {
+        self.nodes
+            .values()
+            .filter(move |index| {
+                schema_title.is_none_or(|title| self.graph[**index].schema() == title)
+            })
+            .map(|index| self.node(*index))
+    }
+
+    /// Remove a node together with its edges.
+    fn remove_node(&mut self, index: NodeIndex) {
+        let edges: Vec<EdgeIndex> = self
+            .graph
+            .edges_directed(index, petgraph::Direction::Outgoing)
+            .chain(
+                self.graph
+                    .edges_directed(index, petgraph::Direction::Incoming),
+            )
+            .map(|edge| edge.id())
+            .collect();
+        for edge in edges {
+            if let Some(row) = self.edge_rows.remove(&edge) {
+                self.edges.remove(&row.id);
+            }
+        }
+        if let Some(row) = self.node_rows.remove(&index) {
+            self.nodes.remove(&row.id);
+        }
+        self.graph.remove_node(index);
+    }
+
+    /// Add an edge as `row` says unless the same one exists.
+    fn add_edge(
+        &mut self,
+        source: NodeIndex,
+        target: NodeIndex,
+        weight: String,
+        row: Row,
+    ) -> Option<EdgeIndex> {
+        let count = self.graph.edge_count();
+        let index = self.graph.add_edge_s(source, target, weight);
+        if self.graph.edge_count() == count {
+            return None;
+        }
+        self.last_edge_id = self.last_edge_id.max(row.id);
+        self.edges.insert(row.id, index);
+        self.edge_rows.insert(index, row);
+        Some(index)
+    }
+}
+
+fn paginate<T>(items: Vec<T>, page: Page) -> Vec<T> {
+    let items = items.into_iter().skip(page.offset.max(0) as usize);
+    match page.limit {
+        Some(limit) => items.take(limit.max(0) as usize).collect(),
+        None => items.collect(),
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for MemoryStorage {
+    async fn version(&self) -> Result<GraphVersion, Error> {
+        Ok(self.read().version)
+    }
+
+    async fn list_schemas(&self, page: Page) -> Result<Vec<DbSchema>, Error> {
+        let schemas = self.read().schemas.iter().rev().cloned().collect();
+        Ok(paginate(schemas, page))
+    }
+
+    async fn count_schemas(&self) -> Result<i64, Error> {
+        Ok(self.read().schemas.len() as i64)
+    }
+
+    async fn get_schemas(&self, titles: &[String]) -> Result<Vec<DbSchema>, Error> {
+        Ok(self
+            .read()
+            .schemas
+            .iter()
+            .filter(|schema| titles.contains(&schema.title))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_schema_by_uid(&self, uid: Uuid) -> Result<Option<DbSchema>, Error> {
+        Ok(self
+            .read()
+            .schemas
+            .iter()
+            .find(|schema| schema.uid == uid)
+            .cloned())
+    }
+
+    async fn catalog(&self) -> Result<SchemaCatalog, Error> {
+        Ok(self.read().catalog())
+    }
+
+    async fn insert_schema(
+        &self,
+        schema: NewSchema,
+        check: &CheckSchemas<'_>,
+    ) -> Result<DbSchema, Error> {
+        let mut graph = self.write();
+        check(&graph.catalog())?;
+        let row = Row::new(graph.last_schema_id + 1);
+        let schema = graph.add_schema(schema, row, false)?;
+        graph.bump(true);
+        Ok(schema)
+    }
+
+    async fn delete_schema(&self, title: &str, check: &CheckSchemas<'_>) -> Result<bool, Error> {
+        let mut graph = self.write();
+        check(&graph.catalog())?;
+        let Some(position) = graph
+            .schemas
+            .iter()
+            .position(|schema| schema.title == title)
+        else {
+            return Ok(false);
+        };
+        graph.schemas.remove(position);
+        let nodes: Vec<NodeIndex> = graph
+            .nodes
+            .values()
+            .copied()
+            .filter(|index| graph.graph[*index].schema() == title)
+            .collect();
+        for index in nodes {
+            graph.remove_node(index);
+        }
+        graph.bump(true);
+        Ok(true)
+    }
+
+    async fn list_nodes(&self, filter: &NodeFilter, page: Page) -> Result<Vec<DbNode>, Error> {
+        Ok(paginate(self.read().filtered_nodes(filter), page))
+    }
+
+    async fn count_nodes(&self, filter: &NodeFilter) -> Result<i64, Error> {
+        Ok(self.read().filtered_nodes(filter).len() as i64)
+    }
+
+    async fn get_nodes(&self, ids: &[i32]) -> Result<Vec<DbNode>, Error> {
+        let graph = self.read();
+        Ok(ids
+            .iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|id| graph.nodes.get(id))
+            .map(|index| graph.node(*index))
+            .collect())
+    }
+
+    async fn get_nodes_by_uid(&self, uids: &[Uuid]) -> Result<Vec<DbNode>, Error> {
+        let graph = self.read();
+        Ok(graph
+            .node_rows
+            .iter()
+            .filter(|(_, row)| uids.contains(&row.uid))
+            .map(|(index, _)| graph.node(*index))
+            .collect())
+    }
+
+    async fn find_node(&self, schema_title: &str, name: &str) -> Result<Option<DbNode>, Error> {
+        let graph = self.read();
+        Ok(graph
+            .find(schema_title, name)
+            .map(|index| graph.node(index)))
+    }
+
+    async fn find_nodes(&self, keys: &[NodeKey]) -> Result<Vec<DbNode>, Error> {
+        let graph = self.read();
+        let keys: HashSet<(&str, &str)> = keys
+            .iter()
+            .map(|(schema_title, name)| (schema_title.as_str(), name.as_str()))
+            .collect();
+        Ok(graph
+            .nodes
+            .values()
+            .filter(|index| {
+                let instance = &graph.graph[**index];
+                keys.contains(&(instance.schema().as_str(), instance.name()))
+            })
+            .map(|index| graph.node(*index))
+            .collect())
+    }
+
+    async fn insert_node(&self, node: NewNode) -> Result<DbNode, Error> {
+        let mut graph = self.write();
+        let row = Row::new(graph.last_node_id + 1);
+        let index = graph.add_node(node, row, false)?;
+        graph.bump(false);
+        Ok(graph.node(index))
+    }
+
+    async fn delete_node(&self, id: i32) -> Result<bool, Error> {
+        let mut graph = self.write();
+        let Some(index) = graph.nodes.get(&id).copied() else {
+            return Ok(false);
+        };
+        graph.remove_node(index);
+        graph.bump(false);
+        Ok(true)
+    }
+
+    async fn merge_nodes(
+        &self,
+        keep_id: i32,
+        merge_ids: &[i32],
+        combine: &Combine<'_>,
+    ) -> Result<DbNode, Error> {
+        let mut graph = self.write();
+        let nodes: Vec<DbNode> = std::iter::once(&keep_id)
+            .chain(merge_ids)
+            .filter_map(|id| graph.nodes.get(id))
+            .map(|index| graph.node(*index))
+            .collect();
+        let data = combine(&nodes)?;
+        let keep = graph.nodes[&keep_id];
+        let merged: HashSet<NodeIndex> = merge_ids.iter().map(|id| graph.nodes[id]).collect();
+
+        // copy edges onto the survivor, dropping duplicates and the self-loops
+        // that edges between merged nodes and the survivor would become
+        let mut copies = Vec::new();
+        for index in &merged {
+            for edge in graph
+                .graph
+                .edges_directed(*index, petgraph::Direction::Outgoing)
+                .chain(
+                    graph
+                        .graph
+                        .edges_directed(*index, petgraph::Direction::Incoming),
+                )
+            {
+                let (source, target) = (edge.source(), edge.target());
+                let moved = |node: NodeIndex| if merged.contains(&node) { keep } else { node };
+                if moved(source) != moved(target) || source == target {
+                    let created_at = graph.edge_rows[&edge.id()].created_at;
+                    copies.push((
+                        moved(source),
+                        moved(target),
+                        edge.weight().clone(),
+                        created_at,
+                    ));
+                }
+            }
+        }
+        copies.sort_by_key(|copy| copy.3);
+        for (source, target, weight, created_at) in copies {
+            let row = Row {
+                created_at,
+                ..Row::new(graph.last_edge_id + 1)
+            };
+            graph.add_edge(source, target, weight, row);
+        }
+        for index in merged {
+            graph.remove_node(index);
+        }
+
+        let name = graph.graph[keep].name().to_string();
+        let schema = graph.graph[keep].schema().clone();
+        graph.graph[keep] = NodeInstance::with_data(schema, name, data)
+            .ok_or_else(|| Error::ValidationFailed("data must be a JSON object".to_string()))?;
+        if let Some(row) = graph.node_rows.get_mut(&keep) {
+            row.updated_at = Utc::now();
+        }
+        graph.bump(false);
+        Ok(graph.node(keep))
+    }
+
+    async fn search_nodes(
+        &self,
+        text: &str,
+        schema_titles: &[String],
+        limit: i64,
+    ) -> Result<Vec<DbSearchHit>, Error> {
+        let graph = self.read();
+        let nodes = graph
+            .nodes_of(None)
+            .filter(|node| schema_titles.is_empty() || schema_titles.contains(&node.schema_title))
+            .map(|node| {
+                let schema = graph.schema(&node.schema_title).map(|s| &s.schema_json);
+                (node, schema)
+            });
+        Ok(text::search(nodes, text, limit))
+    }
+
+    async fn similar_nodes(
+        &self,
+        name: &str,
+        schema_title: Option<&str>,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbSimilarNode>, Error> {
+        let graph = self.read();
+        Ok(text::similar(
+            graph.nodes_of(schema_title),
+            name,
+            threshold,
+            limit,
+        ))
+    }
+
+    async fn duplicate_candidates(
+        &self,
+        schema_title: &str,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbDuplicateCandidate>, Error> {
+        let graph = self.read();
+        let nodes: Vec<DbNode> = graph.nodes_of(Some(schema_title)).collect();
+        Ok(text::duplicates(nodes, threshold, limit))
+    }
+
+    async fn list_edges(&self, page: Page) -> Result<Vec<DbEdge>, Error> {
+        let graph = self.read();
+        let mut edges: Vec<DbEdge> = graph
+            .edges
+            .values()
+            .map(|index| graph.edge(*index))
+            .collect();
+        edges.sort_by_key(|edge| std::cmp::Reverse((edge.created_at, edge.id)));
+        Ok(paginate(edges, page))
+    }
+
+    async fn count_edges(&self) -> Result<i64, Error> {
+        Ok(self.read().edges.len() as i64)
+    }
+
+    async fn get_edge(&self, id: i32) -> Result<Option<DbEdge>, Error> {
+        let graph = self.read();
+        Ok(graph.edges.get(&id).map(|index| graph.edge(*index)))
+    }
+
+    async fn get_edge_by_uid(&self, uid: Uuid) -> Result<Option<DbEdge>, Error> {
+        let graph = self.read();
+        Ok(graph
+            .edge_rows
+            .iter()
+            .find(|(_, row)| row.uid == uid)
+            .map(|(index, _)| graph.edge(*index)))
+    }
+
+    async fn edges_of(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<DbEdge>, Error> {
+        let graph = self.read();
+        let mut sides = Vec::with_capacity(2);
+        if direction != Direction::Incoming {
+            sides.push(petgraph::Direction::Outgoing);
+        }
+        if direction != Direction::Outgoing {
+            sides.push(petgraph::Direction::Incoming);
+        }
+        let mut edges: BTreeMap<i32, DbEdge> = BTreeMap::new();
+        for index in node_ids.iter().filter_map(|id| graph.nodes.get(id)) {
+            for side in &sides {
+                for edge in graph.graph.edges_directed(*index, *side) {
+                    let edge = graph.edge(edge.id());
+                    edges.insert(edge.id, edge);
+                }
+            }
+        }
+        let mut edges: Vec<DbEdge> = edges.into_values().collect();
+        edges.sort_by_key(|edge| std::cmp::Reverse((edge.created_at, edge.id)));
+        Ok(paginate(edges, page))
+    }
+
+    async fn count_edges_of(&self, node_ids: &[i32], direction: Direction) -> Result<i64, Error> {
+        Ok(self
+            .edges_of(node_ids, direction, Page::default())
+            .await?
+            .len() as i64)
+    }
+
+    async fn edges_of_each(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<(i32, DbEdge)>, Error> {
+        let mut edges = Vec::new();
+        for node_id in node_ids {
+            let of_node = self.edges_of(&[*node_id], direction, page).await?;
+            edges.extend(of_node.into_iter().map(|edge| (*node_id, edge)));
+        }
+        Ok(edges)
+    }
+
+    async fn insert_edge(&self, edge: NewEdge) -> Result<DbEdge, Error> {
+        let mut graph = self.write();
+        let endpoint = |id: i32| {
+            graph
+                .nodes
+                .get(&id)
+                .copied()
+                .ok_or_else(|| Error::ForeignKeyViolation(format!("node {id} does not exist")))
+        };
+        let (source, target) = (
+            endpoint(edge.source_node_id)?,
+            endpoint(edge.target_node_id)?,
+        );
+        let key = format!(
+            "(source_node_id, target_node_id, weight)=({}, {}, {})",
+            edge.source_node_id, edge.target_node_id, edge.weight
+        );
+        let row = Row::new(graph.last_edge_id + 1);
+        let index = graph
+            .add_edge(source, target, edge.weight, row)
+            .ok_or(Error::UniqueViolation { key })?;
+        graph.bump(false);
+        Ok(graph.edge(index))
+    }
+
+    async fn delete_edge(&self, id: i32) -> Result<bool, Error> {
+        let mut graph = self.write();
+        let Some(index) = graph.edges.remove(&id) else {
+            return Ok(false);
+        };
+        graph.edge_rows.remove(&index);
+        graph.graph.remove_edge(index);
+        graph.bump(false);
+        Ok(true)
+    }
+
+    async fn walk(&self, start: i32, path: &[String]) -> Result<Vec<DbNode>, Error> {
+        let graph = self.read();
+        let mut reached: HashSet<NodeIndex> =
+            graph.nodes.get(&start).copied().into_iter().collect();
+        for label in path {
+            reached = reached
+                .iter()
+                .flat_map(|index| graph.graph.edges(*index))
+                .filter(|edge| edge.weight() == label)
+                .map(|edge| edge.target())
+                .collect();
+        }
+        Ok(reached.into_iter().map(|index| graph.node(index)).collect())
+    }
+    async fn graph_query(&self, _query: &CompiledQuery) -> Result<Vec<Vec<Value>>, Error> {
+        Err(Error::Unsupported {
+            feature: "pattern queries",
+            storages: WITHOUT_GRAPH_QUERIES,
+        })
+    }
+
+    async fn snapshot(&self) -> Result<Snapshot, Error> {
+        let graph = self.read();
+        let mut schemas = graph.schemas.clone();
+        schemas.sort_by_key(|schema| schema.id);
+        Ok(Snapshot {
+            schemas,
+            nodes: graph.nodes.values().map(|index| graph.node(*index)).collect(),
+            edges: graph.edges.values().map(|index| graph.edge(*index)).collect(),
+        })
+    }
+
+    async fn import(&self, batch: ImportBatch) -> Result<ImportReport, Error> {
+        let mut graph = self.write();
+        let mut next = graph.clone();
+        let mut report = ImportReport::default();
+
+        for schema in batch.schemas {
+            let row = Row::new(next.last_schema_id + 1);
+            next.add_schema(schema, row, true)?;
+            report.schemas += 1;
+        }
+        for node in batch.nodes {
+            let row = Row::new(next.last_node_id + 1);
+            next.add_node(node, row, batch.upsert)?;
+            report.nodes += 1;
+        }
+        for (source, target, weight) in batch.edges {
+            let endpoint = |(schema_title, name): &NodeKey| {
+                next.find(schema_title, name).ok_or_else(|| {
+                    Error::ForeignKeyViolation(format!(
+                        "(schema_title, name)=({schema_title}, {name}) does not exist"
+                    ))
+                })
+            };
+            let (source, target) = (endpoint(&source)?, endpoint(&target)?);
+            let row = Row::new(next.last_edge_id + 1);
+            if next.add_edge(source, target, weight, row).is_some() {
+                report.edges += 1;
+            }
+        }
+
+        next.bump(report.schemas > 0);
+        *graph = next;
+        Ok(report)
+    }
+
+    async fn restore(&self, backup: &Backup, mode: RestoreMode) -> Result<RestoreReport, Error> {
+        let mut graph = self.write();
+        if mode == RestoreMode::Empty
+            && !(graph.schemas.is_empty() && graph.nodes.is_empty() && graph.edges.is_empty())
+        {
+            return Err(Error::ValidationFailed(
+                "the storage is not empty, restore into an empty one or merge".to_string(),
+            ));
+        }
+        let mut next = graph.clone();
+        let mut report = RestoreReport::default();
+        // ids are kept in an empty storage only
+        let id = |id: i32, last_id: i32| {
+            if mode == RestoreMode::Empty {
+                id
+            } else {
+                last_id + 1
+            }
+        };
+
+        for schema in &backup.schemas {
+            match next.schema(&schema.title) {
+                Some(stored) if stored.schema_json == schema.schema_json => {
+                    report.existing_schemas += 1
+                }
+                Some(_) => {
+                    return Err(Error::ValidationFailed(format!(
+                        "schema '{}' differs from the stored one",
+                        schema.title
+                    )));
+                }
+                None => {
+                    let taken = next.schemas.iter().any(|stored| stored.uid == schema.uid);
+                    let row = Row::restored(
+                        id(schema.id, next.last_schema_id),
+                        public_id(schema.uid).filter(|_| !taken),
+                        schema.created_at,
+                        schema.updated_at,
+                    );
+                    let new = NewSchema {
+                        title: schema.title.clone(),
+                        schema_json: schema.schema_json.clone(),
+                    };
+                    next.add_schema(new, row, false)?;
+                    report.schemas += 1;
+                }
+            }
+        }
+
+        // the indices of the backup's nodes in the graph
+        let mut node_indices: HashMap<i32, NodeIndex> = HashMap::new();
+        let mut uids: HashSet<Uuid> = next.node_rows.values().map(|row| row.uid).collect();
+        for node in &backup.nodes {
+            if let Some(index) = next.find(&node.schema_title, &node.name) {
+                node_indices.insert(node.id, index);
+                report.existing_nodes += 1;
+                continue;
+            }
+            let row = Row::restored(
+                id(node.id, next.last_node_id),
+                public_id(node.uid).filter(|uid| !uids.contains(uid)),
+                node.created_at,
+                node.updated_at,
+            );
+            uids.insert(row.uid);
+            let new = NewNode {
+                schema_title: node.schema_title.clone(),
+                name: node.name.clone(),
+                data: node.data.clone(),
+            };
+            node_indices.insert(node.id, next.add_node(new, row, false)?);
+            report.nodes += 1;
+        }
+
+        let mut uids: HashSet<Uuid> = next.edge_rows.values().map(|row| row.uid).collect();
+        for edge in &backup.edges {
+            let (Some(source), Some(target)) = (
+                node_indices.get(&edge.source_node_id),
+                node_indices.get(&edge.target_node_id),
+            ) else {
+                return Err(Error::ValidationFailed(format!(
+                    "edge {} refers to a node that is not in the backup",
+                    edge.id
+                )));
+            };
+            let row = Row::restored(
+                id(edge.id, next.last_edge_id),
+                public_id(edge.uid).filter(|uid| !uids.contains(uid)),
+                edge.created_at,
+                None,
+            );
+            if next
+                .add_edge(*source, *target, edge.weight.clone(), row)
+                .is_some()
+            {
+                uids.insert(row.uid);
+                report.edges += 1;
+            } else {
+                report.existing_edges += 1;
+            }
+        }
+
+        next.bump(report.schemas > 0);
+        *graph = next;
+        Ok(report)
+    }
+}
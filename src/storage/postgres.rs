@@ -0,0 +1,1192 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::{CheckSchemas, Combine, GraphVersion, ImportBatch, NodeKey, Snapshot, Storage};
+use crate::{
+    backup::{Backup, RestoreMode, RestoreReport, public_id},
+    catalog::SchemaCatalog,
+    error::Error,
+    import::ImportReport,
+    model::{DbDuplicateCandidate, DbEdge, DbNode, DbSchema, DbSearchHit, DbSimilarNode},
+    query::{Bind, CompiledQuery, STATEMENT_TIMEOUT},
+    repository::{Direction, NewEdge, NewNode, NewSchema, NodeFilter, NodeOrder, Page},
+};
+
+/// Readers fold the recorded writing transactions into the version counters
+/// once there are more than this many.
+const FOLD_GRAPH_CHANGES: i64 = 1000;
+
+/// The graph in the Postgres database the migrations set up.
+#[derive(Clone)]
+pub struct PgStorage {
+    pool: sqlx::PgPool,
+}
+
+impl PgStorage {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        PgStorage { pool }
+    }
+
+    /// Add the committed `graph_changes` to the counters in `graph_version`,
+    /// which leaves the versions as they are. Writers do not wait for this,
+    /// and while one reader folds the others skip it.
+    async fn fold_graph_changes(&self) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        let locked = sqlx::query_scalar!(
+            r#"SELECT pg_try_advisory_xact_lock(hashtext('graph_changes')) as "locked!""#
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if locked {
+            sqlx::query!(
+                r#"
+                WITH folded AS (
+                    DELETE FROM graph_changes
+                    RETURNING schemas
+                )
+                UPDATE graph_version
+                SET version = version + (SELECT count(*) FROM folded WHERE NOT schemas),
+                    schema_version = schema_version + (SELECT count(*) FROM folded WHERE schemas)
+                "#
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for PgStorage {
+    async fn version(&self) -> Result<GraphVersion, Error> {
+        // one statement sees the counters and the changes of one snapshot
+        let row = sqlx::query!(
+            r#"
+            SELECT v.version + c.graph as "graph!",
+                   v.schema_version + c.schemas as "schemas!",
+                   c.graph + c.schemas as "changes!"
+            FROM graph_version v,
+                 (SELECT count(*) FILTER (WHERE NOT schemas) as graph,
+                         count(*) FILTER (WHERE schemas) as schemas
+                  FROM graph_changes) c
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if row.changes > FOLD_GRAPH_CHANGES {
+            self.fold_graph_changes().await?;
+        }
+        Ok(GraphVersion {
+            graph: row.graph,
+            schemas: row.schemas,
+        })
+    }
+
+    async fn list_schemas(&self, page: Page) -> Result<Vec<DbSchema>, Error> {
+        let schemas = sqlx::query_as!(
+            DbSchema,
+            r#"
+            SELECT id, uid, title, schema_json as "schema_json: Value", created_at, updated_at
+            FROM schemas
+            ORDER BY created_at DESC, id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(schemas)
+    }
+
+    async fn count_schemas(&self) -> Result<i64, Error> {
+        let count = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM schemas"#)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn get_schemas(&self, titles: &[String]) -> Result<Vec<DbSchema>, Error> {
+        let schemas = sqlx::query_as!(
+            DbSchema,
+            r#"
+            SELECT id, uid, title, schema_json as "schema_json: Value", created_at, updated_at
+            FROM schemas
+            WHERE title = ANY($1)
+            "#,
+            titles
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(schemas)
+    }
+
+    async fn get_schema_by_uid(&self, uid: Uuid) -> Result<Option<DbSchema>, Error> {
+        let schema = sqlx::query_as!(
+            DbSchema,
+            r#"
+            SELECT id, uid, title, schema_json as "schema_json: Value", created_at, updated_at
+            FROM schemas
+            WHERE uid = $1
+            "#,
+            uid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(schema)
+    }
+
+    async fn catalog(&self) -> Result<SchemaCatalog, Error> {
+        Ok(SchemaCatalog::load(&self.pool).await?)
+    }
+
+    async fn insert_schema(
+        &self,
+        schema: NewSchema,
+        check: &CheckSchemas<'_>,
+    ) -> Result<DbSchema, Error> {
+        let mut tx = self.pool.begin().await?;
+        lock_schemas(&mut tx).await?;
+        check(&SchemaCatalog::load(&mut *tx).await?)?;
+
+        let schema = sqlx::query_as!(
+            DbSchema,
+            r#"
+            INSERT INTO schemas (title, schema_json)
+            VALUES ($1, $2)
+            RETURNING id, uid, title, schema_json as "schema_json: Value", created_at, updated_at
+            "#,
+            schema.title,
+            schema.schema_json
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(schema)
+    }
+
+    async fn delete_schema(&self, title: &str, check: &CheckSchemas<'_>) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+        lock_schemas(&mut tx).await?;
+        check(&SchemaCatalog::load(&mut *tx).await?)?;
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM schemas
+            WHERE title = $1
+            "#,
+            title
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_nodes(&self, filter: &NodeFilter, page: Page) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as!(
+            DbNode,
+            r#"
+            WITH RECURSIVE kinds (title) AS (
+                SELECT title FROM schemas WHERE title = $1
+                UNION
+                SELECT s.title
+                FROM schemas s
+                JOIN kinds k ON s.extends = k.title
+                WHERE $2
+            )
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            FROM nodes
+            WHERE $1::TEXT IS NULL OR schema_title IN (SELECT title FROM kinds)
+            ORDER BY CASE WHEN $5 THEN name END, created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            filter.schema_title,
+            filter.include_subtypes,
+            page.limit,
+            page.offset,
+            filter.order == NodeOrder::Name
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes)
+    }
+
+    async fn count_nodes(&self, filter: &NodeFilter) -> Result<i64, Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+            WITH RECURSIVE kinds (title) AS (
+                SELECT title FROM schemas WHERE title = $1
+                UNION
+                SELECT s.title
+                FROM schemas s
+                JOIN kinds k ON s.extends = k.title
+                WHERE $2
+            )
+            SELECT count(*) as "count!"
+            FROM nodes
+            WHERE $1::TEXT IS NULL OR schema_title IN (SELECT title FROM kinds)
+            "#,
+            filter.schema_title,
+            filter.include_subtypes
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn get_nodes(&self, ids: &[i32]) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as!(
+            DbNode,
+            r#"
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            FROM nodes
+            WHERE id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes)
+    }
+
+    async fn get_nodes_by_uid(&self, uids: &[Uuid]) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as!(
+            DbNode,
+            r#"
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            FROM nodes
+            WHERE uid = ANY($1)
+            "#,
+            uids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes)
+    }
+
+    async fn find_node(&self, schema_title: &str, name: &str) -> Result<Option<DbNode>, Error> {
+        let node = sqlx::query_as!(
+            DbNode,
+            r#"
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            FROM nodes
+            WHERE schema_title = $1 AND name = $2
+            "#,
+            schema_title,
+            name
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(node)
+    }
+
+    async fn find_nodes(&self, keys: &[NodeKey]) -> Result<Vec<DbNode>, Error> {
+        let (schema_titles, names): (Vec<String>, Vec<String>) = keys.iter().cloned().unzip();
+        let nodes = sqlx::query_as!(
+            DbNode,
+            r#"
+            SELECT n.id, n.uid, n.schema_title, n.name, n.data as "data: Value", n.created_at,
+                   n.updated_at
+            FROM nodes n
+            JOIN UNNEST($1::text[], $2::text[]) AS k(schema_title, name)
+              ON n.schema_title = k.schema_title AND n.name = k.name
+            "#,
+            &schema_titles,
+            &names
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes)
+    }
+
+    async fn insert_node(&self, node: NewNode) -> Result<DbNode, Error> {
+        let node = sqlx::query_as!(
+            DbNode,
+            r#"
+            INSERT INTO nodes (schema_title, name, data)
+            VALUES ($1, $2, $3)
+            RETURNING id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            "#,
+            node.schema_title,
+            node.name,
+            node.data
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(node)
+    }
+
+    async fn delete_node(&self, id: i32) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM nodes
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn merge_nodes(
+        &self,
+        keep_id: i32,
+        merge_ids: &[i32],
+        combine: &Combine<'_>,
+    ) -> Result<DbNode, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut ids = vec![keep_id];
+        ids.extend(merge_ids);
+        let nodes = sqlx::query_as!(
+            DbNode,
+            r#"
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            FROM nodes
+            WHERE id = ANY($1)
+            ORDER BY array_position($1, id)
+            FOR UPDATE
+            "#,
+            &ids
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let data = combine(&nodes)?;
+
+        // copy edges onto the survivor, dropping duplicates and the self-loops
+        // that edges between merged nodes and the survivor would become
+        sqlx::query!(
+            r#"
+            INSERT INTO edges (source_node_id, target_node_id, weight, created_at)
+            SELECT source_id, target_id, weight, created_at
+            FROM (
+                SELECT CASE WHEN source_node_id = ANY($2) THEN $1 ELSE source_node_id END as source_id,
+                       CASE WHEN target_node_id = ANY($2) THEN $1 ELSE target_node_id END as target_id,
+                       source_node_id, target_node_id, weight, created_at
+                FROM edges
+                WHERE source_node_id = ANY($2) OR target_node_id = ANY($2)
+            ) e
+            WHERE source_id <> target_id OR source_node_id = target_node_id
+            ON CONFLICT (source_node_id, target_node_id, weight) DO NOTHING
+            "#,
+            keep_id,
+            merge_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM nodes
+            WHERE id = ANY($1)
+            "#,
+            merge_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let node = sqlx::query_as!(
+            DbNode,
+            r#"
+            UPDATE nodes
+            SET data = $2, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            RETURNING id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            "#,
+            keep_id,
+            data
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(node)
+    }
+
+    async fn search_nodes(
+        &self,
+        text: &str,
+        schema_titles: &[String],
+        limit: i64,
+    ) -> Result<Vec<DbSearchHit>, Error> {
+        // snippets show the properties `node_search_vector` indexes
+        let hits = sqlx::query!(
+            r#"
+            SELECT n.id, n.uid, n.schema_title, n.name, n.data as "data: Value",
+                   n.created_at, n.updated_at,
+                   ts_rank_cd(n.search_vector, q) as "rank!",
+                   ts_headline(
+                       'english',
+                       n.name || ' ' || coalesce((
+                           SELECT string_agg(p.value #>> '{}', ' ')
+                           FROM jsonb_each(n.data) p
+                           WHERE jsonb_typeof(p.value) = 'string' AND p.key <> 'name'
+                             AND (s.schema_json -> 'properties' -> p.key -> 'x-search-weight')
+                                 IS DISTINCT FROM 'false'::jsonb
+                       ), ''),
+                       q,
+                       'StartSel=<mark>, StopSel=</mark>, MaxFragments=2'
+                   ) as "snippet!"
+            FROM nodes n
+            LEFT JOIN schemas s ON s.title = n.schema_title,
+            websearch_to_tsquery('english', $1) q
+            WHERE n.search_vector @@ q
+              AND (cardinality($2::text[]) = 0 OR n.schema_title = ANY($2))
+            ORDER BY 8 DESC, n.id
+            LIMIT $3
+            "#,
+            text,
+            schema_titles,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(hits
+            .into_iter()
+            .map(|hit| DbSearchHit {
+                node: DbNode {
+                    id: hit.id,
+                    uid: hit.uid,
+                    schema_title: hit.schema_title,
+                    name: hit.name,
+                    data: hit.data,
+                    created_at: hit.created_at,
+                    updated_at: hit.updated_at,
+                },
+                rank: hit.rank,
+                snippet: hit.snippet,
+            })
+            .collect())
+    }
+
+    async fn similar_nodes(
+        &self,
+        name: &str,
+        schema_title: Option<&str>,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbSimilarNode>, Error> {
+        let mut tx = self.pool.begin().await?;
+        // `%` only uses the trigram index with the threshold set for the session
+        sqlx::query("SELECT set_config('pg_trgm.similarity_threshold', $1, true)")
+            .bind(threshold.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at,
+                   similarity(lower(name), lower($1)) as "similarity!"
+            FROM nodes
+            WHERE lower(name) % lower($1)
+              AND ($2::text IS NULL OR schema_title = $2)
+            ORDER BY 8 DESC, id
+            LIMIT $3
+            "#,
+            name,
+            schema_title,
+            limit
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DbSimilarNode {
+                node: DbNode {
+                    id: row.id,
+                    uid: row.uid,
+                    schema_title: row.schema_title,
+                    name: row.name,
+                    data: row.data,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                },
+                similarity: row.similarity,
+            })
+            .collect())
+    }
+
+    async fn duplicate_candidates(
+        &self,
+        schema_title: &str,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbDuplicateCandidate>, Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT set_config('pg_trgm.similarity_threshold', $1, true)")
+            .bind(threshold.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT a.id as a_id, a.uid as a_uid, a.name as a_name, a.data as "a_data: Value",
+                   a.created_at as a_created_at, a.updated_at as a_updated_at,
+                   b.id as b_id, b.uid as b_uid, b.name as b_name, b.data as "b_data: Value",
+                   b.created_at as b_created_at, b.updated_at as b_updated_at,
+                   similarity(lower(a.name), lower(b.name)) as "similarity!"
+            FROM nodes a
+            JOIN nodes b
+              ON b.schema_title = a.schema_title AND a.id < b.id AND lower(a.name) % lower(b.name)
+            WHERE a.schema_title = $1
+            ORDER BY 13 DESC, a.id, b.id
+            LIMIT $2
+            "#,
+            schema_title,
+            limit
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DbDuplicateCandidate {
+                left: DbNode {
+                    id: row.a_id,
+                    uid: row.a_uid,
+                    schema_title: schema_title.to_string(),
+                    name: row.a_name,
+                    data: row.a_data,
+                    created_at: row.a_created_at,
+                    updated_at: row.a_updated_at,
+                },
+                right: DbNode {
+                    id: row.b_id,
+                    uid: row.b_uid,
+                    schema_title: schema_title.to_string(),
+                    name: row.b_name,
+                    data: row.b_data,
+                    created_at: row.b_created_at,
+                    updated_at: row.b_updated_at,
+                },
+                similarity: row.similarity,
+            })
+            .collect())
+    }
+
+    async fn list_edges(&self, page: Page) -> Result<Vec<DbEdge>, Error> {
+        let edges = sqlx::query_as!(
+            DbEdge,
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            ORDER BY created_at DESC, id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(edges)
+    }
+
+    async fn count_edges(&self) -> Result<i64, Error> {
+        let count = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM edges"#)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn get_edge(&self, id: i32) -> Result<Option<DbEdge>, Error> {
+        let edge = sqlx::query_as!(
+            DbEdge,
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(edge)
+    }
+
+    async fn get_edge_by_uid(&self, uid: Uuid) -> Result<Option<DbEdge>, Error> {
+        let edge = sqlx::query_as!(
+            DbEdge,
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            WHERE uid = $1
+            "#,
+            uid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(edge)
+    }
+
+    async fn edges_of(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<DbEdge>, Error> {
+        let edges = sqlx::query_as!(
+            DbEdge,
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            WHERE ($2 AND source_node_id = ANY($1)) OR ($3 AND target_node_id = ANY($1))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            node_ids,
+            direction != Direction::Incoming,
+            direction != Direction::Outgoing,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(edges)
+    }
+
+    async fn count_edges_of(&self, node_ids: &[i32], direction: Direction) -> Result<i64, Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT count(*) as "count!"
+            FROM edges
+            WHERE ($2 AND source_node_id = ANY($1)) OR ($3 AND target_node_id = ANY($1))
+            "#,
+            node_ids,
+            direction != Direction::Incoming,
+            direction != Direction::Outgoing
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn edges_of_each(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<(i32, DbEdge)>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT n.node_id as "node_id!", e.id, e.uid, e.source_node_id, e.target_node_id,
+                   e.weight, e.created_at
+            FROM unnest($1::INT[]) AS n (node_id)
+            CROSS JOIN LATERAL (
+                SELECT id, uid, source_node_id, target_node_id, weight, created_at
+                FROM edges
+                WHERE ($2 AND source_node_id = n.node_id) OR ($3 AND target_node_id = n.node_id)
+                ORDER BY created_at DESC, id DESC
+                LIMIT $4 OFFSET $5
+            ) e
+            "#,
+            node_ids,
+            direction != Direction::Incoming,
+            direction != Direction::Outgoing,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let edge = DbEdge {
+                    id: row.id,
+                    uid: row.uid,
+                    source_node_id: row.source_node_id,
+                    target_node_id: row.target_node_id,
+                    weight: row.weight,
+                    created_at: row.created_at,
+                };
+                (row.node_id, edge)
+            })
+            .collect())
+    }
+
+    async fn insert_edge(&self, edge: NewEdge) -> Result<DbEdge, Error> {
+        let edge = sqlx::query_as!(
+            DbEdge,
+            r#"
+            INSERT INTO edges (source_node_id, target_node_id, weight)
+            VALUES ($1, $2, $3)
+            RETURNING id, uid, source_node_id, target_node_id, weight, created_at
+            "#,
+            edge.source_node_id,
+            edge.target_node_id,
+            edge.weight
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(edge)
+    }
+
+    async fn delete_edge(&self, id: i32) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM edges
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn walk(&self, start: i32, path: &[String]) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as!(
+            DbNode,
+            r#"
+            WITH RECURSIVE walk (node_id, depth) AS (
+                SELECT $1::INT, 0
+                UNION
+                SELECT e.target_node_id, w.depth + 1
+                FROM walk w
+                JOIN edges e ON e.source_node_id = w.node_id AND e.weight = ($2::TEXT[])[w.depth + 1]
+                WHERE w.depth < cardinality($2::TEXT[])
+            )
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            FROM nodes
+            WHERE id IN (SELECT node_id FROM walk WHERE depth = cardinality($2::TEXT[]))
+            "#,
+            start,
+            path
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes)
+    }
+
+    async fn graph_query(&self, query: &CompiledQuery) -> Result<Vec<Vec<Value>>, Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(&format!(
+            "SET LOCAL statement_timeout = {}",
+            STATEMENT_TIMEOUT.as_millis()
+        ))
+        .execute(&mut *tx)
+        .await?;
+        let mut statement = sqlx::query(&query.sql);
+        for bind in &query.binds {
+            statement = match bind {
+                Bind::Text(text) => statement.bind(text),
+                Bind::Json(value) => statement.bind(value),
+            };
+        }
+        let rows = statement
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|error| match &error {
+                // query_canceled, raised by `statement_timeout`
+                sqlx::Error::Database(database) if database.code().as_deref() == Some("57014") => {
+                    Error::ValidationFailed(format!(
+                        "the query ran longer than {}s",
+                        STATEMENT_TIMEOUT.as_secs()
+                    ))
+                }
+                _ => Error::from(error),
+            })?
+            .into_iter()
+            .map(|row| match row.try_get::<Value, _>(0) {
+                Ok(Value::Array(values)) => Ok(values),
+                Ok(other) => Ok(vec![other]),
+                Err(e) => Err(e),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        tx.rollback().await?;
+
+        Ok(rows)
+    }
+
+    async fn snapshot(&self) -> Result<Snapshot, Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+
+        let schemas = sqlx::query_as!(
+            DbSchema,
+            r#"
+            SELECT id, uid, title, schema_json as "schema_json: Value", created_at, updated_at
+            FROM schemas
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let nodes = sqlx::query_as!(
+            DbNode,
+            r#"
+            SELECT id, uid, schema_title, name, data as "data: Value", created_at, updated_at
+            FROM nodes
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let edges = sqlx::query_as!(
+            DbEdge,
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Snapshot {
+            schemas,
+            nodes,
+            edges,
+        })
+    }
+
+    async fn import(&self, batch: ImportBatch) -> Result<ImportReport, Error> {
+        let mut report = ImportReport::default();
+        let mut tx = self.pool.begin().await?;
+
+        for schema in batch.schemas {
+            sqlx::query!(
+                r#"
+                INSERT INTO schemas (title, schema_json)
+                VALUES ($1, $2)
+                ON CONFLICT (title) DO UPDATE
+                SET schema_json = EXCLUDED.schema_json, updated_at = CURRENT_TIMESTAMP
+                "#,
+                schema.title,
+                schema.schema_json
+            )
+            .execute(&mut *tx)
+            .await?;
+            report.schemas += 1;
+        }
+
+        let mut ids: HashMap<NodeKey, i32> = HashMap::new();
+        for node in batch.nodes {
+            let id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO nodes (schema_title, name, data)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (schema_title, name) DO UPDATE
+                SET data = EXCLUDED.data, updated_at = CURRENT_TIMESTAMP
+                WHERE $4
+                RETURNING id
+                "#,
+                node.schema_title,
+                node.name,
+                node.data,
+                batch.upsert
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            // dropping the transaction rolls back the rows written so far
+            let id = id.ok_or_else(|| Error::UniqueViolation {
+                key: format!(
+                    "(schema_title, name)=({}, {})",
+                    node.schema_title, node.name
+                ),
+            })?;
+            ids.insert((node.schema_title, node.name), id);
+            report.nodes += 1;
+        }
+
+        // the stored nodes the edges refer to
+        let (schema_titles, names): (Vec<String>, Vec<String>) = batch
+            .edges
+            .iter()
+            .flat_map(|(source, target, _)| [source, target])
+            .filter(|key| !ids.contains_key(*key))
+            .cloned()
+            .unzip();
+        let stored = sqlx::query!(
+            r#"
+            SELECT n.id, n.schema_title, n.name
+            FROM nodes n
+            JOIN UNNEST($1::text[], $2::text[]) AS k(schema_title, name)
+              ON n.schema_title = k.schema_title AND n.name = k.name
+            "#,
+            &schema_titles,
+            &names
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        ids.extend(
+            stored
+                .into_iter()
+                .map(|row| ((row.schema_title, row.name), row.id)),
+        );
+
+        for (source, target, weight) in batch.edges {
+            let id = |key: &NodeKey| {
+                ids.get(key).copied().ok_or_else(|| {
+                    Error::ForeignKeyViolation(format!(
+                        "(schema_title, name)=({}, {}) does not exist",
+                        key.0, key.1
+                    ))
+                })
+            };
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO edges (source_node_id, target_node_id, weight)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (source_node_id, target_node_id, weight) DO NOTHING
+                "#,
+                id(&source)?,
+                id(&target)?,
+                weight
+            )
+            .execute(&mut *tx)
+            .await?;
+            report.edges += result.rows_affected() as usize;
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+
+    async fn restore(&self, backup: &Backup, mode: RestoreMode) -> Result<RestoreReport, Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut report = RestoreReport::default();
+
+        if mode == RestoreMode::Empty {
+            let stored = sqlx::query_scalar!(
+                r#"
+                SELECT (SELECT count(*) FROM schemas) + (SELECT count(*) FROM nodes)
+                     + (SELECT count(*) FROM edges) as "count!"
+                "#
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if stored > 0 {
+                return Err(Error::ValidationFailed(
+                    "the database is not empty, restore into an empty one or merge".to_string(),
+                ));
+            }
+        }
+
+        for schema in &backup.schemas {
+            let stored = sqlx::query_scalar!(
+                r#"SELECT schema_json as "schema_json: Value" FROM schemas WHERE title = $1"#,
+                schema.title
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            match stored {
+                Some(stored) if stored == schema.schema_json => report.existing_schemas += 1,
+                Some(_) => {
+                    return Err(Error::ValidationFailed(format!(
+                        "schema '{}' differs from the stored one",
+                        schema.title
+                    )));
+                }
+                None => {
+                    sqlx::query!(
+                        r#"
+                        INSERT INTO schemas (id, uid, title, schema_json, created_at, updated_at)
+                        VALUES (coalesce($1, nextval(pg_get_serial_sequence('schemas', 'id'))::int),
+                                CASE WHEN $2::uuid IS NULL OR EXISTS (SELECT FROM schemas WHERE uid = $2)
+                                     THEN uuid_generate_v7(coalesce($5, clock_timestamp()))
+                                     ELSE $2 END,
+                                $3, $4, $5, $6)
+                        "#,
+                        (mode == RestoreMode::Empty).then_some(schema.id),
+                        public_id(schema.uid),
+                        schema.title,
+                        schema.schema_json,
+                        schema.created_at,
+                        schema.updated_at
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                    report.schemas += 1;
+                }
+            }
+        }
+
+        // the ids of the backup's nodes in the database
+        let mut node_ids: HashMap<i32, i32> = HashMap::new();
+        let restored = sqlx::query!(
+            r#"
+            WITH input AS (
+                SELECT *
+                FROM UNNEST($1::int[], $2::text[], $3::text[], $4::jsonb[],
+                            $5::timestamptz[], $6::timestamptz[], $8::uuid[])
+                    AS t(old_id, schema_title, name, data, created_at, updated_at, uid)
+            ),
+            inserted AS (
+                INSERT INTO nodes (id, uid, schema_title, name, data, created_at, updated_at)
+                SELECT CASE WHEN $7 THEN old_id
+                            ELSE nextval(pg_get_serial_sequence('nodes', 'id'))::int END,
+                       CASE WHEN uid IS NULL OR EXISTS (SELECT FROM nodes n WHERE n.uid = i.uid)
+                            THEN uuid_generate_v7(coalesce(created_at, clock_timestamp()))
+                            ELSE uid END,
+                       schema_title, name, data, created_at, updated_at
+                FROM input i
+                ON CONFLICT (schema_title, name) DO NOTHING
+                RETURNING id, schema_title, name
+            )
+            SELECT i.old_id as "old_id!", coalesce(ins.id, n.id) as "id!",
+                   ins.id IS NOT NULL as "inserted!"
+            FROM input i
+            LEFT JOIN inserted ins ON ins.schema_title = i.schema_title AND ins.name = i.name
+            LEFT JOIN nodes n ON n.schema_title = i.schema_title AND n.name = i.name
+            "#,
+            &backup.nodes.iter().map(|node| node.id).collect::<Vec<_>>(),
+            &backup
+                .nodes
+                .iter()
+                .map(|node| node.schema_title.clone())
+                .collect::<Vec<_>>(),
+            &backup
+                .nodes
+                .iter()
+                .map(|node| node.name.clone())
+                .collect::<Vec<_>>(),
+            &backup
+                .nodes
+                .iter()
+                .map(|node| node.data.clone())
+                .collect::<Vec<_>>(),
+            &backup
+                .nodes
+                .iter()
+                .map(|node| node.created_at)
+                .collect::<Vec<_>>() as &[Option<DateTime<Utc>>],
+            &backup
+                .nodes
+                .iter()
+                .map(|node| node.updated_at)
+                .collect::<Vec<_>>() as &[Option<DateTime<Utc>>],
+            mode == RestoreMode::Empty,
+            &backup
+                .nodes
+                .iter()
+                .map(|node| public_id(node.uid))
+                .collect::<Vec<_>>() as &[Option<Uuid>]
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in restored {
+            node_ids.insert(row.old_id, row.id);
+            if row.inserted {
+                report.nodes += 1;
+            } else {
+                report.existing_nodes += 1;
+            }
+        }
+
+        let mut edges = (
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+        for edge in &backup.edges {
+            let (Some(source), Some(target)) = (
+                node_ids.get(&edge.source_node_id),
+                node_ids.get(&edge.target_node_id),
+            ) else {
+                return Err(Error::ValidationFailed(format!(
+                    "edge {} refers to a node that is not in the backup",
+                    edge.id
+                )));
+            };
+            edges.0.push(edge.id);
+            edges.1.push(*source);
+            edges.2.push(*target);
+            edges.3.push(edge.weight.clone());
+            edges.4.push(edge.created_at);
+            edges.5.push(public_id(edge.uid));
+        }
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO edges (id, uid, source_node_id, target_node_id, weight, created_at)
+            SELECT CASE WHEN $6 THEN id ELSE nextval(pg_get_serial_sequence('edges', 'id'))::int END,
+                   CASE WHEN uid IS NULL OR EXISTS (SELECT FROM edges e WHERE e.uid = t.uid)
+                        THEN uuid_generate_v7(coalesce(created_at, clock_timestamp()))
+                        ELSE uid END,
+                   source_node_id, target_node_id, weight, created_at
+            FROM UNNEST($1::int[], $2::int[], $3::int[], $4::text[], $5::timestamptz[], $7::uuid[])
+                AS t(id, source_node_id, target_node_id, weight, created_at, uid)
+            ON CONFLICT (source_node_id, target_node_id, weight) DO NOTHING
+            "#,
+            &edges.0,
+            &edges.1,
+            &edges.2,
+            &edges.3,
+            &edges.4 as &[Option<DateTime<Utc>>],
+            mode == RestoreMode::Empty,
+            &edges.5 as &[Option<Uuid>]
+        )
+        .execute(&mut *tx)
+        .await?;
+        report.edges = inserted.rows_affected() as usize;
+        report.existing_edges = backup.edges.len() - report.edges;
+
+        if mode == RestoreMode::Empty {
+            // continue numbering after the restored ids
+            sqlx::query(
+                r#"
+                SELECT setval(pg_get_serial_sequence(t, 'id'), max_id + 1, false)
+                FROM (VALUES ('schemas', (SELECT coalesce(max(id), 0) FROM schemas)),
+                             ('nodes', (SELECT coalesce(max(id), 0) FROM nodes)),
+                             ('edges', (SELECT coalesce(max(id), 0) FROM edges))) AS s(t, max_id)
+                "#,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+}
+
+/// Hold off other writes to `schemas` until the transaction ends, so that
+/// what it checked against the stored schemas still holds when it commits.
+/// Node and edge writes are not affected.
+async fn lock_schemas(tx: &mut sqlx::PgConnection) -> Result<(), Error> {
+    sqlx::query!("LOCK TABLE schemas IN SHARE ROW EXCLUSIVE MODE")
+        .execute(tx)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,1114 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{error::ErrorKind, types::Json};
+use uuid::{Uuid, fmt::Hyphenated};
+
+use super::{
+    CheckSchemas, Combine, GraphVersion, ImportBatch, NodeKey, Snapshot, Storage,
+    WITHOUT_GRAPH_QUERIES, text,
+};
+use crate::{
+    backup::{Backup, RestoreMode, RestoreReport, new_public_id, public_id},
+    catalog::{self, SchemaCatalog},
+    error::Error,
+    import::ImportReport,
+    model::{DbDuplicateCandidate, DbEdge, DbNode, DbSchema, DbSearchHit, DbSimilarNode},
+    query::CompiledQuery,
+    repository::{Direction, NewEdge, NewNode, NewSchema, NodeFilter, NodeOrder, Page},
+};
+
+/// The graph in the SQLite database the migrations in `migrations/sqlite/`
+/// set up, for single-user deployments without a Postgres server.
+///
+/// JSON is kept as text and lists of ids or titles are bound as JSON arrays
+/// that `json_each` unpacks. SQLite has neither `tsvector` nor `pg_trgm`, so
+/// search and name similarity are computed over the candidate rows here.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: sqlx::SqlitePool,
+}
+
+impl SqliteStorage {
+    pub fn new(pool: sqlx::SqlitePool) -> Self {
+        SqliteStorage { pool }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SchemaRow {
+    id: i32,
+    uid: Hyphenated,
+    title: String,
+    schema_json: Json<Value>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<SchemaRow> for DbSchema {
+    fn from(row: SchemaRow) -> Self {
+        DbSchema {
+            id: row.id,
+            uid: row.uid.into(),
+            title: row.title,
+            schema_json: row.schema_json.0,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct NodeRow {
+    id: i32,
+    uid: Hyphenated,
+    schema_title: String,
+    name: String,
+    data: Json<Value>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<NodeRow> for DbNode {
+    fn from(row: NodeRow) -> Self {
+        DbNode {
+            id: row.id,
+            uid: row.uid.into(),
+            schema_title: row.schema_title,
+            name: row.name,
+            data: row.data.0,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// An edge found for `node_id`.
+#[derive(sqlx::FromRow)]
+struct NodeEdgeRow {
+    node_id: i32,
+    #[sqlx(flatten)]
+    edge: EdgeRow,
+}
+
+#[derive(sqlx::FromRow)]
+struct EdgeRow {
+    id: i32,
+    uid: Hyphenated,
+    source_node_id: i32,
+    target_node_id: i32,
+    weight: String,
+    created_at: Option<DateTime<Utc>>,
+}
+
+impl From<EdgeRow> for DbEdge {
+    fn from(row: EdgeRow) -> Self {
+        DbEdge {
+            id: row.id,
+            uid: row.uid.into(),
+            source_node_id: row.source_node_id,
+            target_node_id: row.target_node_id,
+            weight: row.weight,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// A node together with the schema it belongs to.
+#[derive(sqlx::FromRow)]
+struct SchemaNodeRow {
+    #[sqlx(flatten)]
+    node: NodeRow,
+    schema_json: Json<Value>,
+}
+
+/// `error`, or what `report` makes of a constraint violation: SQLite names the
+/// violated constraint but not the offending values, which the caller knows.
+fn violation(error: sqlx::Error, report: impl FnOnce(ErrorKind, &str) -> Option<Error>) -> Error {
+    if let sqlx::Error::Database(database) = &error
+        && let Some(reported) = report(database.kind(), database.message())
+    {
+        return reported;
+    }
+    Error::from(error)
+}
+
+/// What a failed insert of `schema` violated.
+fn schema_violation(error: sqlx::Error, schema: &NewSchema) -> Error {
+    violation(error, |kind, message| match kind {
+        ErrorKind::UniqueViolation if message.contains("idx_schemas_id_uri") => {
+            Some(Error::UniqueViolation {
+                key: format!(
+                    "(schema_json ->> '$id')=({})",
+                    schema
+                        .schema_json
+                        .get("$id")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                ),
+            })
+        }
+        ErrorKind::UniqueViolation => Some(Error::UniqueViolation {
+            key: format!("(title)=({})", schema.title),
+        }),
+        ErrorKind::ForeignKeyViolation => Some(Error::ForeignKeyViolation(format!(
+            "(extends)=({}) does not exist",
+            catalog::parent_title(&schema.schema_json).unwrap_or_default()
+        ))),
+        _ => None,
+    })
+}
+
+/// What a failed insert of `node` violated.
+fn node_violation(error: sqlx::Error, node: &NewNode) -> Error {
+    violation(error, |kind, _| match kind {
+        ErrorKind::UniqueViolation => Some(Error::UniqueViolation {
+            key: format!(
+                "(schema_title, name)=({}, {})",
+                node.schema_title, node.name
+            ),
+        }),
+        ErrorKind::ForeignKeyViolation => Some(Error::ForeignKeyViolation(format!(
+            "(schema_title)=({}) does not exist",
+            node.schema_title
+        ))),
+        _ => None,
+    })
+}
+
+/// `at` as the column defaults write timestamps, which keeps them ordered.
+fn timestamp(at: Option<DateTime<Utc>>) -> Option<String> {
+    at.map(|at| at.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+}
+
+async fn load_catalog(executor: impl sqlx::SqliteExecutor<'_>) -> Result<SchemaCatalog, Error> {
+    let schemas: Vec<(String, Json<Value>)> =
+        sqlx::query_as("SELECT title, schema_json FROM schemas")
+            .fetch_all(executor)
+            .await?;
+
+    let mut catalog = SchemaCatalog::default();
+    for (title, Json(schema_json)) in schemas {
+        catalog.insert(&title, schema_json);
+    }
+    Ok(catalog)
+}
+
+#[async_trait::async_trait]
+impl Storage for SqliteStorage {
+    async fn version(&self) -> Result<GraphVersion, Error> {
+        let (graph, schemas) = sqlx::query_as("SELECT version, schema_version FROM graph_version")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(GraphVersion { graph, schemas })
+    }
+
+    async fn list_schemas(&self, page: Page) -> Result<Vec<DbSchema>, Error> {
+        let schemas = sqlx::query_as::<_, SchemaRow>(
+            r#"
+            SELECT id, uid, title, schema_json, created_at, updated_at
+            FROM schemas
+            ORDER BY created_at DESC, id DESC
+            LIMIT coalesce(?1, -1) OFFSET ?2
+            "#,
+        )
+        .bind(page.limit)
+        .bind(page.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(schemas.into_iter().map(DbSchema::from).collect())
+    }
+
+    async fn count_schemas(&self) -> Result<i64, Error> {
+        let count = sqlx::query_scalar("SELECT count(*) FROM schemas")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn get_schemas(&self, titles: &[String]) -> Result<Vec<DbSchema>, Error> {
+        let schemas = sqlx::query_as::<_, SchemaRow>(
+            r#"
+            SELECT id, uid, title, schema_json, created_at, updated_at
+            FROM schemas
+            WHERE title IN (SELECT value FROM json_each(?1))
+            "#,
+        )
+        .bind(Json(titles))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(schemas.into_iter().map(DbSchema::from).collect())
+    }
+
+    async fn get_schema_by_uid(&self, uid: Uuid) -> Result<Option<DbSchema>, Error> {
+        let schema = sqlx::query_as::<_, SchemaRow>(
+            r#"
+            SELECT id, uid, title, schema_json, created_at, updated_at
+            FROM schemas
+            WHERE uid = ?1
+            "#,
+        )
+        .bind(uid.hyphenated())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(schema.map(DbSchema::from))
+    }
+
+    async fn catalog(&self) -> Result<SchemaCatalog, Error> {
+        load_catalog(&self.pool).await
+    }
+
+    async fn insert_schema(
+        &self,
+        schema: NewSchema,
+        check: &CheckSchemas<'_>,
+    ) -> Result<DbSchema, Error> {
+        // taking the write lock first keeps other writers out until commit
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        check(&load_catalog(&mut *tx).await?)?;
+
+        let inserted = sqlx::query_as::<_, SchemaRow>(
+            r#"
+            INSERT INTO schemas (title, schema_json, uid)
+            VALUES (?1, ?2, ?3)
+            RETURNING id, uid, title, schema_json, created_at, updated_at
+            "#,
+        )
+        .bind(&schema.title)
+        .bind(Json(&schema.schema_json))
+        .bind(Uuid::now_v7().hyphenated())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|error| schema_violation(error, &schema))?;
+
+        tx.commit().await?;
+        Ok(inserted.into())
+    }
+
+    async fn delete_schema(&self, title: &str, check: &CheckSchemas<'_>) -> Result<bool, Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        check(&load_catalog(&mut *tx).await?)?;
+
+        let result = sqlx::query("DELETE FROM schemas WHERE title = ?1")
+            .bind(title)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_nodes(&self, filter: &NodeFilter, page: Page) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            WITH RECURSIVE kinds (title) AS (
+                SELECT title FROM schemas WHERE title = ?1
+                UNION
+                SELECT s.title
+                FROM schemas s
+                JOIN kinds k ON s.extends = k.title
+                WHERE ?2
+            )
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            WHERE ?1 IS NULL OR schema_title IN (SELECT title FROM kinds)
+            ORDER BY CASE WHEN ?5 THEN name END, created_at DESC, id DESC
+            LIMIT coalesce(?3, -1) OFFSET ?4
+            "#,
+        )
+        .bind(&filter.schema_title)
+        .bind(filter.include_subtypes)
+        .bind(page.limit)
+        .bind(page.offset)
+        .bind(filter.order == NodeOrder::Name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes.into_iter().map(DbNode::from).collect())
+    }
+
+    async fn count_nodes(&self, filter: &NodeFilter) -> Result<i64, Error> {
+        let count = sqlx::query_scalar(
+            r#"
+            WITH RECURSIVE kinds (title) AS (
+                SELECT title FROM schemas WHERE title = ?1
+                UNION
+                SELECT s.title
+                FROM schemas s
+                JOIN kinds k ON s.extends = k.title
+                WHERE ?2
+            )
+            SELECT count(*)
+            FROM nodes
+            WHERE ?1 IS NULL OR schema_title IN (SELECT title FROM kinds)
+            "#,
+        )
+        .bind(&filter.schema_title)
+        .bind(filter.include_subtypes)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn get_nodes(&self, ids: &[i32]) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            WHERE id IN (SELECT value FROM json_each(?1))
+            "#,
+        )
+        .bind(Json(ids))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes.into_iter().map(DbNode::from).collect())
+    }
+
+    async fn get_nodes_by_uid(&self, uids: &[Uuid]) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            WHERE uid IN (SELECT value FROM json_each(?1))
+            "#,
+        )
+        .bind(Json(uids))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes.into_iter().map(DbNode::from).collect())
+    }
+
+    async fn find_node(&self, schema_title: &str, name: &str) -> Result<Option<DbNode>, Error> {
+        let node = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            WHERE schema_title = ?1 AND name = ?2
+            "#,
+        )
+        .bind(schema_title)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(node.map(DbNode::from))
+    }
+
+    async fn find_nodes(&self, keys: &[NodeKey]) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT n.id, n.uid, n.schema_title, n.name, n.data, n.created_at, n.updated_at
+            FROM nodes n
+            JOIN json_each(?1) k ON n.schema_title = k.value ->> 0 AND n.name = k.value ->> 1
+            "#,
+        )
+        .bind(Json(keys))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes.into_iter().map(DbNode::from).collect())
+    }
+
+    async fn insert_node(&self, node: NewNode) -> Result<DbNode, Error> {
+        let inserted = sqlx::query_as::<_, NodeRow>(
+            r#"
+            INSERT INTO nodes (schema_title, name, data, uid)
+            VALUES (?1, ?2, ?3, ?4)
+            RETURNING id, uid, schema_title, name, data, created_at, updated_at
+            "#,
+        )
+        .bind(&node.schema_title)
+        .bind(&node.name)
+        .bind(Json(&node.data))
+        .bind(Uuid::now_v7().hyphenated())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|error| node_violation(error, &node))?;
+
+        Ok(inserted.into())
+    }
+
+    async fn delete_node(&self, id: i32) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM nodes WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn merge_nodes(
+        &self,
+        keep_id: i32,
+        merge_ids: &[i32],
+        combine: &Combine<'_>,
+    ) -> Result<DbNode, Error> {
+        // take the write lock up front rather than failing to upgrade to it
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let mut ids = vec![keep_id];
+        ids.extend(merge_ids);
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT n.id, n.uid, n.schema_title, n.name, n.data, n.created_at, n.updated_at
+            FROM nodes n
+            JOIN json_each(?1) ids ON ids.value = n.id
+            ORDER BY ids.key
+            "#,
+        )
+        .bind(Json(&ids))
+        .fetch_all(&mut *tx)
+        .await?;
+        let nodes: Vec<DbNode> = nodes.into_iter().map(DbNode::from).collect();
+        let data = combine(&nodes)?;
+
+        // copy edges onto the survivor, dropping duplicates and the self-loops
+        // that edges between merged nodes and the survivor would become
+        let copies: Vec<(i32, i32, String, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT source_id, target_id, weight, created_at
+            FROM (
+                SELECT CASE WHEN source_node_id IN (SELECT value FROM json_each(?2))
+                            THEN ?1 ELSE source_node_id END as source_id,
+                       CASE WHEN target_node_id IN (SELECT value FROM json_each(?2))
+                            THEN ?1 ELSE target_node_id END as target_id,
+                       source_node_id, target_node_id, weight, created_at
+                FROM edges
+                WHERE source_node_id IN (SELECT value FROM json_each(?2))
+                   OR target_node_id IN (SELECT value FROM json_each(?2))
+            ) e
+            WHERE source_id <> target_id OR source_node_id = target_node_id
+            ORDER BY created_at
+            "#,
+        )
+        .bind(keep_id)
+        .bind(Json(merge_ids))
+        .fetch_all(&mut *tx)
+        .await?;
+        for (source_id, target_id, weight, created_at) in copies {
+            sqlx::query(
+                r#"
+                INSERT INTO edges (source_node_id, target_node_id, weight, created_at, uid)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT (source_node_id, target_node_id, weight) DO NOTHING
+                "#,
+            )
+            .bind(source_id)
+            .bind(target_id)
+            .bind(weight)
+            .bind(created_at)
+            .bind(Uuid::now_v7().hyphenated())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("DELETE FROM nodes WHERE id IN (SELECT value FROM json_each(?1))")
+            .bind(Json(merge_ids))
+            .execute(&mut *tx)
+            .await?;
+
+        let node = sqlx::query_as::<_, NodeRow>(
+            r#"
+            UPDATE nodes
+            SET data = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE id = ?1
+            RETURNING id, uid, schema_title, name, data, created_at, updated_at
+            "#,
+        )
+        .bind(keep_id)
+        .bind(Json(&data))
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(node.into())
+    }
+
+    async fn search_nodes(
+        &self,
+        text: &str,
+        schema_titles: &[String],
+        limit: i64,
+    ) -> Result<Vec<DbSearchHit>, Error> {
+        let rows = sqlx::query_as::<_, SchemaNodeRow>(
+            r#"
+            SELECT n.id, n.uid, n.schema_title, n.name, n.data, n.created_at, n.updated_at,
+                   s.schema_json
+            FROM nodes n
+            JOIN schemas s ON s.title = n.schema_title
+            WHERE json_array_length(?1) = 0
+               OR n.schema_title IN (SELECT value FROM json_each(?1))
+            "#,
+        )
+        .bind(Json(schema_titles))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let rows: Vec<(DbNode, Value)> = rows
+            .into_iter()
+            .map(|row| (row.node.into(), row.schema_json.0))
+            .collect();
+        let nodes = rows
+            .iter()
+            .map(|(node, schema_json)| (node.clone(), Some(schema_json)));
+        Ok(text::search(nodes, text, limit))
+    }
+
+    async fn similar_nodes(
+        &self,
+        name: &str,
+        schema_title: Option<&str>,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbSimilarNode>, Error> {
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            WHERE ?1 IS NULL OR schema_title = ?1
+            "#,
+        )
+        .bind(schema_title)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(text::similar(
+            nodes.into_iter().map(DbNode::from),
+            name,
+            threshold,
+            limit,
+        ))
+    }
+
+    async fn duplicate_candidates(
+        &self,
+        schema_title: &str,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<DbDuplicateCandidate>, Error> {
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            WHERE schema_title = ?1
+            "#,
+        )
+        .bind(schema_title)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(text::duplicates(
+            nodes.into_iter().map(DbNode::from).collect(),
+            threshold,
+            limit,
+        ))
+    }
+
+    async fn list_edges(&self, page: Page) -> Result<Vec<DbEdge>, Error> {
+        let edges = sqlx::query_as::<_, EdgeRow>(
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            ORDER BY created_at DESC, id DESC
+            LIMIT coalesce(?1, -1) OFFSET ?2
+            "#,
+        )
+        .bind(page.limit)
+        .bind(page.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(edges.into_iter().map(DbEdge::from).collect())
+    }
+
+    async fn count_edges(&self) -> Result<i64, Error> {
+        let count = sqlx::query_scalar("SELECT count(*) FROM edges")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn get_edge(&self, id: i32) -> Result<Option<DbEdge>, Error> {
+        let edge = sqlx::query_as::<_, EdgeRow>(
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(edge.map(DbEdge::from))
+    }
+
+    async fn get_edge_by_uid(&self, uid: Uuid) -> Result<Option<DbEdge>, Error> {
+        let edge = sqlx::query_as::<_, EdgeRow>(
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            WHERE uid = ?1
+            "#,
+        )
+        .bind(uid.hyphenated())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(edge.map(DbEdge::from))
+    }
+
+    async fn edges_of(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<DbEdge>, Error> {
+        let edges = sqlx::query_as::<_, EdgeRow>(
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            WHERE (?2 AND source_node_id IN (SELECT value FROM json_each(?1)))
+               OR (?3 AND target_node_id IN (SELECT value FROM json_each(?1)))
+            ORDER BY created_at DESC, id DESC
+            LIMIT coalesce(?4, -1) OFFSET ?5
+            "#,
+        )
+        .bind(Json(node_ids))
+        .bind(direction != Direction::Incoming)
+        .bind(direction != Direction::Outgoing)
+        .bind(page.limit)
+        .bind(page.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(edges.into_iter().map(DbEdge::from).collect())
+    }
+
+    async fn count_edges_of(&self, node_ids: &[i32], direction: Direction) -> Result<i64, Error> {
+        let count = sqlx::query_scalar(
+            r#"
+            SELECT count(*)
+            FROM edges
+            WHERE (?2 AND source_node_id IN (SELECT value FROM json_each(?1)))
+               OR (?3 AND target_node_id IN (SELECT value FROM json_each(?1)))
+            "#,
+        )
+        .bind(Json(node_ids))
+        .bind(direction != Direction::Incoming)
+        .bind(direction != Direction::Outgoing)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn edges_of_each(
+        &self,
+        node_ids: &[i32],
+        direction: Direction,
+        page: Page,
+    ) -> Result<Vec<(i32, DbEdge)>, Error> {
+        let rows = sqlx::query_as::<_, NodeEdgeRow>(
+            r#"
+            SELECT node_id, id, uid, source_node_id, target_node_id, weight, created_at
+            FROM (
+                SELECT n.value AS node_id, e.*, row_number() OVER (
+                    PARTITION BY n.value ORDER BY e.created_at DESC, e.id DESC
+                ) AS position
+                FROM json_each(?1) n
+                JOIN edges e
+                  ON (?2 AND e.source_node_id = n.value) OR (?3 AND e.target_node_id = n.value)
+            )
+            WHERE position > ?5 AND (?4 IS NULL OR position <= ?5 + ?4)
+            ORDER BY node_id, position
+            "#,
+        )
+        .bind(Json(node_ids))
+        .bind(direction != Direction::Incoming)
+        .bind(direction != Direction::Outgoing)
+        .bind(page.limit)
+        .bind(page.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.node_id, DbEdge::from(row.edge)))
+            .collect())
+    }
+
+    async fn insert_edge(&self, edge: NewEdge) -> Result<DbEdge, Error> {
+        let inserted = sqlx::query_as::<_, EdgeRow>(
+            r#"
+            INSERT INTO edges (source_node_id, target_node_id, weight, uid)
+            VALUES (?1, ?2, ?3, ?4)
+            RETURNING id, uid, source_node_id, target_node_id, weight, created_at
+            "#,
+        )
+        .bind(edge.source_node_id)
+        .bind(edge.target_node_id)
+        .bind(&edge.weight)
+        .bind(Uuid::now_v7().hyphenated())
+        .fetch_one(&self.pool)
+        .await;
+
+        match inserted {
+            Ok(inserted) => Ok(inserted.into()),
+            Err(error) if matches!(&error, sqlx::Error::Database(e) if e.is_foreign_key_violation()) =>
+            {
+                // SQLite does not say which end is missing
+                let existing: Vec<i32> =
+                    sqlx::query_scalar("SELECT id FROM nodes WHERE id IN (?1, ?2)")
+                        .bind(edge.source_node_id)
+                        .bind(edge.target_node_id)
+                        .fetch_all(&self.pool)
+                        .await?;
+                let (column, id) = if existing.contains(&edge.source_node_id) {
+                    ("target_node_id", edge.target_node_id)
+                } else {
+                    ("source_node_id", edge.source_node_id)
+                };
+                Err(Error::ForeignKeyViolation(format!(
+                    "({column})=({id}) does not exist"
+                )))
+            }
+            Err(error) => Err(violation(error, |kind, _| {
+                (kind == ErrorKind::UniqueViolation).then(|| Error::UniqueViolation {
+                    key: format!(
+                        "(source_node_id, target_node_id, weight)=({}, {}, {})",
+                        edge.source_node_id, edge.target_node_id, edge.weight
+                    ),
+                })
+            })),
+        }
+    }
+
+    async fn delete_edge(&self, id: i32) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM edges WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn walk(&self, start: i32, path: &[String]) -> Result<Vec<DbNode>, Error> {
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            WITH RECURSIVE walk (node_id, depth) AS (
+                SELECT ?1, 0
+                UNION
+                SELECT e.target_node_id, w.depth + 1
+                FROM walk w
+                JOIN edges e ON e.source_node_id = w.node_id AND e.weight = ?2 ->> w.depth
+                WHERE w.depth < json_array_length(?2)
+            )
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            WHERE id IN (SELECT node_id FROM walk WHERE depth = json_array_length(?2))
+            "#,
+        )
+        .bind(start)
+        .bind(Json(path))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes.into_iter().map(DbNode::from).collect())
+    }
+    async fn graph_query(&self, _query: &CompiledQuery) -> Result<Vec<Vec<Value>>, Error> {
+        Err(Error::Unsupported {
+            feature: "pattern queries",
+            storages: WITHOUT_GRAPH_QUERIES,
+        })
+    }
+
+    async fn snapshot(&self) -> Result<Snapshot, Error> {
+        // the reads of one transaction see the database as of the first
+        let mut tx = self.pool.begin().await?;
+        let schemas = sqlx::query_as::<_, SchemaRow>(
+            r#"
+            SELECT id, uid, title, schema_json, created_at, updated_at
+            FROM schemas
+            ORDER BY id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let nodes = sqlx::query_as::<_, NodeRow>(
+            r#"
+            SELECT id, uid, schema_title, name, data, created_at, updated_at
+            FROM nodes
+            ORDER BY id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let edges = sqlx::query_as::<_, EdgeRow>(
+            r#"
+            SELECT id, uid, source_node_id, target_node_id, weight, created_at
+            FROM edges
+            ORDER BY id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Snapshot {
+            schemas: schemas.into_iter().map(DbSchema::from).collect(),
+            nodes: nodes.into_iter().map(DbNode::from).collect(),
+            edges: edges.into_iter().map(DbEdge::from).collect(),
+        })
+    }
+
+    async fn import(&self, batch: ImportBatch) -> Result<ImportReport, Error> {
+        let mut report = ImportReport::default();
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        for schema in &batch.schemas {
+            sqlx::query(
+                r#"
+                INSERT INTO schemas (title, schema_json, uid)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT (title) DO UPDATE
+                SET schema_json = excluded.schema_json,
+                    updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                "#,
+            )
+            .bind(&schema.title)
+            .bind(Json(&schema.schema_json))
+            .bind(Uuid::now_v7().hyphenated())
+            .execute(&mut *tx)
+            .await
+            .map_err(|error| schema_violation(error, schema))?;
+            report.schemas += 1;
+        }
+
+        let mut ids: HashMap<NodeKey, i32> = HashMap::new();
+        for node in batch.nodes {
+            let id: Option<i32> = sqlx::query_scalar(
+                r#"
+                INSERT INTO nodes (schema_title, name, data, uid)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (schema_title, name) DO UPDATE
+                SET data = excluded.data, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                WHERE ?5
+                RETURNING id
+                "#,
+            )
+            .bind(&node.schema_title)
+            .bind(&node.name)
+            .bind(Json(&node.data))
+            .bind(Uuid::now_v7().hyphenated())
+            .bind(batch.upsert)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|error| node_violation(error, &node))?;
+            // dropping the transaction rolls back the rows written so far
+            let id = id.ok_or_else(|| Error::UniqueViolation {
+                key: format!(
+                    "(schema_title, name)=({}, {})",
+                    node.schema_title, node.name
+                ),
+            })?;
+            ids.insert((node.schema_title, node.name), id);
+            report.nodes += 1;
+        }
+
+        for (source, target, weight) in batch.edges {
+            let mut endpoints = [0; 2];
+            for (endpoint, key) in endpoints.iter_mut().zip([source, target]) {
+                *endpoint = match ids.get(&key) {
+                    Some(id) => *id,
+                    None => {
+                        let id = sqlx::query_scalar(
+                            "SELECT id FROM nodes WHERE schema_title = ?1 AND name = ?2",
+                        )
+                        .bind(&key.0)
+                        .bind(&key.1)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                        .ok_or_else(|| {
+                            Error::ForeignKeyViolation(format!(
+                                "(schema_title, name)=({}, {}) does not exist",
+                                key.0, key.1
+                            ))
+                        })?;
+                        ids.insert(key, id);
+                        id
+                    }
+                };
+            }
+            let result = sqlx::query(
+                r#"
+                INSERT INTO edges (source_node_id, target_node_id, weight, uid)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (source_node_id, target_node_id, weight) DO NOTHING
+                "#,
+            )
+            .bind(endpoints[0])
+            .bind(endpoints[1])
+            .bind(&weight)
+            .bind(Uuid::now_v7().hyphenated())
+            .execute(&mut *tx)
+            .await?;
+            report.edges += result.rows_affected() as usize;
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+
+    async fn restore(&self, backup: &Backup, mode: RestoreMode) -> Result<RestoreReport, Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut report = RestoreReport::default();
+        // ids are kept in an empty database only, where AUTOINCREMENT then
+        // continues after the largest
+        let keep_ids = mode == RestoreMode::Empty;
+
+        if keep_ids {
+            let stored: i64 = sqlx::query_scalar(
+                r#"
+                SELECT (SELECT count(*) FROM schemas) + (SELECT count(*) FROM nodes)
+                     + (SELECT count(*) FROM edges)
+                "#,
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if stored > 0 {
+                return Err(Error::ValidationFailed(
+                    "the database is not empty, restore into an empty one or merge".to_string(),
+                ));
+            }
+        }
+
+        for schema in &backup.schemas {
+            let stored: Option<Json<Value>> =
+                sqlx::query_scalar("SELECT schema_json FROM schemas WHERE title = ?1")
+                    .bind(&schema.title)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            match stored {
+                Some(Json(stored)) if stored == schema.schema_json => report.existing_schemas += 1,
+                Some(_) => {
+                    return Err(Error::ValidationFailed(format!(
+                        "schema '{}' differs from the stored one",
+                        schema.title
+                    )));
+                }
+                None => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO schemas (id, uid, title, schema_json, created_at, updated_at)
+                        VALUES (?1,
+                                CASE WHEN ?2 IS NULL OR EXISTS (SELECT 1 FROM schemas WHERE uid = ?2)
+                                     THEN ?3 ELSE ?2 END,
+                                ?4, ?5, ?6, ?7)
+                        "#,
+                    )
+                    .bind(keep_ids.then_some(schema.id))
+                    .bind(public_id(schema.uid).map(Uuid::hyphenated))
+                    .bind(new_public_id(schema.created_at).hyphenated())
+                    .bind(&schema.title)
+                    .bind(Json(&schema.schema_json))
+                    .bind(timestamp(schema.created_at))
+                    .bind(timestamp(schema.updated_at))
+                    .execute(&mut *tx)
+                    .await?;
+                    report.schemas += 1;
+                }
+            }
+        }
+
+        // the ids of the backup's nodes in the database
+        let mut node_ids: HashMap<i32, i32> = HashMap::new();
+        for node in &backup.nodes {
+            let inserted: Option<i32> = sqlx::query_scalar(
+                r#"
+                INSERT INTO nodes (id, uid, schema_title, name, data, created_at, updated_at)
+                VALUES (?1,
+                        CASE WHEN ?2 IS NULL OR EXISTS (SELECT 1 FROM nodes WHERE uid = ?2)
+                             THEN ?3 ELSE ?2 END,
+                        ?4, ?5, ?6, ?7, ?8)
+                ON CONFLICT (schema_title, name) DO NOTHING
+                RETURNING id
+                "#,
+            )
+            .bind(keep_ids.then_some(node.id))
+            .bind(public_id(node.uid).map(Uuid::hyphenated))
+            .bind(new_public_id(node.created_at).hyphenated())
+            .bind(&node.schema_title)
+            .bind(&node.name)
+            .bind(Json(&node.data))
+            .bind(timestamp(node.created_at))
+            .bind(timestamp(node.updated_at))
+            .fetch_optional(&mut *tx)
+            .await?;
+            let id = match inserted {
+                Some(id) => {
+                    report.nodes += 1;
+                    id
+                }
+                None => {
+                    report.existing_nodes += 1;
+                    sqlx::query_scalar("SELECT id FROM nodes WHERE schema_title = ?1 AND name = ?2")
+                        .bind(&node.schema_title)
+                        .bind(&node.name)
+                        .fetch_one(&mut *tx)
+                        .await?
+                }
+            };
+            node_ids.insert(node.id, id);
+        }
+
+        for edge in &backup.edges {
+            let (Some(source), Some(target)) = (
+                node_ids.get(&edge.source_node_id),
+                node_ids.get(&edge.target_node_id),
+            ) else {
+                return Err(Error::ValidationFailed(format!(
+                    "edge {} refers to a node that is not in the backup",
+                    edge.id
+                )));
+            };
+            let result = sqlx::query(
+                r#"
+                INSERT INTO edges (id, uid, source_node_id, target_node_id, weight, created_at)
+                VALUES (?1,
+                        CASE WHEN ?2 IS NULL OR EXISTS (SELECT 1 FROM edges WHERE uid = ?2)
+                             THEN ?3 ELSE ?2 END,
+                        ?4, ?5, ?6, ?7)
+                ON CONFLICT (source_node_id, target_node_id, weight) DO NOTHING
+                "#,
+            )
+            .bind(keep_ids.then_some(edge.id))
+            .bind(public_id(edge.uid).map(Uuid::hyphenated))
+            .bind(new_public_id(edge.created_at).hyphenated())
+            .bind(source)
+            .bind(target)
+            .bind(&edge.weight)
+            .bind(timestamp(edge.created_at))
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() > 0 {
+                report.edges += 1;
+            } else {
+                report.existing_edges += 1;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+}
@@ -0,0 +1,185 @@
+//! Full-text search and name similarity for the storages whose database
+//! cannot compute them, mirroring what Postgres does with `tsvector` and
+//! `pg_trgm`.
+
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::model::{DbDuplicateCandidate, DbNode, DbSearchHit, DbSimilarNode};
+
+/// The nodes, each given with its schema, that match every word of `text`,
+/// best first.
+pub(super) fn search<'a>(
+    nodes: impl IntoIterator<Item = (DbNode, Option<&'a Value>)>,
+    text: &str,
+    limit: i64,
+) -> Vec<DbSearchHit> {
+    let terms: Vec<String> = words(text).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let mut hits: Vec<DbSearchHit> = nodes
+        .into_iter()
+        .filter_map(|(node, schema_json)| search_hit(schema_json, node, &terms))
+        .collect();
+    hits.sort_by(|a, b| b.rank.total_cmp(&a.rank).then(a.node.id.cmp(&b.node.id)));
+    hits.truncate(limit.max(0) as usize);
+    hits
+}
+
+/// The nodes whose names are at least `threshold` similar to `name`, most
+/// similar first.
+pub(super) fn similar(
+    nodes: impl IntoIterator<Item = DbNode>,
+    name: &str,
+    threshold: f32,
+    limit: i64,
+) -> Vec<DbSimilarNode> {
+    let mut similar: Vec<DbSimilarNode> = nodes
+        .into_iter()
+        .map(|node| DbSimilarNode {
+            similarity: similarity(&node.name, name),
+            node,
+        })
+        .filter(|similar| similar.similarity >= threshold)
+        .collect();
+    similar.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then(a.node.id.cmp(&b.node.id))
+    });
+    similar.truncate(limit.max(0) as usize);
+    similar
+}
+
+/// The pairs of `nodes` whose names are at least `threshold` similar, most
+/// similar first and with the older node on the left.
+pub(super) fn duplicates(
+    mut nodes: Vec<DbNode>,
+    threshold: f32,
+    limit: i64,
+) -> Vec<DbDuplicateCandidate> {
+    nodes.sort_by_key(|node| node.id);
+    let mut candidates = Vec::new();
+    for (i, left) in nodes.iter().enumerate() {
+        for right in &nodes[i + 1..] {
+            let similarity = similarity(&left.name, &right.name);
+            if similarity >= threshold {
+                candidates.push(DbDuplicateCandidate {
+                    left: left.clone(),
+                    right: right.clone(),
+                    similarity,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then(a.left.id.cmp(&b.left.id))
+            .then(a.right.id.cmp(&b.right.id))
+    });
+    candidates.truncate(limit.max(0) as usize);
+    candidates
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// The trigrams of the words of `text` as `pg_trgm` extracts them.
+fn trigrams(text: &str) -> HashSet<String> {
+    let mut trigrams = HashSet::new();
+    for word in words(text) {
+        let padded: Vec<char> = format!("  {word} ").chars().collect();
+        trigrams.extend(padded.windows(3).map(|window| window.iter().collect()));
+    }
+    trigrams
+}
+
+/// The share of trigrams two texts have in common, like `pg_trgm`'s
+/// `similarity`.
+fn similarity(left: &str, right: &str) -> f32 {
+    let (left, right) = (trigrams(left), trigrams(right));
+    let shared = left.intersection(&right).count();
+    let all = left.len() + right.len() - shared;
+    if all == 0 {
+        0.0
+    } else {
+        shared as f32 / all as f32
+    }
+}
+
+/// The rank of an `x-search-weight` annotation, `fallback` when there is none
+/// and `None` when the text is excluded from search.
+fn search_weight(annotation: Option<&Value>, fallback: f32) -> Option<f32> {
+    match annotation.and_then(Value::as_str) {
+        _ if annotation == Some(&Value::Bool(false)) => None,
+        Some("A") => Some(1.0),
+        Some("B") => Some(0.4),
+        Some("C") => Some(0.2),
+        Some("D") => Some(0.1),
+        _ => Some(fallback),
+    }
+}
+
+/// Rank `node` for `terms`, all of which must start one of its words.
+fn search_hit(schema_json: Option<&Value>, node: DbNode, terms: &[String]) -> Option<DbSearchHit> {
+    let annotation = |pointer: &str| schema_json.and_then(|schema| schema.pointer(pointer));
+    let mut fields = vec![(
+        node.name.clone(),
+        search_weight(annotation("/x-search-weight"), 1.0),
+    )];
+    if let Some(data) = node.data.as_object() {
+        for (key, value) in data {
+            if let (Some(text), false) = (value.as_str(), key == "name") {
+                let pointer = format!(
+                    "/properties/{}/x-search-weight",
+                    key.replace('~', "~0").replace('/', "~1")
+                );
+                fields.push((text.to_string(), search_weight(annotation(&pointer), 0.2)));
+            }
+        }
+    }
+
+    let matches = |word: &str| terms.iter().any(|term| word.starts_with(term.as_str()));
+    let mut rank = 0.0;
+    let mut found = HashSet::new();
+    for (text, weight) in &fields {
+        let Some(weight) = weight else { continue };
+        for word in words(text) {
+            for term in terms.iter().filter(|term| word.starts_with(term.as_str())) {
+                found.insert(term.clone());
+                rank += weight;
+            }
+        }
+    }
+    if found.len() < terms.len() {
+        return None;
+    }
+
+    let snippet = fields
+        .iter()
+        .map(|(text, _)| {
+            text.split(' ')
+                .map(|word| {
+                    if words(word).any(|word| matches(&word)) {
+                        format!("<mark>{word}</mark>")
+                    } else {
+                        word.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(DbSearchHit {
+        node,
+        rank,
+        snippet,
+    })
+}